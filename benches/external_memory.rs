@@ -1,9 +1,29 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
 use std::hint::black_box;
 use hpc_rs::external_memory::cache_oblivious::{
     matrix_transpose, matrix_transpose_cache_oblivious, matrix_transpose_cache_oblivious_fast,
-    Matrix,
+    matrix_transpose_in_place, Matrix,
 };
+use hpc_rs::external_memory::convolution::{ConvolutionMode, convolve_naive};
+use hpc_rs::external_memory::dynamic_programming::{
+    edit_distance_table, edit_distance_table_naive, floyd_warshall, floyd_warshall_naive,
+    lcs_table, lcs_table_naive,
+};
+use hpc_rs::external_memory::eviction_policies::{
+    ArcCache, CachePolicy, Clock, Fifo, Lfu, Lru, RandomEviction, belady, replay, zipf_trace,
+};
+use hpc_rs::external_memory::external_sorting::{
+    bitonic_merge_i32, branchless_merge, heap_merge, merge, merge_into, ExternalSorter,
+    KWayMerger,
+};
+use hpc_rs::external_memory::funnelsort::funnelsort;
+use hpc_rs::external_memory::gemm::{gemm, gemm_scalar};
+use hpc_rs::external_memory::matrix_layout::{Morton, MortonMatrix};
+use hpc_rs::external_memory::matrix_view::MatrixRef;
+use hpc_rs::external_memory::parallel::Parallelism;
+use hpc_rs::external_memory::record::RecordWriter;
+use hpc_rs::external_memory::strassen::DEFAULT_CUTOFF;
+use hpc_rs::external_memory::veb_tree::VebTree;
 use rand::distr::{Distribution, Uniform};
 use rand::rngs::StdRng;
 use rand::SeedableRng;
@@ -49,9 +69,55 @@ fn matrix_transpose_benchmarks(c: &mut Criterion) {
             let _transposed_matrix = matrix.transpose();
         })
     });
+
+    // rectangular, N x N / 2
+    const M: usize = N / 2;
+    group.bench_function("matrix_transpose_rectangular_struct", |b| {
+        let mut rng = StdRng::seed_from_u64(0);
+        let uniform = Uniform::new(0., 1.).unwrap();
+        let matrix = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * M).collect(), N, M);
+        b.iter(|| {
+            let _transposed_matrix = black_box(&matrix).transpose();
+        })
+    });
+    group.bench_function("matrix_transpose_in_place", |b| {
+        let mut rng = StdRng::seed_from_u64(0);
+        let uniform = Uniform::new(0., 1.).unwrap();
+        let mut matrix: Vec<f64> = uniform.sample_iter(&mut rng).take(N * M).collect();
+        b.iter(|| {
+            matrix_transpose_in_place(black_box(&mut matrix), black_box(N), black_box(M));
+        })
+    });
+    group.bench_function("matrix_transpose_in_place_struct", |b| {
+        let mut rng = StdRng::seed_from_u64(0);
+        let uniform = Uniform::new(0., 1.).unwrap();
+        let mut matrix =
+            Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * M).collect(), N, M);
+        b.iter(|| {
+            black_box(&mut matrix).transpose_in_place();
+        })
+    });
     group.finish();
 }
 
+/// hides that a matrix is in memory, so `mul` runs the cache-oblivious recursion
+/// instead of the packed gemm
+struct Unstrided<'a, T>(&'a Matrix<T>);
+
+impl<T> MatrixRef<T> for Unstrided<'_, T> {
+    fn rows(&self) -> usize {
+        self.0.rows()
+    }
+
+    fn cols(&self) -> usize {
+        self.0.cols()
+    }
+
+    fn entry(&self, i: usize, j: usize) -> &T {
+        self.0.get(i, j)
+    }
+}
+
 fn matrix_mul_benchmarks(c: &mut Criterion) {
     let mut group = c.benchmark_group("Matrix Multiplication");
     const N: usize = 1024;
@@ -80,12 +146,413 @@ fn matrix_mul_benchmarks(c: &mut Criterion) {
         let uniform = Uniform::new(0., 1.).unwrap();
         let matrix_a = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
         let matrix_b = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+        b.iter(|| {
+            let _result = matrix_a.mul(black_box(&Unstrided(&matrix_b)));
+        });
+    });
+    group.bench_function("gemm_f64", |b| {
+        let mut rng = StdRng::seed_from_u64(0);
+        let uniform = Uniform::new(0., 1.).unwrap();
+        let matrix_a = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+        let matrix_b = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+        b.iter(|| {
+            let mut result = Matrix::<f64>::new(N, N);
+            gemm(
+                matrix_a.view(),
+                black_box(matrix_b.view()),
+                result.view_mut(),
+            );
+        });
+    });
+    group.bench_function("gemm_scalar", |b| {
+        let mut rng = StdRng::seed_from_u64(0);
+        let uniform = Uniform::new(0., 1.).unwrap();
+        let matrix_a = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+        let matrix_b = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+        b.iter(|| {
+            let mut result = Matrix::<f64>::new(N, N);
+            gemm_scalar(matrix_a.view(), black_box(matrix_b.view()), result.view_mut());
+        });
+    });
+    group.bench_function("gemm_f32", |b| {
+        let mut rng = StdRng::seed_from_u64(0);
+        let uniform = Uniform::new(0f32, 1.).unwrap();
+        let matrix_a = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+        let matrix_b = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+        b.iter(|| {
+            let _result = matrix_a.mul(black_box(&matrix_b));
+        });
+    });
+    group.finish();
+}
+
+fn matrix_convolution_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Matrix Convolution");
+    group.sample_size(10);
+
+    // a wide input, and a kernel too large for the rows of both to stay in L1
+    let mut rng = StdRng::seed_from_u64(0);
+    let uniform = Uniform::new(0., 1.).unwrap();
+    let (n, m, k) = (256, 2048, 24);
+    let input = Matrix::from_vec(uniform.sample_iter(&mut rng).take(n * m).collect(), n, m);
+    let kernel = Matrix::from_vec(uniform.sample_iter(&mut rng).take(k * k).collect(), k, k);
+    group.bench_function("convolve_naive", |b| {
+        b.iter(|| {
+            let _result = convolve_naive(&input, black_box(&kernel), ConvolutionMode::Same);
+        });
+    });
+    group.bench_function("convolve_cache_oblivious", |b| {
+        b.iter(|| {
+            let _result = input.convolve(black_box(&kernel), ConvolutionMode::Same);
+        });
+    });
+    group.finish();
+}
+
+fn dynamic_programming_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Dynamic Programming");
+    group.sample_size(10);
+
+    const N: usize = 1024;
+    let mut rng = StdRng::seed_from_u64(0);
+    let uniform = Uniform::new(1., 100.).unwrap();
+    let graph = Matrix::<f64>::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+    group.bench_function("floyd_warshall_naive", |b| {
+        b.iter(|| {
+            let mut dist = graph.clone();
+            floyd_warshall_naive(black_box(&mut dist));
+        });
+    });
+    group.bench_function("floyd_warshall_cache_oblivious", |b| {
+        b.iter(|| {
+            let mut dist = graph.clone();
+            floyd_warshall(black_box(&mut dist));
+        });
+    });
+
+    // a long and a short string, so the rows of the table are far apart
+    let letters = Uniform::new(0u8, 4).unwrap();
+    let a: Vec<u8> = letters.sample_iter(&mut rng).take(1 << 10).collect();
+    let b: Vec<u8> = letters.sample_iter(&mut rng).take(1 << 16).collect();
+    group.bench_function("lcs_table_naive", |bench| {
+        bench.iter(|| lcs_table_naive(black_box(&a), &b));
+    });
+    group.bench_function("lcs_table_cache_oblivious", |bench| {
+        bench.iter(|| lcs_table(black_box(&a), &b));
+    });
+    group.bench_function("edit_distance_table_naive", |bench| {
+        bench.iter(|| edit_distance_table_naive(black_box(&a), &b));
+    });
+    group.bench_function("edit_distance_table_cache_oblivious", |bench| {
+        bench.iter(|| edit_distance_table(black_box(&a), &b));
+    });
+    group.finish();
+}
+
+fn fast_matrix_mul_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Fast Matrix Multiplication");
+    const N: usize = 1024;
+    group.sample_size(10);
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let uniform = Uniform::new(0., 1.).unwrap();
+    let matrix_a = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+    let matrix_b = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+    group.bench_function("mul", |b| {
         b.iter(|| {
             let _result = matrix_a.mul(black_box(&matrix_b));
         });
     });
+    for cutoff in [128, DEFAULT_CUTOFF] {
+        group.bench_function(format!("strassen/{cutoff}"), |b| {
+            b.iter(|| {
+                let _result = matrix_a.strassen_mul(black_box(&matrix_b), cutoff);
+            });
+        });
+        group.bench_function(format!("winograd/{cutoff}"), |b| {
+            b.iter(|| {
+                let _result = matrix_a.winograd_mul(black_box(&matrix_b), cutoff);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn parallel_matrix_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Parallel Matrix");
+    const N: usize = 1024;
+    group.sample_size(10);
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let uniform = Uniform::new(0., 1.).unwrap();
+    let matrix_a = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+    let matrix_b = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+    let max_threads = Parallelism::available().threads().max(2);
+    for threads in (0..).map(|i| 1 << i).take_while(|&t| t <= max_threads) {
+        let parallelism = Parallelism::new(threads);
+        group.bench_function(format!("par_mul/{threads}"), |b| {
+            b.iter(|| {
+                let _result = matrix_a.par_mul(black_box(&matrix_b), parallelism);
+            });
+        });
+        group.bench_function(format!("par_transpose/{threads}"), |b| {
+            b.iter(|| {
+                let _result = black_box(&matrix_a).par_transpose(parallelism);
+            });
+        });
+    }
+    group.finish();
+}
+
+fn matrix_layout_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Matrix Layouts");
+    group.sample_size(10);
+    const N: usize = 1024;
+    const TILE: usize = 32;
+
+    let mut rng = StdRng::seed_from_u64(0);
+    let uniform = Uniform::new(0., 1.).unwrap();
+    let matrix = Matrix::from_vec(uniform.sample_iter(&mut rng).take(N * N).collect(), N, N);
+    let morton = matrix.to_morton();
+    let tiled = matrix.to_tiled(TILE);
+    group.bench_function("to_morton", |b| b.iter(|| black_box(&matrix).to_morton()));
+    group.bench_function("to_morton portable", |b| {
+        b.iter(|| MortonMatrix::from_matrix(black_box(&matrix), Morton::new(N, N).portable()))
+    });
+    group.bench_function("morton to_matrix", |b| {
+        b.iter(|| black_box(&morton).to_matrix())
+    });
+    group.bench_function("to_tiled", |b| b.iter(|| black_box(&matrix).to_tiled(TILE)));
+    group.bench_function("row-major transpose", |b| {
+        b.iter(|| black_box(&matrix).transpose())
+    });
+    group.bench_function("morton transpose", |b| {
+        b.iter(|| black_box(&morton).transpose())
+    });
+    group.bench_function("tiled transpose", |b| {
+        b.iter(|| black_box(&tiled).transpose())
+    });
+
+    // i64 so that the row-major product is the generic recursion, not the packed GEMM
+    const M: usize = 512;
+    let uniform = Uniform::new(-100, 100).unwrap();
+    let a = Matrix::from_vec(uniform.sample_iter(&mut rng).take(M * M).collect(), M, M);
+    let b_matrix = Matrix::from_vec(uniform.sample_iter(&mut rng).take(M * M).collect(), M, M);
+    group.bench_function("row-major mul", |b| b.iter(|| black_box(&a).mul(&b_matrix)));
+    let (a_morton, b_morton) = (a.to_morton(), b_matrix.to_morton());
+    group.bench_function("morton mul", |b| {
+        b.iter(|| black_box(&a_morton).mul(&b_morton))
+    });
+    let (a_tiled, b_tiled) = (a.to_tiled(TILE), b_matrix.to_tiled(TILE));
+    group.bench_function("tiled mul", |b| {
+        b.iter(|| black_box(&a_tiled).mul(&b_tiled))
+    });
+    group.finish();
+}
+
+fn search_tree_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Search Trees");
+    const QUERIES: usize = 1 << 12;
+
+    // u32 keys from 4 KiB (L1) to 128 MiB (main memory)
+    for log_n in [10, 14, 18, 22, 25] {
+        let n = 1usize << log_n;
+        let mut rng = StdRng::seed_from_u64(0);
+        let uniform = Uniform::new(0, u32::MAX).unwrap();
+        let mut keys: Vec<u32> = uniform.sample_iter(&mut rng).take(n).collect();
+        keys.sort_unstable();
+        let queries: Vec<u32> = uniform.sample_iter(&mut rng).take(QUERIES).collect();
+        let tree = VebTree::from_sorted(&keys);
+
+        group.bench_function(format!("partition_point/2^{log_n}"), |b| {
+            b.iter(|| {
+                for &x in &queries {
+                    black_box(keys.partition_point(|&k| k < black_box(x)));
+                }
+            });
+        });
+        group.bench_function(format!("veb_tree/2^{log_n}"), |b| {
+            b.iter(|| {
+                for &x in &queries {
+                    black_box(tree.lower_bound(&black_box(x)));
+                }
+            });
+        });
+    }
+    group.finish();
+}
+
+fn k_way_merge_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("K-Way Merge");
+    const N: usize = 1 << 20;
+
+    for k in [4, 16, 64, 256] {
+        let mut rng = StdRng::seed_from_u64(0);
+        let uniform = Uniform::new(0, u32::MAX).unwrap();
+        let runs: Vec<Vec<u32>> = (0..k)
+            .map(|_| {
+                let mut run: Vec<u32> = uniform.sample_iter(&mut rng).take(N / k).collect();
+                run.sort_unstable();
+                run
+            })
+            .collect();
+        let slices: Vec<&[u32]> = runs.iter().map(|run| run.as_slice()).collect();
+
+        group.bench_function(format!("pairwise merge k={k}"), |b| {
+            b.iter(|| {
+                let mut merged: Vec<Vec<u32>> = runs.clone();
+                while merged.len() > 1 {
+                    merged = merged
+                        .chunks(2)
+                        .map(|pair| match pair {
+                            [left, right] => merge(left, right),
+                            [single] => single.clone(),
+                            _ => unreachable!(),
+                        })
+                        .collect();
+                }
+                black_box(merged)
+            })
+        });
+        group.bench_function(format!("binary heap merge k={k}"), |b| {
+            b.iter(|| heap_merge(black_box(&slices)))
+        });
+        group.bench_function(format!("loser tree merge k={k}"), |b| {
+            b.iter(|| KWayMerger::from_slices(black_box(&slices)).collect::<Vec<_>>())
+        });
+        let mut output = vec![0; N];
+        group.bench_function(format!("loser tree merge_into k={k}"), |b| {
+            b.iter(|| {
+                KWayMerger::new(black_box(&slices).iter().map(|run| run.iter().copied()))
+                    .merge_into(&mut output)
+            })
+        });
+    }
+    group.finish();
+}
+
+fn sorting_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Sorting");
+    group.sample_size(10);
+    let dir = std::env::temp_dir();
+    let (input, output) = (dir.join("hpc-rs-bench-sort-in"), dir.join("hpc-rs-bench-sort-out"));
+
+    // u64 keys from 512 KiB up to 512 MiB, past the last-level cache
+    for log_n in [16, 20, 24, 26] {
+        let mut rng = StdRng::seed_from_u64(0);
+        let uniform = Uniform::new(0, u64::MAX).unwrap();
+        let data: Vec<u64> = uniform.sample_iter(&mut rng).take(1 << log_n).collect();
+
+        group.bench_function(format!("sort_unstable/2^{log_n}"), |b| {
+            b.iter_batched(
+                || data.clone(),
+                |mut data| data.sort_unstable(),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_function(format!("funnelsort/2^{log_n}"), |b| {
+            b.iter_batched(
+                || data.clone(),
+                |mut data| funnelsort(&mut data),
+                BatchSize::LargeInput,
+            )
+        });
+
+        let mut writer = RecordWriter::new(std::fs::File::create(&input).unwrap());
+        writer.write_records(&data).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let sorter = ExternalSorter::new(16 << 20);
+        group.bench_function(format!("external sort 16 MiB/2^{log_n}"), |b| {
+            b.iter(|| sorter.sort_file::<u64>(&input, &output).unwrap())
+        });
+    }
+    let _ = std::fs::remove_file(&input);
+    let _ = std::fs::remove_file(&output);
+    group.finish();
+}
+
+fn two_way_merge_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Two-Way Merge");
+    const N: usize = 1 << 20;
+    let mut rng = StdRng::seed_from_u64(0);
+    let uniform = Uniform::new(i32::MIN, i32::MAX).unwrap();
+    let mut random_left: Vec<i32> = uniform.sample_iter(&mut rng).take(N).collect();
+    let mut random_right: Vec<i32> = uniform.sample_iter(&mut rng).take(N).collect();
+    random_left.sort_unstable();
+    random_right.sort_unstable();
+
+    // disjoint: the comparison is always true, so it is perfectly predicted
+    // alternating: a short periodic pattern the predictor can learn
+    // random: the outcome is a coin flip, the worst case for a branchy merge
+    let inputs = [
+        (
+            "disjoint",
+            (0..N as i32).collect(),
+            (N as i32..2 * N as i32).collect(),
+        ),
+        (
+            "alternating",
+            (0..N as i32).map(|x| 2 * x).collect(),
+            (0..N as i32).map(|x| 2 * x + 1).collect(),
+        ),
+        ("random", random_left, random_right),
+    ];
+    let mut output = vec![0; 2 * N];
+    for (name, left, right) in &inputs {
+        let (left, right): (&Vec<i32>, &Vec<i32>) = (left, right);
+        group.bench_function(format!("merge {name}"), |b| {
+            b.iter(|| merge(black_box(left), black_box(right)))
+        });
+        group.bench_function(format!("merge_into {name}"), |b| {
+            b.iter(|| merge_into(black_box(left), black_box(right), &mut output))
+        });
+        group.bench_function(format!("branchless_merge {name}"), |b| {
+            b.iter(|| branchless_merge(black_box(left), black_box(right), &mut output))
+        });
+        group.bench_function(format!("bitonic_merge_i32 {name}"), |b| {
+            b.iter(|| bitonic_merge_i32(black_box(left), black_box(right), &mut output))
+        });
+    }
+    group.finish();
+}
+
+fn eviction_policy_benchmarks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Eviction Policies");
+    const CAPACITY: usize = 4096;
+    let trace = zipf_trace(1 << 16, 1 << 20, 0.9, 0);
+
+    type Policy = fn() -> Box<dyn CachePolicy<u64>>;
+    let policies: [(&str, Policy); 6] = [
+        ("LRU", || Box::new(Lru::new(CAPACITY))),
+        ("LFU", || Box::new(Lfu::new(CAPACITY))),
+        ("FIFO", || Box::new(Fifo::new(CAPACITY))),
+        ("CLOCK", || Box::new(Clock::new(CAPACITY))),
+        ("ARC", || Box::new(ArcCache::new(CAPACITY))),
+        ("random", || Box::new(RandomEviction::new(CAPACITY, 0))),
+    ];
+    for (name, policy) in policies {
+        group.bench_function(name, |b| {
+            b.iter(|| replay(policy().as_mut(), black_box(&trace)))
+        });
+    }
+    group.bench_function("Belady", |b| b.iter(|| belady(black_box(&trace), CAPACITY)));
     group.finish();
 }
 
-criterion_group!(benches, matrix_transpose_benchmarks, matrix_mul_benchmarks);
+criterion_group!(
+    benches,
+    matrix_transpose_benchmarks,
+    matrix_mul_benchmarks,
+    matrix_convolution_benchmarks,
+    dynamic_programming_benchmarks,
+    fast_matrix_mul_benchmarks,
+    parallel_matrix_benchmarks,
+    matrix_layout_benchmarks,
+    search_tree_benchmarks,
+    k_way_merge_benchmarks,
+    sorting_benchmarks,
+    two_way_merge_benchmarks,
+    eviction_policy_benchmarks
+);
 criterion_main!(benches);
@@ -72,7 +72,195 @@ fn bench_sum(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_linear_recurrence(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Linear Recurrence");
+    let montgomery = Montgomery::new(1_000_000_007);
+    group.bench_function("fibonacci matrix 10^18", |b| {
+        b.iter(|| {
+            fibonacci_matrix(
+                black_box(1_000_000_000_000_000_000),
+                black_box(1_000_000_007),
+            )
+        })
+    });
+    group.bench_function("fibonacci fast doubling 10^18", |b| {
+        b.iter(|| {
+            fibonacci_fast_doubling(black_box(1_000_000_000_000_000_000), black_box(&montgomery))
+        })
+    });
+
+    let coeffs: Vec<u32> = (1..=16).collect();
+    let init: Vec<u32> = (0..16).collect();
+    group.bench_function("linear recurrence matrix k=16", |b| {
+        b.iter(|| {
+            linear_recurrence_matrix(
+                black_box(&coeffs),
+                black_box(&init),
+                black_box(1_000_000_000_000),
+                1_000_000_007,
+            )
+        })
+    });
+    group.bench_function("kitamasa k=16", |b| {
+        b.iter(|| {
+            kitamasa(
+                black_box(&coeffs),
+                black_box(&init),
+                black_box(1_000_000_000_000),
+                &montgomery,
+            )
+        })
+    });
+    group.finish();
+}
+
+fn bench_wide_montgomery(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Wide Montgomery");
+    let montgomery = Montgomery::new(1_000_000_007);
+    let (a, b) = (montgomery.from(123456789), montgomery.from(987654321));
+    group.bench_function("montgomery mul u32", |bencher| {
+        bencher.iter(|| montgomery.mul(black_box(a), black_box(b)))
+    });
+
+    let p256 = U256::from_hex("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F");
+    let montgomery_256 = WideMontgomery::new(p256);
+    let a = montgomery_256.from(&U256::from_hex(
+        "1234567890ABCDEF1234567890ABCDEF1234567890ABCDEF",
+    ));
+    let b = montgomery_256.from(&U256::from_hex(
+        "FEDCBA0987654321FEDCBA0987654321FEDCBA0987654321",
+    ));
+    group.bench_function("montgomery mul U256", |bencher| {
+        bencher.iter(|| montgomery_256.mul(black_box(&a), black_box(&b)))
+    });
+    group.bench_function("pow_mod U256", |bencher| {
+        bencher.iter(|| montgomery_256.pow_mod(black_box(&U256::from_u64(2)), black_box(&p256)))
+    });
+    group.bench_function("inv_mod U256", |bencher| {
+        bencher.iter(|| montgomery_256.inv_mod(black_box(&U256::from_u64(2))))
+    });
+
+    let p512 = U512::MAX.wrapping_sub(&U512::from_u64(568)); // 2^512 - 569 is prime
+    let montgomery_512 = WideMontgomery::new(p512);
+    let a = montgomery_512.from(&U512::from_limbs([1, 2, 3, 4, 5, 6, 7, 8]));
+    let b = montgomery_512.from(&U512::from_limbs([8, 7, 6, 5, 4, 3, 2, 1]));
+    group.bench_function("montgomery mul U512", |bencher| {
+        bencher.iter(|| montgomery_512.mul(black_box(&a), black_box(&b)))
+    });
+    group.finish();
+}
+
+fn bench_exponentiation_variants(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Exponentiation Variants");
+    const P: u64 = 1_000_000_007;
+    let montgomery = Montgomery::new(P as i32);
+    let barrett = Barrett::new(P as u32);
+    let base = montgomery.from(564400443);
+
+    group.bench_function("binpow_iter", |b| {
+        b.iter(|| binpow_iter::<P>(black_box(564400443), black_box(P - 2)))
+    });
+    group.bench_function("binpow_iter_with_mod", |b| {
+        b.iter(|| binpow_iter_with_mod(black_box(564400443), black_box(P - 2), black_box(P as u32)))
+    });
+    group.bench_function("inverse_with_montgomery", |b| {
+        b.iter(|| inverse_with_montgomery(black_box(564400443), black_box(&montgomery)))
+    });
+    group.bench_function("pow_binary barrett", |b| {
+        b.iter(|| pow_binary(&barrett, black_box(&564400443), black_box(&[P - 2])))
+    });
+    group.bench_function("pow_binary montgomery", |b| {
+        b.iter(|| pow_binary(&montgomery, black_box(&base), black_box(&[P - 2])))
+    });
+    group.bench_function("pow_k_ary montgomery (w=4)", |b| {
+        b.iter(|| pow_k_ary(&montgomery, black_box(&base), black_box(&[P - 2]), 4))
+    });
+    group.bench_function("pow_sliding_window montgomery (w=4)", |b| {
+        b.iter(|| pow_sliding_window(&montgomery, black_box(&base), black_box(&[P - 2]), 4))
+    });
+    let comb = FixedBaseComb::new(&montgomery, &base, 30, 6);
+    group.bench_function("fixed base comb montgomery (h=6)", |b| {
+        b.iter(|| comb.pow(&montgomery, black_box(&[P - 2])))
+    });
+    let other = montgomery.from(2);
+    group.bench_function("two pow_binary montgomery", |b| {
+        b.iter(|| {
+            montgomery.mul(
+                pow_binary(&montgomery, black_box(&base), black_box(&[P - 2])),
+                pow_binary(&montgomery, black_box(&other), black_box(&[P - 3])),
+            )
+        })
+    });
+    group.bench_function("multi_pow montgomery", |b| {
+        b.iter(|| {
+            multi_pow(
+                &montgomery,
+                black_box(&base),
+                black_box(&[P - 2]),
+                black_box(&other),
+                black_box(&[P - 3]),
+            )
+        })
+    });
+
+    let p256 = U256::from_hex("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F");
+    let montgomery_256 = WideMontgomery::new(p256);
+    let g = montgomery_256.from(&U256::from_u64(7));
+    let exp = p256.wrapping_sub(&U256::from_u64(2));
+    group.bench_function("WideMontgomery::pow U256", |b| {
+        b.iter(|| montgomery_256.pow(black_box(&g), black_box(&exp)))
+    });
+    group.bench_function("pow_binary U256", |b| {
+        b.iter(|| pow_binary(&montgomery_256, black_box(&g), black_box(exp.limbs())))
+    });
+    group.bench_function("pow_k_ary U256 (w=4)", |b| {
+        b.iter(|| pow_k_ary(&montgomery_256, black_box(&g), black_box(exp.limbs()), 4))
+    });
+    group.bench_function("pow_sliding_window U256 (w=5)", |b| {
+        b.iter(|| pow_sliding_window(&montgomery_256, black_box(&g), black_box(exp.limbs()), 5))
+    });
+    let comb_256 = FixedBaseComb::new(&montgomery_256, &g, 256, 8);
+    group.bench_function("fixed base comb U256 (h=8)", |b| {
+        b.iter(|| comb_256.pow(&montgomery_256, black_box(exp.limbs())))
+    });
+    group.finish();
+}
+
+fn bench_batch_pow(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Batch Exponentiation");
+    const N: usize = 1 << 16;
+    let montgomery = Montgomery::new(1_000_000_007);
+    let a: Vec<u32> = (0..N as u32)
+        .map(|i| i.wrapping_mul(2654435761) % 1_000_000_007)
+        .collect();
+
+    group.bench_function("inverse_with_montgomery loop", |b| {
+        b.iter(|| {
+            black_box(&a)
+                .iter()
+                .map(|&x| inverse_with_montgomery(x as i32, &montgomery) as u32)
+                .collect::<Vec<_>>()
+        })
+    });
+    group.bench_function("batch_pow_scalar", |b| {
+        b.iter(|| batch_pow_scalar(black_box(&a), black_box(1_000_000_005), &montgomery))
+    });
+    group.bench_function("batch_pow avx2", |b| {
+        b.iter(|| batch_pow(black_box(&a), black_box(1_000_000_005), &montgomery))
+    });
+    group.finish();
+}
+
 // Criterion group for all benchmarks
-criterion_group!(benches, bench_binary_exponentiation, bench_exgcd, bench_sum);
+criterion_group!(
+    benches,
+    bench_binary_exponentiation,
+    bench_exgcd,
+    bench_sum,
+    bench_linear_recurrence,
+    bench_wide_montgomery,
+    bench_exponentiation_variants,
+    bench_batch_pow
+);
 // Criterion main function
 criterion_main!(benches);
@@ -17,6 +17,24 @@ pub const fn lemire_division(n: u64, magic_number_x: u64) -> u64 {
     ((n as u128 * magic_number_x as u128) >> 64) as u64
 }
 
+#[inline]
+pub const fn cal_barrett_reduction_magic_number(x: u32) -> u64 {
+    u64::MAX / x as u64 // floor(2^64 / n)
+}
+
+/// 取模运算 n % x，n 可以取遍整个 u64
+#[inline]
+pub const fn barrett_reduction_mod(n: u64, x: u32, magic_number_x: u64) -> u32 {
+    let q = ((n as u128 * magic_number_x as u128) >> 64) as u64;
+    // q is floor(n / x) or floor(n / x) - 1
+    let r = n - q * x as u64;
+    if r >= x as u64 {
+        (r - x as u64) as u32
+    } else {
+        r as u32
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -36,4 +54,19 @@ mod tests {
         assert_eq!(lemire_reduction_mod(n, x, magic_number_x), 37);
         assert_eq!(lemire_division(n, magic_number_x), 81);
     }
+
+    #[test]
+    fn test_barrett_reduction() {
+        let x = 1_000_000_007;
+        let magic_number_x = cal_barrett_reduction_magic_number(x);
+        let sqr_x = x as u64 * x as u64;
+        for n in [0, 1, 10, x as u64 - 1, x as u64, sqr_x, u64::MAX - 12345, u64::MAX] {
+            assert_eq!(
+                barrett_reduction_mod(n, x, magic_number_x) as u64,
+                n % x as u64
+            );
+        }
+        let magic_number_1 = cal_barrett_reduction_magic_number(1);
+        assert_eq!(barrett_reduction_mod(u64::MAX, 1, magic_number_1), 0);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,6 @@
+use hpc_rs::caches::cache_simulator;
+
+fn main() {
+    let report = cache_simulator::cache_simulation_report();
+    println!("{}", report);
+}
@@ -0,0 +1,22 @@
+use std::fs::File;
+use std::io::BufReader;
+
+use hpc_rs::external_memory::eviction_policies::{self, EvictionReport};
+
+/// replays the key trace given as the first argument (one key per line),
+/// or a Zipf distributed trace without one
+fn main() -> std::io::Result<()> {
+    let capacities = [16, 64, 256, 1024, 4096];
+    let report = match std::env::args().nth(1) {
+        Some(path) => {
+            let trace = eviction_policies::read_key_trace(BufReader::new(File::open(path)?))?;
+            EvictionReport::replay(&trace, &capacities)
+        }
+        None => {
+            let trace = eviction_policies::zipf_trace(100_000, 1_000_000, 0.9, 0);
+            EvictionReport::replay(&trace, &capacities)
+        }
+    };
+    println!("{}", report);
+    Ok(())
+}
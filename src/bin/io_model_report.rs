@@ -0,0 +1,6 @@
+use hpc_rs::external_memory::block_device;
+
+fn main() {
+    let report = block_device::io_model_report();
+    println!("{}", report);
+}
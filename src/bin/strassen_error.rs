@@ -0,0 +1,9 @@
+use hpc_rs::external_memory::strassen::{DEFAULT_CUTOFF, ErrorReport};
+
+/// the error of the fast products next to simple_mul, for a few cutoffs
+fn main() {
+    let sizes = [64, 128, 256, 512, 1024];
+    for cutoff in [16, 64, DEFAULT_CUTOFF] {
+        println!("{}", ErrorReport::measure(&sizes, cutoff, 0));
+    }
+}
@@ -0,0 +1,583 @@
+//! A set-associative cache simulator for memory access traces.
+//!
+//! The traces come from the ordinary kernels run on a [`TracedArray`], which records
+//! the address of every element it hands out, so the simulated program is exactly the
+//! one that runs on real memory.
+
+use std::cell::RefCell;
+use std::fmt;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::external_memory::cache_oblivious::{
+    Matrix, matrix_transpose, matrix_transpose_cache_oblivious,
+    matrix_transpose_cache_oblivious_fast,
+};
+use crate::external_memory::storage::Storage;
+
+/// Which line of a full set is evicted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplacementPolicy {
+    /// least recently used
+    Lru,
+    /// the line that was filled first
+    Fifo,
+    /// tree pseudo-LRU, one bit per internal node as in most L1 caches
+    TreePlru,
+    Random,
+}
+
+/// Geometry of one cache level.
+#[derive(Debug, Clone, Copy)]
+pub struct CacheConfig {
+    size: usize,
+    line_size: usize,
+    associativity: usize,
+    policy: ReplacementPolicy,
+}
+
+impl CacheConfig {
+    /// `size` bytes in lines of `line_size` bytes, grouped in sets of `associativity` lines
+    ///
+    /// The line size and the number of sets must be powers of two. Uses LRU by default.
+    pub fn new(size: usize, line_size: usize, associativity: usize) -> Self {
+        assert!(line_size.is_power_of_two());
+        assert!(associativity > 0 && size.is_multiple_of(line_size * associativity));
+        assert!((size / (line_size * associativity)).is_power_of_two());
+        CacheConfig {
+            size,
+            line_size,
+            associativity,
+            policy: ReplacementPolicy::Lru,
+        }
+    }
+
+    pub fn policy(mut self, policy: ReplacementPolicy) -> Self {
+        assert!(
+            policy != ReplacementPolicy::TreePlru
+                || (self.associativity.is_power_of_two() && self.associativity <= 64),
+            "tree PLRU needs a power of two associativity of at most 64"
+        );
+        self.policy = policy;
+        self
+    }
+
+    #[inline]
+    pub fn sets(&self) -> usize {
+        self.size / (self.line_size * self.associativity)
+    }
+
+    /// addresses this far apart map to the same set
+    #[inline]
+    pub fn critical_stride(&self) -> usize {
+        self.sets() * self.line_size
+    }
+}
+
+/// 32 KiB 8-way L1, 1 MiB 16-way L2 and 8 MiB 16-way L3 with 64-byte lines
+pub fn typical_hierarchy() -> Vec<CacheConfig> {
+    vec![
+        CacheConfig::new(32 << 10, 64, 8).policy(ReplacementPolicy::TreePlru),
+        CacheConfig::new(1 << 20, 64, 16),
+        CacheConfig::new(8 << 20, 64, 16),
+    ]
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LevelStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl LevelStats {
+    #[inline]
+    pub fn accesses(&self) -> u64 {
+        self.hits + self.misses
+    }
+
+    pub fn miss_ratio(&self) -> f64 {
+        self.misses as f64 / self.accesses().max(1) as f64
+    }
+}
+
+const INVALID: u64 = u64::MAX;
+
+struct CacheLevel {
+    config: CacheConfig,
+    set_bits: u32,
+    line_bits: u32,
+    /// `associativity` tags per set
+    tags: Vec<u64>,
+    /// last use for LRU, fill time for FIFO
+    stamps: Vec<u64>,
+    /// tree PLRU bits of every set, bit `node` points to the colder half
+    plru: Vec<u64>,
+    clock: u64,
+    rng: StdRng,
+    stats: LevelStats,
+}
+
+impl CacheLevel {
+    fn new(config: CacheConfig) -> Self {
+        let lines = config.sets() * config.associativity;
+        CacheLevel {
+            config,
+            set_bits: config.sets().trailing_zeros(),
+            line_bits: config.line_size.trailing_zeros(),
+            tags: vec![INVALID; lines],
+            stamps: vec![0; lines],
+            plru: vec![0; config.sets()],
+            clock: 0,
+            rng: StdRng::seed_from_u64(0),
+            stats: LevelStats::default(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.tags.fill(INVALID);
+        self.stamps.fill(0);
+        self.plru.fill(0);
+        // restart the random victims too, so a cleared level replays a trace the same way
+        self.rng = StdRng::seed_from_u64(0);
+        self.stats = LevelStats::default();
+    }
+
+    /// point the PLRU bits on the path to `way` away from it
+    fn plru_touch(&mut self, set: usize, way: usize) {
+        let depth = self.config.associativity.trailing_zeros();
+        let mut node = 1;
+        for level in (0..depth).rev() {
+            let dir = (way >> level) & 1;
+            self.plru[set] = (self.plru[set] & !(1 << node)) | (((dir ^ 1) as u64) << node);
+            node = 2 * node + dir;
+        }
+    }
+
+    fn plru_victim(&self, set: usize) -> usize {
+        let ways = self.config.associativity;
+        let mut node = 1;
+        while node < ways {
+            node = 2 * node + ((self.plru[set] >> node) & 1) as usize;
+        }
+        node - ways
+    }
+
+    /// look `address` up and fill it on a miss, returns whether it hit
+    fn access(&mut self, address: u64) -> bool {
+        self.clock += 1;
+        let line = address >> self.line_bits;
+        let set = (line & ((1 << self.set_bits) - 1)) as usize;
+        let tag = line >> self.set_bits;
+        let ways = self.config.associativity;
+        let base = set * ways;
+
+        if let Some(way) = self.tags[base..base + ways].iter().position(|&t| t == tag) {
+            self.stats.hits += 1;
+            match self.config.policy {
+                ReplacementPolicy::Lru => self.stamps[base + way] = self.clock,
+                ReplacementPolicy::TreePlru => self.plru_touch(set, way),
+                ReplacementPolicy::Fifo | ReplacementPolicy::Random => {}
+            }
+            return true;
+        }
+
+        self.stats.misses += 1;
+        let way = match self.tags[base..base + ways]
+            .iter()
+            .position(|&t| t == INVALID)
+        {
+            Some(way) => way,
+            None => match self.config.policy {
+                ReplacementPolicy::Lru | ReplacementPolicy::Fifo => (0..ways)
+                    .min_by_key(|&way| self.stamps[base + way])
+                    .unwrap(),
+                ReplacementPolicy::TreePlru => self.plru_victim(set),
+                ReplacementPolicy::Random => self.rng.random_range(0..ways),
+            },
+        };
+        self.tags[base + way] = tag;
+        self.stamps[base + way] = self.clock;
+        if self.config.policy == ReplacementPolicy::TreePlru {
+            self.plru_touch(set, way);
+        }
+        false
+    }
+}
+
+/// A hierarchy of cache levels, L1 first.
+///
+/// An access goes down the levels until one hits, and is filled into every level it
+/// missed, so the levels are neither inclusive nor exclusive.
+pub struct CacheSimulator {
+    levels: Vec<CacheLevel>,
+}
+
+impl CacheSimulator {
+    pub fn new(configs: &[CacheConfig]) -> Self {
+        assert!(!configs.is_empty());
+        CacheSimulator {
+            levels: configs
+                .iter()
+                .map(|&config| CacheLevel::new(config))
+                .collect(),
+        }
+    }
+
+    pub fn access(&mut self, address: u64) {
+        for level in &mut self.levels {
+            if level.access(address) {
+                return;
+            }
+        }
+    }
+
+    pub fn run(&mut self, trace: &[u64]) {
+        for &address in trace {
+            self.access(address);
+        }
+    }
+
+    /// hits and misses of every level, L1 first
+    pub fn stats(&self) -> Vec<LevelStats> {
+        self.levels.iter().map(|level| level.stats).collect()
+    }
+
+    /// empty every level and zero the counters
+    pub fn clear(&mut self) {
+        for level in &mut self.levels {
+            level.clear();
+        }
+    }
+}
+
+/// Hands out [`TracedArray`]s at page-aligned addresses and collects their accesses.
+#[derive(Default)]
+pub struct Tracer {
+    trace: RefCell<Vec<u64>>,
+    next_address: RefCell<u64>,
+}
+
+impl Tracer {
+    const PAGE: u64 = 4096;
+
+    pub fn new() -> Self {
+        Tracer::default()
+    }
+
+    /// place `data` at the next free page
+    pub fn array<T>(&self, data: Vec<T>) -> TracedArray<'_, T> {
+        let mut next = self.next_address.borrow_mut();
+        let base = *next;
+        *next = (base + (data.len() * size_of::<T>()) as u64).next_multiple_of(Self::PAGE);
+        TracedArray {
+            tracer: self,
+            base,
+            data,
+        }
+    }
+
+    pub fn into_trace(self) -> Vec<u64> {
+        self.trace.into_inner()
+    }
+
+    #[inline]
+    fn record(&self, address: u64) {
+        self.trace.borrow_mut().push(address);
+    }
+}
+
+/// A [`Storage`] that records the address of every element access in its [`Tracer`].
+pub struct TracedArray<'t, T> {
+    tracer: &'t Tracer,
+    base: u64,
+    data: Vec<T>,
+}
+
+impl<T> TracedArray<'_, T> {
+    #[inline]
+    fn address(&self, i: usize) -> u64 {
+        self.base + (i * size_of::<T>()) as u64
+    }
+
+    pub fn into_vec(self) -> Vec<T> {
+        self.data
+    }
+}
+
+impl<T> Storage<T> for TracedArray<'_, T> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn element(&self, i: usize) -> &T {
+        self.tracer.record(self.address(i));
+        &self.data[i]
+    }
+
+    fn element_mut(&mut self, i: usize) -> &mut T {
+        self.tracer.record(self.address(i));
+        &mut self.data[i]
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.tracer.record(self.address(i));
+        self.tracer.record(self.address(j));
+        self.data.swap(i, j);
+    }
+}
+
+/// address traces of the transposes of an n x n u32 matrix
+pub fn transpose_traces(n: usize) -> Vec<(&'static str, Vec<u64>)> {
+    type Kernel = fn(&mut TracedArray<u32>, usize);
+    let kernels: [(&str, Kernel); 3] = [
+        ("matrix_transpose", |a, n| matrix_transpose(a, n)),
+        ("matrix_transpose_cache_oblivious", |a, n| {
+            matrix_transpose_cache_oblivious(a, n)
+        }),
+        ("matrix_transpose_cache_oblivious_fast", |a, n| {
+            matrix_transpose_cache_oblivious_fast(a, n)
+        }),
+    ];
+    let mut traces = Vec::new();
+    for (name, kernel) in kernels {
+        let tracer = Tracer::new();
+        kernel(&mut tracer.array(vec![0u32; n * n]), n);
+        traces.push((name, tracer.into_trace()));
+    }
+    let tracer = Tracer::new();
+    let matrix = Matrix::from_storage(tracer.array(vec![0u32; n * n]), n, n);
+    let mut target = Matrix::from_storage(tracer.array(vec![0u32; n * n]), n, n);
+    matrix.transpose_into(&mut target);
+    drop((matrix, target));
+    traces.push(("Matrix::transpose_into", tracer.into_trace()));
+    traces
+}
+
+/// address traces of the n x n u32 matrix multiplications
+pub fn matrix_mul_traces(n: usize) -> Vec<(&'static str, Vec<u64>)> {
+    let mut traces = Vec::new();
+    for (name, cache_oblivious) in [
+        ("Matrix::simple_mul_into", false),
+        ("Matrix::mul_into", true),
+    ] {
+        let tracer = Tracer::new();
+        let a = Matrix::from_storage(tracer.array(vec![0u32; n * n]), n, n);
+        let b = Matrix::from_storage(tracer.array(vec![0u32; n * n]), n, n);
+        let mut c = Matrix::from_storage(tracer.array(vec![0u32; n * n]), n, n);
+        if cache_oblivious {
+            a.mul_into(&b, &mut c);
+        } else {
+            a.simple_mul_into(&b, &mut c);
+        }
+        drop((a, b, c));
+        traces.push((name, tracer.into_trace()));
+    }
+    traces
+}
+
+/// `count` addresses `stride` bytes apart, read `rounds` times
+pub fn strided_trace(stride: usize, count: usize, rounds: usize) -> Vec<u64> {
+    (0..rounds)
+        .flat_map(|_| (0..count).map(move |i| (i * stride) as u64))
+        .collect()
+}
+
+/// One simulated trace.
+pub struct CacheReportRow {
+    pub name: String,
+    pub stats: Vec<LevelStats>,
+}
+
+/// Hits and misses per level for a set of traces on the same hierarchy.
+pub struct CacheReport {
+    pub sections: Vec<(String, Vec<CacheReportRow>)>,
+}
+
+impl CacheReport {
+    /// simulate every trace from an empty cache
+    pub fn simulate(configs: &[CacheConfig], traces: &[(&str, Vec<u64>)]) -> Vec<CacheReportRow> {
+        let mut simulator = CacheSimulator::new(configs);
+        traces
+            .iter()
+            .map(|(name, trace)| {
+                simulator.clear();
+                simulator.run(trace);
+                CacheReportRow {
+                    name: name.to_string(),
+                    stats: simulator.stats(),
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for CacheReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (title, rows) in &self.sections {
+            writeln!(f, "{title}")?;
+            for row in rows {
+                write!(f, "  {:<40}", row.name)?;
+                for (level, stats) in row.stats.iter().enumerate() {
+                    write!(
+                        f,
+                        " L{}: {:>9} misses / {:>9} ({:>5.1}%)",
+                        level + 1,
+                        stats.misses,
+                        stats.accesses(),
+                        100. * stats.miss_ratio()
+                    )?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The transposes and multiplications on [`typical_hierarchy`], and the critical
+/// stride of its L1: 16 addresses 4 KiB apart fall into one 8-way set and miss every
+/// time, while one more line of stride spreads them over 16 sets.
+pub fn cache_simulation_report() -> CacheReport {
+    let hierarchy = typical_hierarchy();
+    let l1 = CacheConfig::new(32 << 10, 64, 8);
+    let stride = l1.critical_stride();
+    let strided = [
+        ("stride = critical stride", strided_trace(stride, 16, 100)),
+        (
+            "stride = critical stride + 64",
+            strided_trace(stride + 64, 16, 100),
+        ),
+        (
+            "stride = critical stride / 2",
+            strided_trace(stride / 2, 16, 100),
+        ),
+    ];
+    CacheReport {
+        sections: vec![
+            (
+                "1024 x 1024 u32 transpose".to_string(),
+                CacheReport::simulate(&hierarchy, &transpose_traces(1024)),
+            ),
+            (
+                "1023 x 1023 u32 transpose, the rows are off the critical stride".to_string(),
+                CacheReport::simulate(&hierarchy, &transpose_traces(1023)),
+            ),
+            (
+                "128 x 128 u32 multiply".to_string(),
+                CacheReport::simulate(&hierarchy, &matrix_mul_traces(128)),
+            ),
+            (
+                format!("critical stride of a 32 KiB 8-way L1: {stride} bytes"),
+                CacheReport::simulate(&[l1], &strided),
+            ),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_mapping_and_policies() {
+        // 4 sets of 2 lines of 16 bytes
+        let config = CacheConfig::new(128, 16, 2);
+        assert_eq!(config.critical_stride(), 64);
+        // a, b, a, c, b in one set: LRU evicts b for c, FIFO evicts a
+        let trace = [0, 64, 0, 128, 64];
+        let mut lru = CacheSimulator::new(&[config]);
+        lru.run(&trace);
+        assert_eq!(lru.stats()[0], LevelStats { hits: 1, misses: 4 });
+        let mut fifo = CacheSimulator::new(&[config.policy(ReplacementPolicy::Fifo)]);
+        fifo.run(&trace);
+        assert_eq!(fifo.stats()[0], LevelStats { hits: 2, misses: 3 });
+
+        // addresses in different sets and in the same line do not conflict
+        let mut simulator = CacheSimulator::new(&[config]);
+        simulator.run(&[0, 16, 32, 48, 4, 20, 36, 52]);
+        assert_eq!(simulator.stats()[0], LevelStats { hits: 4, misses: 4 });
+    }
+
+    #[test]
+    fn test_tree_plru() {
+        // one set of 4 lines: after a, b, c, d and a hit on a the victim is in the c, d half
+        let config = CacheConfig::new(64, 16, 4).policy(ReplacementPolicy::TreePlru);
+        let mut simulator = CacheSimulator::new(&[config]);
+        simulator.run(&[0, 16, 32, 48, 0, 64, 0, 16]);
+        assert_eq!(simulator.stats()[0], LevelStats { hits: 3, misses: 5 });
+
+        // every policy keeps a working set that fits
+        for policy in [
+            ReplacementPolicy::Lru,
+            ReplacementPolicy::Fifo,
+            ReplacementPolicy::TreePlru,
+            ReplacementPolicy::Random,
+        ] {
+            let config = CacheConfig::new(1024, 16, 8).policy(policy);
+            let mut simulator = CacheSimulator::new(&[config]);
+            simulator.run(&strided_trace(16, 64, 10));
+            assert_eq!(simulator.stats()[0].misses, 64, "{policy:?}");
+        }
+
+        // random eviction does not depend on the traces run before a clear
+        let config = CacheConfig::new(256, 16, 4).policy(ReplacementPolicy::Random);
+        let trace = strided_trace(16, 24, 20);
+        let mut simulator = CacheSimulator::new(&[config]);
+        simulator.run(&trace);
+        let first = simulator.stats();
+        simulator.run(&strided_trace(48, 100, 3));
+        simulator.clear();
+        simulator.run(&trace);
+        assert_eq!(simulator.stats(), first);
+    }
+
+    #[test]
+    fn test_multiple_levels() {
+        let configs = [CacheConfig::new(64, 16, 1), CacheConfig::new(256, 16, 2)];
+        let mut simulator = CacheSimulator::new(&configs);
+        // 8 lines fit in L2 but not in L1
+        simulator.run(&strided_trace(16, 8, 3));
+        let stats = simulator.stats();
+        assert_eq!(
+            stats[0],
+            LevelStats {
+                hits: 0,
+                misses: 24
+            }
+        );
+        assert_eq!(
+            stats[1],
+            LevelStats {
+                hits: 16,
+                misses: 8
+            }
+        );
+    }
+
+    #[test]
+    fn test_critical_stride() {
+        let l1 = CacheConfig::new(32 << 10, 64, 8);
+        let mut simulator = CacheSimulator::new(&[l1]);
+        simulator.run(&strided_trace(l1.critical_stride(), 16, 10));
+        assert_eq!(simulator.stats()[0].hits, 0);
+        simulator.clear();
+        simulator.run(&strided_trace(l1.critical_stride() + 64, 16, 10));
+        assert_eq!(simulator.stats()[0].misses, 16);
+    }
+
+    #[test]
+    fn test_kernel_traces() {
+        // rows off the critical stride, so the misses are capacity misses
+        let n = 300;
+        let l1 = [CacheConfig::new(8 << 10, 64, 8)];
+        let rows = CacheReport::simulate(&l1, &transpose_traces(n));
+        // every swap touches two elements
+        assert_eq!(rows[0].stats[0].accesses(), (n * (n - 1)) as u64);
+        assert!(rows[2].stats[0].misses * 4 < rows[0].stats[0].misses);
+
+        let rows = CacheReport::simulate(&l1, &matrix_mul_traces(64));
+        assert!(rows[1].stats[0].misses < rows[0].stats[0].misses);
+    }
+}
@@ -1 +1,2 @@
 pub mod alignment;
+pub mod cache_simulator;
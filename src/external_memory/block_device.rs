@@ -0,0 +1,754 @@
+//! A simulated block device for the external memory model.
+//!
+//! Data is kept in RAM, but every access goes through a fully associative LRU cache
+//! of M / B blocks, and the block transfers between that memory and the "disk" are
+//! counted. LRU with 2M memory does at most twice the transfers of the optimal
+//! replacement with M, so the counts match the model up to a constant.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+
+use super::cache_oblivious::{
+    Matrix, matrix_transpose, matrix_transpose_cache_oblivious,
+    matrix_transpose_cache_oblivious_fast,
+};
+use super::external_sorting::{ExternalSorter, SortStorage};
+use super::record::Record;
+use super::storage::Storage;
+
+/// Block transfers between the memory and the disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IoStats {
+    pub reads: u64,
+    pub writes: u64,
+}
+
+impl IoStats {
+    #[inline]
+    pub fn total(&self) -> u64 {
+        self.reads + self.writes
+    }
+}
+
+/// every array or file gets its own 2^40-byte slice of the address space
+const REGION_BITS: u32 = 40;
+
+struct Cache {
+    /// number of blocks that fit in memory
+    capacity: usize,
+    /// block -> (last use, dirty)
+    blocks: HashMap<u64, (u64, bool)>,
+    /// last use -> block
+    lru: BTreeMap<u64, u64>,
+    clock: u64,
+    /// blocks with data on the disk, a miss on any other block needs no read
+    on_disk: BTreeSet<u64>,
+    stats: IoStats,
+}
+
+impl Cache {
+    fn touch(&mut self, block: u64, write: bool) {
+        self.clock += 1;
+        if let Some((last_use, dirty)) = self.blocks.get_mut(&block) {
+            self.lru.remove(last_use);
+            *last_use = self.clock;
+            *dirty |= write;
+            self.lru.insert(self.clock, block);
+            return;
+        }
+        if self.on_disk.contains(&block) {
+            self.stats.reads += 1;
+        }
+        if self.blocks.len() == self.capacity {
+            let (_, victim) = self.lru.pop_first().unwrap();
+            let (_, dirty) = self.blocks.remove(&victim).unwrap();
+            if dirty {
+                self.stats.writes += 1;
+                self.on_disk.insert(victim);
+            }
+        }
+        self.blocks.insert(block, (self.clock, write));
+        self.lru.insert(self.clock, block);
+    }
+
+    fn write_back(&mut self) {
+        for (&block, (_, dirty)) in self.blocks.iter_mut() {
+            if *dirty {
+                *dirty = false;
+                self.stats.writes += 1;
+                self.on_disk.insert(block);
+            }
+        }
+    }
+
+    /// drop the blocks in `blocks` without writing them back
+    fn forget(&mut self, blocks: std::ops::Range<u64>) {
+        let lru = &mut self.lru;
+        self.blocks.retain(|block, (last_use, _)| {
+            let keep = !blocks.contains(block);
+            if !keep {
+                lru.remove(last_use);
+            }
+            keep
+        });
+        let removed: Vec<u64> = self.on_disk.range(blocks).copied().collect();
+        for block in removed {
+            self.on_disk.remove(&block);
+        }
+    }
+}
+
+struct DeviceFile {
+    region: u64,
+    data: Vec<u8>,
+}
+
+/// An in-memory device that counts block transfers in the external memory model.
+///
+/// Holds arrays ([`DeviceArray`], a [`Storage`] for the matrix kernels) and files
+/// (a [`SortStorage`] for [`ExternalSorter`]), which share one memory of
+/// `memory_size` bytes split in blocks of `block_size` bytes.
+pub struct BlockDevice {
+    memory_size: usize,
+    block_size: usize,
+    cache: RefCell<Cache>,
+    files: RefCell<HashMap<PathBuf, DeviceFile>>,
+    next_region: Cell<u64>,
+}
+
+impl BlockDevice {
+    /// `block_size` must be a power of two and `memory_size` at least one block
+    pub fn new(memory_size: usize, block_size: usize) -> Self {
+        assert!(block_size.is_power_of_two() && block_size as u64 <= 1 << REGION_BITS);
+        assert!(memory_size >= block_size);
+        BlockDevice {
+            memory_size,
+            block_size,
+            cache: RefCell::new(Cache {
+                capacity: memory_size / block_size,
+                blocks: HashMap::new(),
+                lru: BTreeMap::new(),
+                clock: 0,
+                on_disk: BTreeSet::new(),
+                stats: IoStats::default(),
+            }),
+            files: RefCell::new(HashMap::new()),
+            next_region: Cell::new(0),
+        }
+    }
+
+    /// M, in bytes
+    #[inline]
+    pub fn memory_size(&self) -> usize {
+        self.memory_size
+    }
+
+    /// B, in bytes
+    #[inline]
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    pub fn stats(&self) -> IoStats {
+        self.cache.borrow().stats
+    }
+
+    pub fn reset_stats(&self) {
+        self.cache.borrow_mut().stats = IoStats::default();
+    }
+
+    /// write every dirty block back to the disk, counting the writes
+    pub fn flush(&self) {
+        self.cache.borrow_mut().write_back();
+    }
+
+    /// flush and empty the memory, so the next accesses start cold
+    pub fn evict_all(&self) {
+        let mut cache = self.cache.borrow_mut();
+        cache.write_back();
+        cache.blocks.clear();
+        cache.lru.clear();
+    }
+
+    fn new_region(&self) -> u64 {
+        let region = self.next_region.get();
+        self.next_region.set(region + 1);
+        region
+    }
+
+    /// the blocks covering bytes [offset, offset + len) of a region
+    fn blocks(&self, region: u64, offset: usize, len: usize) -> std::ops::Range<u64> {
+        let base = region << REGION_BITS;
+        let first = (base + offset as u64) / self.block_size as u64;
+        let end = (base + (offset + len) as u64).div_ceil(self.block_size as u64);
+        first..end
+    }
+
+    fn access(&self, region: u64, offset: usize, len: usize, write: bool) {
+        let mut cache = self.cache.borrow_mut();
+        for block in self.blocks(region, offset, len) {
+            cache.touch(block, write);
+        }
+    }
+
+    /// mark bytes [0, len) of a region as already on the disk, for free
+    fn persist(&self, region: u64, len: usize) {
+        let mut cache = self.cache.borrow_mut();
+        cache.on_disk.extend(self.blocks(region, 0, len));
+    }
+
+    fn release(&self, region: u64) {
+        let blocks = self.blocks(region, 0, 1 << REGION_BITS);
+        self.cache.borrow_mut().forget(blocks);
+    }
+
+    /// create a file that is already on the disk, without counting any transfers
+    pub fn write_file(&self, path: impl Into<PathBuf>, data: Vec<u8>) {
+        let region = self.new_region();
+        self.persist(region, data.len());
+        if let Some(old) = self
+            .files
+            .borrow_mut()
+            .insert(path.into(), DeviceFile { region, data })
+        {
+            self.release(old.region);
+        }
+    }
+
+    /// the contents of a file, without counting any transfers
+    pub fn read_file(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .borrow()
+            .get(path)
+            .map(|file| file.data.clone())
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} is not on the device", path.display()),
+    )
+}
+
+/// An array of `T` on a [`BlockDevice`].
+///
+/// Element `i` occupies bytes [i * size_of::<T>(), (i + 1) * size_of::<T>()). The
+/// elements themselves are kept in `S`, a `Vec<T>` unless another [`Storage`] is given,
+/// so the transfers of a memory-mapped file can be counted too.
+pub struct DeviceArray<'d, T, S = Vec<T>> {
+    device: &'d BlockDevice,
+    region: u64,
+    data: S,
+    _element: PhantomData<T>,
+}
+
+impl<'d, T> DeviceArray<'d, T> {
+    /// put `data` on the disk of `device`, without counting any transfers
+    pub fn from_vec(device: &'d BlockDevice, data: Vec<T>) -> Self {
+        Self::new(device, data)
+    }
+
+    /// the elements, without counting any transfers
+    pub fn into_vec(mut self) -> Vec<T> {
+        std::mem::take(&mut self.data)
+    }
+}
+
+impl<'d, T, S: Storage<T>> DeviceArray<'d, T, S> {
+    /// put the elements of `data` on the disk of `device`, without counting any transfers
+    pub fn new(device: &'d BlockDevice, data: S) -> Self {
+        let region = device.new_region();
+        device.persist(region, data.len() * size_of::<T>());
+        DeviceArray {
+            device,
+            region,
+            data,
+            _element: PhantomData,
+        }
+    }
+
+    /// the underlying storage, accesses through it are not counted
+    pub fn inner(&self) -> &S {
+        &self.data
+    }
+}
+
+impl<T, S> Drop for DeviceArray<'_, T, S> {
+    fn drop(&mut self) {
+        self.device.release(self.region);
+    }
+}
+
+impl<T, S: Storage<T>> Storage<T> for DeviceArray<'_, T, S> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    fn element(&self, i: usize) -> &T {
+        self.device
+            .access(self.region, i * size_of::<T>(), size_of::<T>(), false);
+        self.data.element(i)
+    }
+
+    fn element_mut(&mut self, i: usize) -> &mut T {
+        self.device
+            .access(self.region, i * size_of::<T>(), size_of::<T>(), true);
+        self.data.element_mut(i)
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.device
+            .access(self.region, i * size_of::<T>(), size_of::<T>(), true);
+        self.device
+            .access(self.region, j * size_of::<T>(), size_of::<T>(), true);
+        self.data.swap(i, j);
+    }
+}
+
+/// Sequential reader of a file on a [`BlockDevice`].
+pub struct DeviceFileReader<'d> {
+    device: &'d BlockDevice,
+    path: PathBuf,
+    pos: usize,
+}
+
+impl Read for DeviceFileReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let files = self.device.files.borrow();
+        let file = files.get(&self.path).ok_or_else(|| not_found(&self.path))?;
+        let len = buf.len().min(file.data.len().saturating_sub(self.pos));
+        buf[..len].copy_from_slice(&file.data[self.pos..self.pos + len]);
+        self.device.access(file.region, self.pos, len, false);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+/// Appending writer of a file on a [`BlockDevice`].
+pub struct DeviceFileWriter<'d> {
+    device: &'d BlockDevice,
+    path: PathBuf,
+}
+
+impl Write for DeviceFileWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut files = self.device.files.borrow_mut();
+        let file = files
+            .get_mut(&self.path)
+            .ok_or_else(|| not_found(&self.path))?;
+        self.device
+            .access(file.region, file.data.len(), buf.len(), true);
+        file.data.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Directories are only path prefixes, so creating one does nothing.
+impl SortStorage for BlockDevice {
+    type Reader<'a> = DeviceFileReader<'a>;
+    type Writer<'a> = DeviceFileWriter<'a>;
+
+    fn file_len(&self, path: &Path) -> io::Result<u64> {
+        let files = self.files.borrow();
+        let file = files.get(path).ok_or_else(|| not_found(path))?;
+        Ok(file.data.len() as u64)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<DeviceFileReader<'_>> {
+        self.file_len(path)?;
+        Ok(DeviceFileReader {
+            device: self,
+            path: path.to_path_buf(),
+            pos: 0,
+        })
+    }
+
+    fn create(&self, path: &Path) -> io::Result<DeviceFileWriter<'_>> {
+        let file = DeviceFile {
+            region: self.new_region(),
+            data: Vec::new(),
+        };
+        if let Some(old) = self.files.borrow_mut().insert(path.to_path_buf(), file) {
+            self.release(old.region);
+        }
+        Ok(DeviceFileWriter {
+            device: self,
+            path: path.to_path_buf(),
+        })
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let file = self
+            .files
+            .borrow_mut()
+            .remove(path)
+            .ok_or_else(|| not_found(path))?;
+        self.release(file.region);
+        Ok(())
+    }
+
+    fn create_dir(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        let mut files = self.files.borrow_mut();
+        let removed: Vec<PathBuf> = files
+            .keys()
+            .filter(|p| p.starts_with(path))
+            .cloned()
+            .collect();
+        for p in removed {
+            let file = files.remove(&p).unwrap();
+            self.release(file.region);
+        }
+        Ok(())
+    }
+}
+
+/// A measured run next to the bound of the external memory model for its problem.
+#[derive(Debug, Clone)]
+pub struct IoMeasurement {
+    pub name: String,
+    pub stats: IoStats,
+    /// the theoretical number of block transfers, up to the constant factor
+    pub bound: f64,
+}
+
+impl IoMeasurement {
+    /// measured transfers over the bound
+    pub fn ratio(&self) -> f64 {
+        self.stats.total() as f64 / self.bound
+    }
+}
+
+/// run `f` from a cold memory and count its transfers, including the final write-back
+fn measure(
+    device: &BlockDevice,
+    name: &str,
+    bound: f64,
+    f: impl FnOnce(&BlockDevice),
+) -> IoMeasurement {
+    device.evict_all();
+    device.reset_stats();
+    f(device);
+    device.evict_all();
+    IoMeasurement {
+        name: name.to_string(),
+        stats: device.stats(),
+        bound,
+    }
+}
+
+/// Sort(N) = 2 N / B * (1 + ceil(log_{M / B}(N / M))) transfers for N bytes:
+/// one pass to form runs of M bytes, then merges of M / B runs at a time
+pub fn sort_bound(bytes: usize, memory_size: usize, block_size: usize) -> f64 {
+    let blocks = bytes.div_ceil(block_size) as f64;
+    let runs = bytes.div_ceil(memory_size).max(1) as f64;
+    let fan_in = (memory_size / block_size) as f64;
+    let merge_passes = runs.log(fan_in).ceil().max(0.);
+    2. * blocks * (1. + merge_passes)
+}
+
+/// reading and writing N bytes once: 2 N / B, which the cache-oblivious transpose
+/// reaches under the tall-cache assumption M >= B^2
+pub fn scan_bound(bytes: usize, block_size: usize) -> f64 {
+    2. * bytes.div_ceil(block_size) as f64
+}
+
+/// n^3 / (B * sqrt(M)) for multiplying n x n matrices, counted in elements of `element_size`
+/// bytes, plus the scans of the three matrices
+pub fn matrix_mul_bound(
+    n: usize,
+    element_size: usize,
+    memory_size: usize,
+    block_size: usize,
+) -> f64 {
+    let b = (block_size / element_size) as f64;
+    let m = (memory_size / element_size) as f64;
+    let n = n as f64;
+    n * n * n / (b * m.sqrt()) + 3. * n * n / b
+}
+
+/// sort `records` random u64s stored in a device file
+///
+/// The sorter keeps its runs and I/O buffers in a budget of M bytes of its own, next to
+/// the M bytes cached by the device, so the bound is taken for a memory of 2M.
+pub fn measure_external_sort(device: &BlockDevice, records: usize) -> IoMeasurement {
+    use rand::{Rng, SeedableRng};
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let mut data = vec![0; records * u64::SIZE];
+    for chunk in data.chunks_exact_mut(u64::SIZE) {
+        rng.random::<u64>().encode(chunk);
+    }
+    let (input, output) = (Path::new("input"), Path::new("output"));
+    device.write_file(input, data);
+    let (memory_size, block_size) = (device.memory_size(), device.block_size());
+    let sorter = ExternalSorter::new(memory_size)
+        .fan_in((memory_size / block_size - 1).max(2))
+        .temp_dir("spill");
+    let bound = sort_bound(records * u64::SIZE, 2 * memory_size, block_size);
+    measure(device, "external sort", bound, |device| {
+        sorter.sort_on::<u64, _>(device, input, output).unwrap();
+    })
+}
+
+/// the three in-place transposes and `Matrix::transpose_into` of an n x n u32 matrix
+pub fn measure_transposes(device: &BlockDevice, n: usize) -> Vec<IoMeasurement> {
+    let bytes = n * n * size_of::<u32>();
+    let bound = scan_bound(bytes, device.block_size());
+    let data: Vec<u32> = (0..(n * n) as u32).collect();
+    type Kernel = fn(&mut DeviceArray<u32>, usize);
+    let kernels: [(&str, Kernel); 3] = [
+        ("matrix_transpose", |a, n| matrix_transpose(a, n)),
+        ("matrix_transpose_cache_oblivious", |a, n| {
+            matrix_transpose_cache_oblivious(a, n)
+        }),
+        ("matrix_transpose_cache_oblivious_fast", |a, n| {
+            matrix_transpose_cache_oblivious_fast(a, n)
+        }),
+    ];
+    let mut result = Vec::new();
+    for (name, kernel) in kernels {
+        result.push(measure(device, name, bound, |device| {
+            let mut array = DeviceArray::from_vec(device, data.clone());
+            kernel(&mut array, n);
+        }));
+    }
+    result.push(measure(device, "Matrix::transpose_into", bound, |device| {
+        let matrix = Matrix::from_storage(DeviceArray::from_vec(device, data.clone()), n, n);
+        let mut target = Matrix::from_storage(DeviceArray::from_vec(device, vec![0; n * n]), n, n);
+        matrix.transpose_into(&mut target);
+    }));
+    result
+}
+
+/// `Matrix::simple_mul_into` and `Matrix::mul_into` on n x n u32 matrices
+pub fn measure_matrix_mul(device: &BlockDevice, n: usize) -> Vec<IoMeasurement> {
+    let bound = matrix_mul_bound(
+        n,
+        size_of::<u32>(),
+        device.memory_size(),
+        device.block_size(),
+    );
+    // small entries, so the sums do not overflow
+    let a: Vec<u32> = (0..(n * n) as u32).map(|x| x % 7).collect();
+    let b: Vec<u32> = (0..(n * n) as u32).map(|x| x % 11).collect();
+    fn on_device<'d>(
+        device: &'d BlockDevice,
+        data: &[u32],
+        n: usize,
+    ) -> Matrix<u32, DeviceArray<'d, u32>> {
+        Matrix::from_storage(DeviceArray::from_vec(device, data.to_vec()), n, n)
+    }
+    let zero = vec![0; n * n];
+    let cache_oblivious = measure(device, "Matrix::mul_into", bound, |device| {
+        let (a, b) = (on_device(device, &a, n), on_device(device, &b, n));
+        let mut c = on_device(device, &zero, n);
+        a.mul_into(&b, &mut c);
+    });
+    let naive = measure(device, "Matrix::simple_mul_into", bound, |device| {
+        let (a, b) = (on_device(device, &a, n), on_device(device, &b, n));
+        let mut c = on_device(device, &zero, n);
+        a.simple_mul_into(&b, &mut c);
+    });
+    vec![naive, cache_oblivious]
+}
+
+/// Measured transfers against the external memory bounds.
+pub struct IoReport {
+    pub sections: Vec<(String, Vec<IoMeasurement>)>,
+}
+
+impl fmt::Display for IoReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (title, rows) in &self.sections {
+            writeln!(f, "{title}")?;
+            writeln!(
+                f,
+                "  {:<40} {:>10} {:>10} {:>12} {:>8}",
+                "", "reads", "writes", "bound", "ratio"
+            )?;
+            for row in rows {
+                writeln!(
+                    f,
+                    "  {:<40} {:>10} {:>10} {:>12.0} {:>8.2}",
+                    row.name,
+                    row.stats.reads,
+                    row.stats.writes,
+                    row.bound,
+                    row.ratio()
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sort on a disk-like device (4 KiB blocks, 256 KiB memory and as much for the sorter)
+/// and the matrix kernels on a cache-like one (64-byte blocks, 32 KiB memory, so
+/// M >= B^2 holds).
+pub fn io_model_report() -> IoReport {
+    let disk = BlockDevice::new(256 << 10, 4 << 10);
+    let cache = BlockDevice::new(32 << 10, 64);
+    IoReport {
+        sections: vec![
+            (
+                "N = 2^20 u64 records, M = 256 KiB sort budget + 256 KiB cache, B = 4 KiB"
+                    .to_string(),
+                vec![measure_external_sort(&disk, 1 << 20)],
+            ),
+            (
+                "1024 x 1024 u32 transpose, M = 32 KiB, B = 64 B".to_string(),
+                measure_transposes(&cache, 1024),
+            ),
+            (
+                "256 x 256 u32 multiply, M = 32 KiB, B = 64 B".to_string(),
+                measure_matrix_mul(&cache, 256),
+            ),
+        ],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_device_counts() {
+        // 4 blocks of 16 bytes
+        let device = BlockDevice::new(64, 16);
+        let mut array = DeviceArray::from_vec(&device, vec![0u32; 64]);
+        // a scan reads every block once and writes nothing back
+        for i in 0..64 {
+            array.element(i);
+        }
+        assert_eq!(
+            device.stats(),
+            IoStats {
+                reads: 16,
+                writes: 0
+            }
+        );
+        // the last 4 blocks are still in memory
+        for i in 48..64 {
+            array.element(i);
+        }
+        assert_eq!(device.stats().reads, 16);
+        // dirty blocks are written back when evicted
+        for i in 0..32 {
+            *array.element_mut(i) = i as u32;
+        }
+        assert_eq!(
+            device.stats(),
+            IoStats {
+                reads: 24,
+                writes: 4
+            }
+        );
+        device.flush();
+        assert_eq!(device.stats().writes, 8);
+        drop(array);
+
+        device.reset_stats();
+        // a new file costs no reads, only the writes back
+        {
+            let mut writer = device.create(Path::new("dir/file")).unwrap();
+            writer.write_all(&[7; 100]).unwrap();
+        }
+        assert_eq!(
+            device.stats(),
+            IoStats {
+                reads: 0,
+                writes: 3
+            }
+        );
+        device.evict_all();
+        let mut bytes = Vec::new();
+        device
+            .open(Path::new("dir/file"))
+            .unwrap()
+            .read_to_end(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes, vec![7; 100]);
+        assert_eq!(
+            device.stats(),
+            IoStats {
+                reads: 7,
+                writes: 7
+            }
+        );
+        device.remove_dir(Path::new("dir")).unwrap();
+        assert!(device.open(Path::new("dir/file")).is_err());
+    }
+
+    #[test]
+    fn test_kernels_on_block_device() {
+        let device = BlockDevice::new(4096, 64);
+        let n = 48;
+        let original: Vec<u32> = (0..(n * n) as u32).collect();
+        let mut array = DeviceArray::from_vec(&device, original.clone());
+        matrix_transpose_cache_oblivious_fast(&mut array, n);
+        let transposed = array.into_vec();
+        for i in 0..n {
+            for j in 0..n {
+                assert_eq!(transposed[i * n + j], original[j * n + i]);
+            }
+        }
+
+        let a = Matrix::from_vec(original.clone(), n, n);
+        let expected = a.mul(&a);
+        let a_on_device = Matrix::from_storage(DeviceArray::from_vec(&device, original), n, n);
+        let mut c = Matrix::from_storage(DeviceArray::from_vec(&device, vec![0; n * n]), n, n);
+        a_on_device.mul_into(&a_on_device, &mut c);
+        assert_eq!(c.into_storage().into_vec(), expected.into_storage());
+
+        let mut data = Vec::new();
+        for x in [5u64, 3, 9, 1, 7] {
+            data.extend(x.to_le_bytes());
+        }
+        device.write_file("input", data);
+        ExternalSorter::new(16)
+            .fan_in(2)
+            .temp_dir("tmp")
+            .sort_on::<u64, _>(&device, Path::new("input"), Path::new("output"))
+            .unwrap();
+        let sorted: Vec<u64> = device
+            .read_file(Path::new("output"))
+            .unwrap()
+            .chunks_exact(8)
+            .map(u64::decode)
+            .collect();
+        assert_eq!(sorted, vec![1, 3, 5, 7, 9]);
+    }
+
+    #[test]
+    fn test_io_bounds() {
+        let disk = BlockDevice::new(16 << 10, 1 << 10);
+        let sort = measure_external_sort(&disk, 1 << 14);
+        assert!(sort.ratio() <= 1.0, "{sort:?}");
+        assert!(sort.ratio() >= 0.5, "{sort:?}");
+
+        let cache = BlockDevice::new(8 << 10, 64);
+        let transposes = measure_transposes(&cache, 256);
+        // the naive transpose misses on almost every column access
+        assert!(transposes[0].ratio() > 4.0, "{transposes:?}");
+        for measurement in &transposes[1..] {
+            assert!(measurement.ratio() < 3.0, "{measurement:?}");
+        }
+
+        let mul = measure_matrix_mul(&cache, 64);
+        assert!(mul[1].ratio() < mul[0].ratio(), "{mul:?}");
+    }
+}
@@ -1,4 +1,14 @@
-pub fn matrix_transpose<T>(matrix: &mut [T], n: usize) {
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Add, AddAssign, Index, IndexMut, Mul, Sub, SubAssign};
+
+use crate::arithmetic::{barrett_reduction_mod, cal_barrett_reduction_magic_number};
+
+use super::gemm::try_gemm;
+use super::matrix_view::{MatrixMut, MatrixRef, MatrixView, MatrixViewMut};
+use super::storage::Storage;
+
+pub fn matrix_transpose<T, S: Storage<T> + ?Sized>(matrix: &mut S, n: usize) {
     for i in 0..n {
         for j in 0..i {
             matrix.swap(i * n + j, j * n + i);
@@ -6,53 +16,51 @@ pub fn matrix_transpose<T>(matrix: &mut [T], n: usize) {
     }
 }
 
-fn matrix_transpose_cache_oblivious_rec<T>(matrix: *mut T, n: usize, tot_n: usize) {
+/// transpose the n x n block starting at `offset`
+fn matrix_transpose_cache_oblivious_rec<T, S: Storage<T> + ?Sized>(
+    matrix: &mut S,
+    offset: usize,
+    n: usize,
+    tot_n: usize,
+) {
     if n <= 32 {
         for i in 0..n {
             for j in 0..i {
-                unsafe {
-                    std::ptr::swap(matrix.add(i * tot_n + j), matrix.add(j * tot_n + i));
-                }
+                matrix.swap(offset + i * tot_n + j, offset + j * tot_n + i);
             }
         }
     } else {
         let mid = n / 2;
-        unsafe {
-            matrix_transpose_cache_oblivious_rec(matrix, mid, tot_n);
-            matrix_transpose_cache_oblivious_rec(matrix.add(mid), mid, tot_n);
-            matrix_transpose_cache_oblivious_rec(matrix.add(mid * tot_n), mid, tot_n);
-            matrix_transpose_cache_oblivious_rec(matrix.add(mid * tot_n + mid), mid, tot_n);
-        }
+        matrix_transpose_cache_oblivious_rec(matrix, offset, mid, tot_n);
+        matrix_transpose_cache_oblivious_rec(matrix, offset + mid, mid, tot_n);
+        matrix_transpose_cache_oblivious_rec(matrix, offset + mid * tot_n, mid, tot_n);
+        matrix_transpose_cache_oblivious_rec(matrix, offset + mid * tot_n + mid, mid, tot_n);
         for i in 0..mid {
             for j in 0..mid {
-                unsafe {
-                    std::ptr::swap(
-                        matrix.add(i * tot_n + j + mid),
-                        matrix.add((i + mid) * tot_n + j),
-                    );
-                }
+                matrix.swap(offset + i * tot_n + j + mid, offset + (i + mid) * tot_n + j);
             }
         }
         if n % 2 == 1 {
             let last_row = n - 1;
             for i in 0..n - 1 {
-                unsafe {
-                    std::ptr::swap(
-                        matrix.add(last_row * tot_n + i),
-                        matrix.add(i * tot_n + last_row),
-                    );
-                }
+                matrix.swap(offset + last_row * tot_n + i, offset + i * tot_n + last_row);
             }
         }
     }
 }
 
-pub fn matrix_transpose_cache_oblivious<T>(matrix: &mut [T], n: usize) {
-    matrix_transpose_cache_oblivious_rec(matrix.as_mut_ptr(), n, n);
+pub fn matrix_transpose_cache_oblivious<T, S: Storage<T> + ?Sized>(matrix: &mut S, n: usize) {
+    match matrix.contiguous_mut() {
+        Some(slice) => {
+            matrix_transpose_cache_oblivious_rec(&mut UncheckedSwaps::new(slice, n), 0, n, n)
+        }
+        None => matrix_transpose_cache_oblivious_rec(matrix, 0, n, n),
+    }
 }
 
-fn matrix_transpose_cache_oblivious_fast_rec<T>(
-    matrix: *mut T,
+pub(crate) fn matrix_transpose_cache_oblivious_fast_rec<T, S: Storage<T> + ?Sized>(
+    matrix: &mut S,
+    offset: usize,
     x0: usize,
     y0: usize,
     x1: usize,
@@ -62,109 +70,464 @@ fn matrix_transpose_cache_oblivious_fast_rec<T>(
     if x1 - x0 <= 16 && y1 - y0 <= 16 {
         for i in x0..x1 {
             for j in y0..y1.min(i) {
-                unsafe {
-                    std::ptr::swap(matrix.add(i * tot_n + j), matrix.add(j * tot_n + i));
-                }
+                matrix.swap(offset + i * tot_n + j, offset + j * tot_n + i);
             }
         }
         return;
     }
     if (x1 - x0) > (y1 - y0) {
         let mid_x = x0.midpoint(x1);
-        matrix_transpose_cache_oblivious_fast_rec(matrix, x0, y0, mid_x, y1, tot_n);
-        matrix_transpose_cache_oblivious_fast_rec(matrix, mid_x, y0, x1, y1, tot_n);
+        matrix_transpose_cache_oblivious_fast_rec(matrix, offset, x0, y0, mid_x, y1, tot_n);
+        matrix_transpose_cache_oblivious_fast_rec(matrix, offset, mid_x, y0, x1, y1, tot_n);
         return;
     }
 
     let mid_y = y0.midpoint(y1);
-    matrix_transpose_cache_oblivious_fast_rec(matrix, x0, y0, x1, mid_y, tot_n);
-    matrix_transpose_cache_oblivious_fast_rec(matrix, x0, mid_y, x1, y1, tot_n);
+    matrix_transpose_cache_oblivious_fast_rec(matrix, offset, x0, y0, x1, mid_y, tot_n);
+    matrix_transpose_cache_oblivious_fast_rec(matrix, offset, x0, mid_y, x1, y1, tot_n);
 }
 
-pub fn matrix_transpose_cache_oblivious_fast<T>(matrix: &mut [T], n: usize) {
-    matrix_transpose_cache_oblivious_fast_rec(matrix.as_mut_ptr(), 0, 0, n, n, n);
+pub fn matrix_transpose_cache_oblivious_fast<T, S: Storage<T> + ?Sized>(matrix: &mut S, n: usize) {
+    match matrix.contiguous_mut() {
+        Some(slice) => matrix_transpose_cache_oblivious_fast_rec(
+            &mut UncheckedSwaps::new(slice, n),
+            0,
+            0,
+            0,
+            n,
+            n,
+            n,
+        ),
+        None => matrix_transpose_cache_oblivious_fast_rec(matrix, 0, 0, 0, n, n, n),
+    }
 }
 
-pub struct Matrix<T> {
-    data: Vec<T>,
+/// The first n x n entries of a slice, swapped without bounds checks by the transposes
+/// above, which never leave the matrix.
+struct UncheckedSwaps<'a, T>(&'a mut [T]);
+
+impl<'a, T> UncheckedSwaps<'a, T> {
+    fn new(slice: &'a mut [T], n: usize) -> Self {
+        let len = n.checked_mul(n).expect("matrix size overflows usize");
+        UncheckedSwaps(&mut slice[..len])
+    }
+}
+
+impl<T> Storage<T> for UncheckedSwaps<'_, T> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    fn element(&self, i: usize) -> &T {
+        &self.0[i]
+    }
+
+    #[inline]
+    fn element_mut(&mut self, i: usize) -> &mut T {
+        &mut self.0[i]
+    }
+
+    #[inline]
+    fn swap(&mut self, i: usize, j: usize) {
+        debug_assert!(i < self.0.len() && j < self.0.len());
+        let ptr = self.0.as_mut_ptr();
+        // SAFETY: the transposes only swap entries of the n x n matrix, and new
+        // checked that the slice holds all of them
+        unsafe { std::ptr::swap(ptr.add(i), ptr.add(j)) }
+    }
+}
+
+/// A bit per position, set once the element of the position has been moved.
+struct BitArray {
+    words: Vec<u64>,
+}
+
+impl BitArray {
+    fn new(len: usize) -> Self {
+        BitArray {
+            words: vec![0; len.div_ceil(64)],
+        }
+    }
+
+    #[inline]
+    fn get(&self, i: usize) -> bool {
+        self.words[i / 64] >> (i % 64) & 1 == 1
+    }
+
+    #[inline]
+    fn set(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+
+    fn clear(&mut self) {
+        self.words.fill(0);
+    }
+}
+
+/// transpose the rows x cols matrix of runs of `run` elements starting at `offset`
+///
+/// The run at k = i * cols + j goes to j * rows + i, and every cycle of this permutation
+/// is followed once from its smallest position, the others are marked in `visited`.
+fn transpose_runs_in_place<T, S: Storage<T> + ?Sized>(
+    matrix: &mut S,
+    offset: usize,
+    rows: usize,
+    cols: usize,
+    run: usize,
+    visited: &mut BitArray,
+) {
+    if rows <= 1 || cols <= 1 {
+        return;
+    }
+    visited.clear();
+    let destination = |k: usize| k % cols * rows + k / cols;
+    // the first and the last run stay in place
+    for start in 1..rows * cols - 1 {
+        if visited.get(start) {
+            continue;
+        }
+        // the run taken from `start` is carried along the cycle, swapping in the next one
+        let mut next = destination(start);
+        while next != start {
+            for t in 0..run {
+                matrix.swap(offset + start * run + t, offset + next * run + t);
+            }
+            visited.set(next);
+            next = destination(next);
+        }
+    }
+}
+
+/// transpose the n x m matrix in place, it becomes m x n
+///
+/// Follows the cycles of the permutation, so it needs only n * m bits of extra memory,
+/// but almost every move is a cache miss on large matrices.
+pub fn matrix_transpose_in_place<T, S: Storage<T> + ?Sized>(matrix: &mut S, n: usize, m: usize) {
+    assert_eq!(matrix.len(), n * m);
+    let mut visited = BitArray::new(n * m);
+    transpose_runs_in_place(matrix, 0, n, m, 1, &mut visited);
+}
+
+/// transpose the n x m matrix in place with the cycles moving runs of `block` elements
+///
+/// With the indices split as i = i1 * b + i0 and j = j1 * b + j0, the element at the
+/// digits (i1, i0, j1, j0) of the layout moves to (j1, j0, i1, i0). This is done in four
+/// steps that each move contiguous runs of b or b * b elements: to (i1, j1, i0, j0)
+/// inside every block row, (i1, j1, j0, i0) inside every b x b block, (j1, i1, j0, i0)
+/// over the blocks and (j1, j0, i1, i0) inside every block column. Falls back to
+/// [`matrix_transpose_in_place`] unless `block` divides both n and m.
+pub fn matrix_transpose_in_place_blocked<T, S: Storage<T> + ?Sized>(
+    matrix: &mut S,
     n: usize,
     m: usize,
+    block: usize,
+) {
+    if block <= 1 || !n.is_multiple_of(block) || !m.is_multiple_of(block) {
+        matrix_transpose_in_place(matrix, n, m);
+        return;
+    }
+    assert_eq!(matrix.len(), n * m);
+    let (b, n1, m1) = (block, n / block, m / block);
+    let mut visited = BitArray::new(n.max(m).max(n1 * m1));
+    for i in 0..n1 {
+        transpose_runs_in_place(matrix, i * b * m, b, m1, b, &mut visited);
+    }
+    for k in 0..n1 * m1 {
+        matrix_transpose_cache_oblivious_rec(matrix, k * b * b, b, b);
+    }
+    transpose_runs_in_place(matrix, 0, n1, m1, b * b, &mut visited);
+    for j in 0..m1 {
+        transpose_runs_in_place(matrix, j * n * b, n1, b, b, &mut visited);
+    }
+}
+
+/// A row-major n x m matrix, stored in a `Vec<T>` unless another [`Storage`] is given.
+#[derive(Clone)]
+pub struct Matrix<T, S = Vec<T>> {
+    data: S,
+    n: usize,
+    m: usize,
+    _element: PhantomData<T>,
 }
 
 impl<T: Default + Clone> Matrix<T> {
     pub fn new(n: usize, m: usize) -> Self {
         let size = n * m;
         let data = vec![T::default(); size];
-        Matrix { data, n, m }
+        Matrix::from_storage(data, n, m)
     }
 
     pub fn from_vec(data: Vec<T>, n: usize, m: usize) -> Self {
+        Matrix::from_storage(data, n, m)
+    }
+}
+
+impl<T, S: Storage<T>> Matrix<T, S> {
+    /// `data` holds the n * m elements in row-major order
+    pub fn from_storage(data: S, n: usize, m: usize) -> Self {
         assert_eq!(data.len(), n * m);
-        Matrix { data, n, m }
+        Matrix {
+            data,
+            n,
+            m,
+            _element: PhantomData,
+        }
     }
 
-    fn transpose_to_rec(&self, target: &mut Matrix<T>, x0: usize, y0: usize, x1: usize, y1: usize) {
-        if x1 - x0 <= 16 && y1 - y0 <= 16 {
-            for i in x0..x1 {
-                for j in y0..y1 {
-                    target.set(j, i, self.get(i, j).clone());
-                }
-            }
-            return;
+    pub fn rows(&self) -> usize {
+        self.n
+    }
+
+    pub fn cols(&self) -> usize {
+        self.m
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.data
+    }
+
+    pub fn into_storage(self) -> S {
+        self.data
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> &T {
+        self.data.element(i * self.m + j)
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, value: T) {
+        *self.data.element_mut(i * self.m + j) = value;
+    }
+
+    pub fn get_mut(&mut self, i: usize, j: usize) -> &mut T {
+        self.data.element_mut(i * self.m + j)
+    }
+
+    /// transpose without allocating a second matrix, an n x m matrix becomes m x n
+    pub fn transpose_in_place(&mut self) {
+        if self.n == self.m {
+            matrix_transpose_cache_oblivious_fast(&mut self.data, self.n);
+        } else {
+            // the largest block up to 32 dividing both sides
+            let block = (1..=32)
+                .rev()
+                .find(|&b| self.n.is_multiple_of(b) && self.m.is_multiple_of(b))
+                .unwrap();
+            matrix_transpose_in_place_blocked(&mut self.data, self.n, self.m, block);
         }
-        if (x1 - x0) > (y1 - y0) {
-            let mid_x = x0.midpoint(x1);
-            self.transpose_to_rec(target, x0, y0, mid_x, y1);
-            self.transpose_to_rec(target, mid_x, y0, x1, y1);
-            return;
+        std::mem::swap(&mut self.n, &mut self.m);
+    }
+}
+
+fn transpose_to_rec<T: Clone, A: MatrixRef<T> + ?Sized, B: MatrixMut<T> + ?Sized>(
+    source: &A,
+    target: &mut B,
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+) {
+    if x1 - x0 <= 16 && y1 - y0 <= 16 {
+        for i in x0..x1 {
+            for j in y0..y1 {
+                *target.entry_mut(j, i) = source.entry(i, j).clone();
+            }
         }
+        return;
+    }
+    if (x1 - x0) > (y1 - y0) {
+        let mid_x = x0.midpoint(x1);
+        transpose_to_rec(source, target, x0, y0, mid_x, y1);
+        transpose_to_rec(source, target, mid_x, y0, x1, y1);
+        return;
+    }
 
-        let mid_y = y0.midpoint(y1);
-        self.transpose_to_rec(target, x0, y0, x1, mid_y);
-        self.transpose_to_rec(target, x0, mid_y, x1, y1);
+    let mid_y = y0.midpoint(y1);
+    transpose_to_rec(source, target, x0, y0, x1, mid_y);
+    transpose_to_rec(source, target, x0, mid_y, x1, y1);
+}
+
+/// write the transpose of `source` into `target`, which must be cols x rows
+pub(crate) fn transpose_into_rec<T: Clone, A: MatrixRef<T> + ?Sized, B: MatrixMut<T> + ?Sized>(
+    source: &A,
+    target: &mut B,
+) {
+    assert_eq!(
+        (target.rows(), target.cols()),
+        (source.cols(), source.rows())
+    );
+    transpose_to_rec(source, target, 0, 0, source.rows(), source.cols());
+}
+
+impl<T, S: Storage<T>> MatrixRef<T> for Matrix<T, S> {
+    #[inline]
+    fn rows(&self) -> usize {
+        self.n
+    }
+
+    #[inline]
+    fn cols(&self) -> usize {
+        self.m
+    }
+
+    #[inline]
+    fn entry(&self, i: usize, j: usize) -> &T {
+        self.get(i, j)
+    }
+
+    fn strided(&self) -> Option<MatrixView<'_, T>> {
+        let data = self.data.contiguous()?;
+        Some(MatrixView::new(&data[..self.n * self.m], self.n, self.m))
+    }
+}
+
+impl<T, S: Storage<T>> MatrixMut<T> for Matrix<T, S> {
+    #[inline]
+    fn entry_mut(&mut self, i: usize, j: usize) -> &mut T {
+        self.get_mut(i, j)
+    }
+
+    fn strided_mut(&mut self) -> Option<MatrixViewMut<'_, T>> {
+        let (n, m) = (self.n, self.m);
+        let data = self.data.contiguous_mut()?;
+        Some(MatrixViewMut::new(&mut data[..n * m], n, m))
     }
+}
 
+impl<T, S: Storage<T> + AsRef<[T]>> Matrix<T, S> {
+    pub fn view(&self) -> MatrixView<'_, T> {
+        MatrixView::new(self.data.as_ref(), self.n, self.m)
+    }
+}
+
+impl<T, S: Storage<T> + AsMut<[T]>> Matrix<T, S> {
+    pub fn view_mut(&mut self) -> MatrixViewMut<'_, T> {
+        MatrixViewMut::new(self.data.as_mut(), self.n, self.m)
+    }
+}
+
+impl<T: Default + Clone, S: Storage<T>> Matrix<T, S> {
     pub fn transpose(&self) -> Matrix<T> {
         let mut target = Matrix::new(self.m, self.n);
-        self.transpose_to_rec(&mut target, 0, 0, self.n, self.m);
+        self.transpose_into(&mut target);
         target
     }
 
-    pub fn get(&self, i: usize, j: usize) -> &T {
-        &self.data[i * self.m + j]
+    /// write the transpose into `target`, which must be m x n
+    pub fn transpose_into<M: MatrixMut<T> + ?Sized>(&self, target: &mut M) {
+        transpose_into_rec(self, target);
     }
+}
 
-    pub fn set(&mut self, i: usize, j: usize, value: T) {
-        self.data[i * self.m + j] = value;
+#[rustfmt::skip]
+#[allow(clippy::too_many_arguments)]
+fn mul_rec<T, A, B, C>(
+    a: &A,
+    ax0: usize,
+    ax1: usize,
+    ay0: usize,
+    ay1: usize,
+    b: &B,
+    by0: usize,
+    by1: usize,
+    result: &mut C,
+    cx0: usize,
+    cx1: usize,
+) where
+    T: std::ops::Mul<Output = T> + std::ops::Add<Output = T> + Clone,
+    A: MatrixRef<T> + ?Sized,
+    B: MatrixRef<T> + ?Sized,
+    C: MatrixMut<T> + ?Sized,
+{
+    if ax1 - ax0 <= 16 && ay1 - ay0 <= 16 && by1 - by0 <= 16 {
+        for i in ax0..ax1 {
+            for j in by0..by1 {
+                let mut sum = result.entry(i, j).clone();
+                for k in ay0..ay1 {
+                    sum = sum + a.entry(i, k).clone() * b.entry(k, j).clone();
+                }
+                *result.entry_mut(i, j) = sum;
+            }
+        }
+        return;
     }
 
-    pub fn get_mut(&mut self, i: usize, j: usize) -> &mut T {
-        &mut self.data[i * self.m + j]
+    if ay1 - ay0 > 16 {
+        let mid_y = ay0.midpoint(ay1);
+        mul_rec(a, ax0, ax1, ay0, mid_y, b, by0, by1, result, cx0, cx1);
+        mul_rec(a, ax0, ax1, mid_y, ay1, b, by0, by1, result, cx0, cx1);
+        return;
     }
+
+    if ax1 - ax0 > 16 {
+        let mid_x = ax0.midpoint(ax1);
+        mul_rec(a, ax0, mid_x, ay0, ay1, b, by0, by1, result, cx0, cx1);
+        mul_rec(a, mid_x, ax1, ay0, ay1, b, by0, by1, result, cx0, cx1);
+        return;
+    }
+    // if by1 - by0 > 16 {
+    let mid_x = cx0.midpoint(cx1);
+    mul_rec(a, ax0, ax1, ay0, ay1, b, by0, mid_x, result, cx0, mid_x);
+    mul_rec(a, ax0, ax1, ay0, ay1, b, mid_x, by1, result, mid_x, cx1);
 }
 
-impl<T: std::ops::Mul<Output = T> + std::ops::Add<Output = T> + Default + Clone> Matrix<T> {
-    pub fn simple_mul(&self, other: &Matrix<T>) -> Matrix<T> {
-        assert_eq!(self.m, other.n);
-        let mut result = Matrix::new(self.n, other.m);
+/// result += a * b, result must be a.rows x b.cols
+///
+/// f64 and f32 matrices in memory go to the packed [`gemm`](super::gemm::gemm).
+pub(crate) fn mul_into_rec<T, A, B, C>(a: &A, b: &B, result: &mut C)
+where
+    T: std::ops::Mul<Output = T> + std::ops::Add<Output = T> + Clone + 'static,
+    A: MatrixRef<T> + ?Sized,
+    B: MatrixRef<T> + ?Sized,
+    C: MatrixMut<T> + ?Sized,
+{
+    assert_eq!(a.cols(), b.rows());
+    assert_eq!((result.rows(), result.cols()), (a.rows(), b.cols()));
+    if try_gemm(a, b, result) {
+        return;
+    }
+    let (n, m, p) = (a.rows(), a.cols(), b.cols());
+    mul_rec(a, 0, n, 0, m, b, 0, p, result, 0, p);
+}
+
+impl<
+    T: std::ops::Mul<Output = T> + std::ops::Add<Output = T> + Default + Clone + 'static,
+    S: Storage<T>,
+> Matrix<T, S>
+{
+    pub fn simple_mul<B: MatrixRef<T> + ?Sized>(&self, other: &B) -> Matrix<T> {
+        let mut result = Matrix::new(self.n, other.cols());
+        self.simple_mul_into(other, &mut result);
+        result
+    }
+
+    /// result = self * other with the i-j-k loop, result must be n x other.cols
+    pub fn simple_mul_into<B: MatrixRef<T> + ?Sized, C: MatrixMut<T> + ?Sized>(
+        &self,
+        other: &B,
+        result: &mut C,
+    ) {
+        assert_eq!(self.m, other.rows());
+        assert_eq!((result.rows(), result.cols()), (self.n, other.cols()));
         for i in 0..self.n {
-            for j in 0..other.m {
+            for j in 0..other.cols() {
                 let mut sum = T::default();
                 for k in 0..self.m {
-                    sum = sum + self.get(i, k).clone() * other.get(k, j).clone();
+                    sum = sum + self.get(i, k).clone() * other.entry(k, j).clone();
                 }
-                result.set(i, j, sum);
+                *result.entry_mut(i, j) = sum;
             }
         }
-        result
     }
 
-    pub fn transpose_mul(&self, other: &Matrix<T>) -> Matrix<T> {
-        assert_eq!(self.m, other.n);
-        let other_transposed = other.transpose();
-        let mut result = Matrix::new(self.n, other.m);
+    pub fn transpose_mul<B: MatrixRef<T> + ?Sized>(&self, other: &B) -> Matrix<T> {
+        assert_eq!(self.m, other.rows());
+        let mut other_transposed = Matrix::new(other.cols(), other.rows());
+        transpose_into_rec(other, &mut other_transposed);
+        let mut result = Matrix::new(self.n, other.cols());
         for i in 0..self.n {
-            for j in 0..other.m {
+            for j in 0..other.cols() {
                 let mut sum = T::default();
                 for k in 0..self.m {
                     sum = sum + self.get(i, k).clone() * other_transposed.get(j, k).clone();
@@ -175,67 +538,271 @@ impl<T: std::ops::Mul<Output = T> + std::ops::Add<Output = T> + Default + Clone>
         result
     }
 
-    #[rustfmt::skip]
-    fn mul_rec(
+    pub fn mul<B: MatrixRef<T> + ?Sized>(&self, other: &B) -> Matrix<T> {
+        let mut result = Matrix::new(self.n, other.cols());
+        self.mul_into(other, &mut result);
+        result
+    }
+
+    /// result += self * other, result must be n x other.cols
+    pub fn mul_into<B: MatrixRef<T> + ?Sized, C: MatrixMut<T> + ?Sized>(
         &self,
-        ax0: usize,
-        ax1: usize,
-        ay0: usize,
-        ay1: usize,
-        other: &Matrix<T>,
-        by0: usize,
-        by1: usize,
-        result: &mut Matrix<T>,
-        cx0: usize,
-        cx1: usize,
+        other: &B,
+        result: &mut C,
     ) {
-        if ax1 - ax0 <= 16 && ay1 - ay0 <= 16 && by1 - by0 <= 16 {
-            for i in ax0..ax1 {
-                for j in by0..by1 {
-                    let mut sum = result.get(i, j).clone();
-                    for k in ay0..ay1 {
-                        sum = sum + self.get(i, k).clone() * other.get(k, j).clone();
-                    }
-                    result.set(i, j, sum);
+        mul_into_rec(self, other, result);
+    }
+}
+
+impl<T: Default + Clone + From<u8>> Matrix<T> {
+    pub fn identity(n: usize) -> Self {
+        let mut matrix = Matrix::new(n, n);
+        for i in 0..n {
+            matrix.set(i, i, T::from(1));
+        }
+        matrix
+    }
+}
+
+impl<
+    T: std::ops::Mul<Output = T> + std::ops::Add<Output = T> + Default + Clone + From<u8> + 'static,
+> Matrix<T>
+{
+    /// self^exp by binary exponentiation, self must be square
+    pub fn pow(&self, mut exp: u64) -> Matrix<T> {
+        assert_eq!(self.n, self.m);
+        let mut result = Matrix::identity(self.n);
+        let mut base = self.clone();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result.mul(&base);
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = base.mul(&base);
+            }
+        }
+        result
+    }
+}
+
+impl<T, S: Storage<T>> Matrix<T, S> {
+    /// the entries of row i
+    pub fn row(&self, i: usize) -> impl ExactSizeIterator<Item = &T> + '_ {
+        assert!(i < self.n);
+        (0..self.m).map(move |j| self.get(i, j))
+    }
+
+    /// the entries of column j
+    pub fn col(&self, j: usize) -> impl ExactSizeIterator<Item = &T> + '_ {
+        assert!(j < self.m);
+        (0..self.n).map(move |i| self.get(i, j))
+    }
+
+    pub fn iter_rows(&self) -> impl ExactSizeIterator<Item = impl ExactSizeIterator<Item = &T>> {
+        (0..self.n).map(|i| self.row(i))
+    }
+
+    pub fn iter_cols(&self) -> impl ExactSizeIterator<Item = impl ExactSizeIterator<Item = &T>> {
+        (0..self.m).map(|j| self.col(j))
+    }
+
+    /// all entries in row-major order
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &T> + '_ {
+        (0..self.n * self.m).map(|k| self.data.element(k))
+    }
+
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Matrix<U> {
+        Matrix::from_storage(self.iter().map(f).collect(), self.n, self.m)
+    }
+
+    /// combine the entries at the same positions, the matrices must have the same shape
+    pub fn zip_with<U, V, S2: Storage<U>>(
+        &self,
+        other: &Matrix<U, S2>,
+        mut f: impl FnMut(&T, &U) -> V,
+    ) -> Matrix<V> {
+        assert_eq!((self.n, self.m), (other.n, other.m));
+        let data = self
+            .iter()
+            .zip(other.iter())
+            .map(|(a, b)| f(a, b))
+            .collect();
+        Matrix::from_storage(data, self.n, self.m)
+    }
+}
+
+impl<T, S: Storage<T>> Index<(usize, usize)> for Matrix<T, S> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, (i, j): (usize, usize)) -> &T {
+        self.get(i, j)
+    }
+}
+
+impl<T, S: Storage<T>> IndexMut<(usize, usize)> for Matrix<T, S> {
+    #[inline]
+    fn index_mut(&mut self, (i, j): (usize, usize)) -> &mut T {
+        self.get_mut(i, j)
+    }
+}
+
+/// `a + b` and `a - b` entry by entry, for owned and borrowed matrices
+macro_rules! impl_entrywise_op {
+    ($op:ident, $method:ident, $op_assign:ident, $method_assign:ident) => {
+        impl<T: $op<Output = T> + Clone, S: Storage<T>, S2: Storage<T>> $op_assign<&Matrix<T, S2>>
+            for Matrix<T, S>
+        {
+            fn $method_assign(&mut self, rhs: &Matrix<T, S2>) {
+                assert_eq!((self.n, self.m), (rhs.n, rhs.m));
+                for k in 0..self.n * self.m {
+                    let value = self
+                        .data
+                        .element(k)
+                        .clone()
+                        .$method(rhs.data.element(k).clone());
+                    *self.data.element_mut(k) = value;
                 }
             }
-            return;
         }
 
-        if ay1 - ay0 > 16 {
-            let mid_y = ay0.midpoint(ay1);
-            self.mul_rec(ax0, ax1, ay0, mid_y, other, by0, by1, result, cx0, cx1);
-            self.mul_rec(ax0, ax1, mid_y, ay1, other, by0, by1, result, cx0, cx1);
-            return;
+        impl<T: $op<Output = T> + Clone, S: Storage<T>, S2: Storage<T>> $op<&Matrix<T, S2>>
+            for &Matrix<T, S>
+        {
+            type Output = Matrix<T>;
+
+            fn $method(self, rhs: &Matrix<T, S2>) -> Matrix<T> {
+                self.zip_with(rhs, |a, b| a.clone().$method(b.clone()))
+            }
+        }
+
+        impl<T: $op<Output = T> + Clone, S2: Storage<T>> $op<&Matrix<T, S2>> for Matrix<T> {
+            type Output = Matrix<T>;
+
+            fn $method(mut self, rhs: &Matrix<T, S2>) -> Matrix<T> {
+                self.$method_assign(rhs);
+                self
+            }
+        }
+
+        impl<T: $op<Output = T> + Clone> $op for Matrix<T> {
+            type Output = Matrix<T>;
+
+            fn $method(mut self, rhs: Matrix<T>) -> Matrix<T> {
+                self.$method_assign(&rhs);
+                self
+            }
         }
+    };
+}
+
+impl_entrywise_op!(Add, add, AddAssign, add_assign);
+impl_entrywise_op!(Sub, sub, SubAssign, sub_assign);
+
+/// the cache-oblivious [`Matrix::mul`], only for borrowed matrices so that `a.mul(&b)`
+/// still finds the inherent method when `Mul` is in scope
+impl<T, S: Storage<T>, S2: Storage<T>> Mul<&Matrix<T, S2>> for &Matrix<T, S>
+where
+    T: Mul<Output = T> + Add<Output = T> + Default + Clone + 'static,
+{
+    type Output = Matrix<T>;
+
+    fn mul(self, rhs: &Matrix<T, S2>) -> Matrix<T> {
+        Matrix::mul(self, rhs)
+    }
+}
+
+impl<T: PartialEq, S: Storage<T>, S2: Storage<T>> PartialEq<Matrix<T, S2>> for Matrix<T, S> {
+    fn eq(&self, other: &Matrix<T, S2>) -> bool {
+        (self.n, self.m) == (other.n, other.m) && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq, S: Storage<T>> Eq for Matrix<T, S> {}
+
+impl<T: fmt::Debug, S: Storage<T>> fmt::Debug for Matrix<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rows: Vec<Vec<&T>> = self.iter_rows().map(|row| row.collect()).collect();
+        f.debug_struct("Matrix")
+            .field("rows", &self.n)
+            .field("cols", &self.m)
+            .field("data", &rows)
+            .finish()
+    }
+}
 
-        if ax1 - ax0 > 16 {
-            let mid_x = ax0.midpoint(ax1);
-            self.mul_rec(ax0, mid_x, ay0, ay1, other, by0, by1, result, cx0, cx1);
-            self.mul_rec(mid_x, ax1, ay0, ay1, other, by0, by1, result, cx0, cx1);
-            return;
+/// one row per line with the columns aligned, a precision applies to every entry
+impl<T: fmt::Display, S: Storage<T>> fmt::Display for Matrix<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries: Vec<String> = match f.precision() {
+            Some(precision) => self.iter().map(|x| format!("{x:.precision$}")).collect(),
+            None => self.iter().map(|x| x.to_string()).collect(),
+        };
+        let width = entries.iter().map(|x| x.chars().count()).max().unwrap_or(0);
+        for (i, row) in entries.chunks(self.m.max(1)).enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            for (j, x) in row.iter().enumerate() {
+                if j > 0 {
+                    write!(f, " ")?;
+                }
+                write!(f, "{x:>width$}")?;
+            }
         }
-        // if by1 - by0 > 16 {
-        let mid_x = cx0.midpoint(cx1);
-        self.mul_rec(ax0, ax1, ay0, ay1, other, by0, mid_x, result, cx0, mid_x);
-        self.mul_rec(ax0, ax1, ay0, ay1, other, mid_x, by1, result, mid_x, cx1);
+        Ok(())
     }
+}
 
-    pub fn mul(&self, other: &Matrix<T>) -> Matrix<T> {
+impl Matrix<u32> {
+    /// (self * other) mod modulus
+    ///
+    /// All entries must be in [0, modulus) and modulus must be less than 2^31.
+    /// The dot products are accumulated in u64, subtracting modulus^2 on overflow of it,
+    /// so only one Barrett reduction per entry is needed.
+    pub fn mul_mod(&self, other: &Matrix<u32>, modulus: u32) -> Matrix<u32> {
         assert_eq!(self.m, other.n);
+        assert!(modulus > 0 && modulus < 1 << 31);
+        let sqr_modulus = modulus as u64 * modulus as u64;
+        let magic_number = cal_barrett_reduction_magic_number(modulus);
+        let other_transposed = other.transpose();
         let mut result = Matrix::new(self.n, other.m);
-        self.mul_rec(
-            0,
-            self.n,
-            0,
-            self.m,
-            other,
-            0,
-            other.m,
-            &mut result,
-            0,
-            other.m,
-        );
+        for i in 0..self.n {
+            let row = &self.data[i * self.m..(i + 1) * self.m];
+            for j in 0..other.m {
+                let col = &other_transposed.data[j * other.n..(j + 1) * other.n];
+                let mut sum = 0u64;
+                for (&a, &b) in row.iter().zip(col) {
+                    // sum < modulus^2 < 2^62, so sum + a * b never overflows
+                    sum += a as u64 * b as u64;
+                    if sum >= sqr_modulus {
+                        sum -= sqr_modulus;
+                    }
+                }
+                result.set(i, j, barrett_reduction_mod(sum, modulus, magic_number));
+            }
+        }
+        result
+    }
+
+    /// self^exp mod modulus, self must be square
+    pub fn pow_mod(&self, mut exp: u64, modulus: u32) -> Matrix<u32> {
+        assert_eq!(self.n, self.m);
+        let mut result = Matrix::identity(self.n);
+        for i in 0..self.n {
+            result.set(i, i, 1 % modulus);
+        }
+        let mut base = self.clone();
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = result.mul_mod(&base, modulus);
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = base.mul_mod(&base, modulus);
+            }
+        }
         result
     }
 }
@@ -306,6 +873,83 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_matrix_transpose_in_place() {
+        for (n, m) in [
+            (0, 3),
+            (1, 7),
+            (7, 1),
+            (2, 3),
+            (13, 29),
+            (64, 96),
+            (96, 40),
+            (N, 37),
+        ] {
+            let matrix: Vec<usize> = (0..n * m).collect();
+            let expected = Matrix::from_vec(matrix.clone(), n, m)
+                .transpose()
+                .into_storage();
+
+            let mut transposed = matrix.clone();
+            matrix_transpose_in_place(&mut transposed, n, m);
+            assert_eq!(transposed, expected);
+            for block in [2, 4, 8, 16, 32] {
+                let mut transposed = matrix.clone();
+                matrix_transpose_in_place_blocked(&mut transposed, n, m, block);
+                assert_eq!(transposed, expected, "{n} x {m}, block {block}");
+            }
+
+            let mut transposed = Matrix::from_vec(matrix, n, m);
+            transposed.transpose_in_place();
+            assert_eq!((transposed.n, transposed.m), (m, n));
+            assert_eq!(transposed.into_storage(), expected);
+        }
+    }
+
+    #[test]
+    fn test_matrix_api() {
+        let mut a = Matrix::from_vec(vec![1, 2, 3, 4, 5, 6], 2, 3);
+        assert_eq!((a.rows(), a.cols()), (2, 3));
+        assert_eq!(a[(1, 2)], 6);
+        a[(0, 1)] = 7;
+        assert_eq!(a.row(0).copied().collect::<Vec<_>>(), [1, 7, 3]);
+        assert_eq!(a.col(2).copied().collect::<Vec<_>>(), [3, 6]);
+        assert_eq!(
+            a.iter_rows()
+                .map(|row| row.sum::<i32>())
+                .collect::<Vec<_>>(),
+            [11, 15]
+        );
+        assert_eq!(
+            a.iter_cols().map(|col| col.len()).collect::<Vec<_>>(),
+            [2, 2, 2]
+        );
+
+        let b = a.map(|&x| x * 10);
+        assert_eq!(b, Matrix::from_vec(vec![10, 70, 30, 40, 50, 60], 2, 3));
+        assert_eq!(a.zip_with(&b, |&x, &y| y - x), a.map(|&x| x * 9));
+        assert_eq!(&a + &b, a.map(|&x| x * 11));
+        assert_eq!(&b - &a, a.map(|&x| x * 9));
+        assert_eq!(a.clone() + &b, b.clone() + a.clone());
+        let mut c = b.clone();
+        c -= &b;
+        assert_eq!(c, Matrix::new(2, 3));
+        assert_ne!(a, a.transpose());
+        assert_ne!(Matrix::<i32>::new(2, 3), Matrix::new(3, 2));
+
+        let product = &a * &a.transpose();
+        assert_eq!(product, a.simple_mul(&a.transpose()));
+        assert_eq!(&product * &Matrix::identity(2), product);
+
+        assert_eq!(
+            format!("{a:?}"),
+            "Matrix { rows: 2, cols: 3, data: [[1, 7, 3], [4, 5, 6]] }"
+        );
+        assert_eq!(b.to_string(), "10 70 30\n40 50 60");
+        let halves = Matrix::from_vec(vec![0.5, -12.25], 2, 1);
+        assert_eq!(format!("{halves:.1}"), "  0.5\n-12.2");
+    }
+
     #[test]
     fn test_matrix_mul() {
         let mut rng = rand::rng();
@@ -322,4 +966,39 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_matrix_pow() {
+        let matrix = Matrix::from_vec(vec![1u64, 1, 1, 0], 2, 2);
+        assert_eq!(matrix.pow(0).data, vec![1, 0, 0, 1]);
+        assert_eq!(matrix.pow(1).data, vec![1, 1, 1, 0]);
+        // [[F(n + 1), F(n)], [F(n), F(n - 1)]]
+        assert_eq!(matrix.pow(10).data, vec![89, 55, 55, 34]);
+    }
+
+    #[test]
+    fn test_matrix_pow_mod() {
+        const M: u32 = 1_000_000_007;
+        const K: usize = 20;
+        let mut rng = rand::rng();
+        let uniform = Uniform::new(0, M).unwrap();
+        let matrix = Matrix::from_vec(uniform.sample_iter(&mut rng).take(K * K).collect(), K, K);
+        let mut expected = Matrix::identity(K);
+        for exp in 0..10 {
+            assert_eq!(matrix.pow_mod(exp, M).data, expected.data);
+            let mut next = Matrix::new(K, K);
+            for i in 0..K {
+                for j in 0..K {
+                    let mut sum = 0u64;
+                    for k in 0..K {
+                        sum = (sum + *expected.get(i, k) as u64 * *matrix.get(k, j) as u64)
+                            % M as u64;
+                    }
+                    next.set(i, j, sum as u32);
+                }
+            }
+            expected = next;
+        }
+        assert_eq!(Matrix::<u32>::identity(3).pow_mod(5, 1).data, vec![0; 9]);
+    }
 }
@@ -0,0 +1,334 @@
+//! Cache-oblivious 2D convolution and correlation.
+//!
+//! Every output entry (i, j) sums input(i + p - pad, j + q - pad) * kernel(p, q) over the
+//! kernel, so the work is a 4D box of output rows, output columns, kernel rows and kernel
+//! columns. The recursion halves the longest side of the box, like `mul_rec` does for
+//! the 3D box of a product, until every side is small, and the leaves read the input,
+//! the kernel and the output in blocks that fit any cache. Inputs and kernels can have
+//! any shape.
+
+use std::ops::{Add, Mul};
+
+use super::cache_oblivious::Matrix;
+use super::matrix_view::{MatrixMut, MatrixRef};
+use super::storage::Storage;
+
+/// sides of the box at most this are not split
+const LEAF: usize = 16;
+
+/// Which part of the full output is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvolutionMode {
+    /// every position where the kernel overlaps the input, n + kn - 1 x m + km - 1
+    Full,
+    /// the center of the full output with the shape of the input
+    Same,
+    /// only positions where the kernel is inside the input, n - kn + 1 x m - km + 1
+    Valid,
+}
+
+impl ConvolutionMode {
+    /// (output size, padding before the input) along a side of length n with a kernel of kn
+    fn side(self, n: usize, kn: usize) -> (usize, usize) {
+        if n == 0 || kn == 0 {
+            return (0, 0);
+        }
+        match self {
+            ConvolutionMode::Full => (n + kn - 1, kn - 1),
+            ConvolutionMode::Same => (n, kn / 2),
+            ConvolutionMode::Valid => ((n + 1).saturating_sub(kn), 0),
+        }
+    }
+
+    /// the shape of the output for an n x m input and a kn x km kernel
+    pub fn output_shape(self, (n, m): (usize, usize), (kn, km): (usize, usize)) -> (usize, usize) {
+        (self.side(n, kn).0, self.side(m, km).0)
+    }
+}
+
+/// the input, the kernel and the padding of a correlation
+struct Correlation<'a, A: ?Sized, K: ?Sized> {
+    input: &'a A,
+    kernel: &'a K,
+    pad: (usize, usize),
+}
+
+impl<A: ?Sized, K: ?Sized> Correlation<'_, A, K> {
+    /// output[i][j] += input[i + p - pad][j + q - pad] * kernel[p][q] for (i, j, p, q) in
+    /// the box, skipping the input positions outside of it
+    #[allow(clippy::too_many_arguments)]
+    fn rec<T, C>(
+        &self,
+        output: &mut C,
+        i0: usize,
+        i1: usize,
+        j0: usize,
+        j1: usize,
+        p0: usize,
+        p1: usize,
+        q0: usize,
+        q1: usize,
+    ) where
+        T: Mul<Output = T> + Add<Output = T> + Clone,
+        A: MatrixRef<T>,
+        K: MatrixRef<T>,
+        C: MatrixMut<T> + ?Sized,
+    {
+        let sides = [i1 - i0, j1 - j0, p1 - p0, q1 - q0];
+        let longest = (0..4).max_by_key(|&d| sides[d]).unwrap();
+        if sides[longest] <= LEAF {
+            self.leaf(output, i0, i1, j0, j1, p0, p1, q0, q1);
+            return;
+        }
+        match longest {
+            0 => {
+                let mid = i0.midpoint(i1);
+                self.rec(output, i0, mid, j0, j1, p0, p1, q0, q1);
+                self.rec(output, mid, i1, j0, j1, p0, p1, q0, q1);
+            }
+            1 => {
+                let mid = j0.midpoint(j1);
+                self.rec(output, i0, i1, j0, mid, p0, p1, q0, q1);
+                self.rec(output, i0, i1, mid, j1, p0, p1, q0, q1);
+            }
+            2 => {
+                let mid = p0.midpoint(p1);
+                self.rec(output, i0, i1, j0, j1, p0, mid, q0, q1);
+                self.rec(output, i0, i1, j0, j1, mid, p1, q0, q1);
+            }
+            _ => {
+                let mid = q0.midpoint(q1);
+                self.rec(output, i0, i1, j0, j1, p0, p1, q0, mid);
+                self.rec(output, i0, i1, j0, j1, p0, p1, mid, q1);
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn leaf<T, C>(
+        &self,
+        output: &mut C,
+        i0: usize,
+        i1: usize,
+        j0: usize,
+        j1: usize,
+        p0: usize,
+        p1: usize,
+        q0: usize,
+        q1: usize,
+    ) where
+        T: Mul<Output = T> + Add<Output = T> + Clone,
+        A: MatrixRef<T>,
+        K: MatrixRef<T>,
+        C: MatrixMut<T> + ?Sized,
+    {
+        let (n, m) = (self.input.rows(), self.input.cols());
+        let (pad_rows, pad_cols) = self.pad;
+        for i in i0..i1 {
+            // the kernel rows that land inside the input
+            let p_start = p0.max(pad_rows.saturating_sub(i));
+            let p_end = p1.min((n + pad_rows).saturating_sub(i));
+            for j in j0..j1 {
+                let q_start = q0.max(pad_cols.saturating_sub(j));
+                let q_end = q1.min((m + pad_cols).saturating_sub(j));
+                if p_start >= p_end || q_start >= q_end {
+                    continue;
+                }
+                let mut sum = output.entry(i, j).clone();
+                for p in p_start..p_end {
+                    for q in q_start..q_end {
+                        let x = self.input.entry(i + p - pad_rows, j + q - pad_cols);
+                        sum = sum + x.clone() * self.kernel.entry(p, q).clone();
+                    }
+                }
+                *output.entry_mut(i, j) = sum;
+            }
+        }
+    }
+}
+
+/// output += the correlation of `input` with `kernel`, the kernel is not flipped
+///
+/// The output must have the shape [`ConvolutionMode::output_shape`] gives.
+pub fn correlate_into<T, A, K, C>(input: &A, kernel: &K, output: &mut C, mode: ConvolutionMode)
+where
+    T: Mul<Output = T> + Add<Output = T> + Clone,
+    A: MatrixRef<T> + ?Sized,
+    K: MatrixRef<T> + ?Sized,
+    C: MatrixMut<T> + ?Sized,
+{
+    let (n, m) = (input.rows(), input.cols());
+    let (kn, km) = (kernel.rows(), kernel.cols());
+    let ((rows, pad_rows), (cols, pad_cols)) = (mode.side(n, kn), mode.side(m, km));
+    assert_eq!((output.rows(), output.cols()), (rows, cols));
+    let correlation = Correlation {
+        input,
+        kernel,
+        pad: (pad_rows, pad_cols),
+    };
+    correlation.rec(output, 0, rows, 0, cols, 0, kn, 0, km);
+}
+
+/// the kernel rotated by 180 degrees
+fn flipped<T: Default + Clone, K: MatrixRef<T> + ?Sized>(kernel: &K) -> Matrix<T> {
+    let (kn, km) = (kernel.rows(), kernel.cols());
+    let mut result = Matrix::new(kn, km);
+    for p in 0..kn {
+        for q in 0..km {
+            result.set(p, q, kernel.entry(kn - 1 - p, km - 1 - q).clone());
+        }
+    }
+    result
+}
+
+/// output += the convolution of `input` with `kernel`, the correlation with the
+/// kernel flipped in both directions
+pub fn convolve_into<T, A, K, C>(input: &A, kernel: &K, output: &mut C, mode: ConvolutionMode)
+where
+    T: Mul<Output = T> + Add<Output = T> + Default + Clone,
+    A: MatrixRef<T> + ?Sized,
+    K: MatrixRef<T> + ?Sized,
+    C: MatrixMut<T> + ?Sized,
+{
+    correlate_into(input, &flipped(kernel), output, mode);
+}
+
+/// the correlation straight from the definition over the full output, for testing
+pub fn correlate_naive<T, A, K>(input: &A, kernel: &K, mode: ConvolutionMode) -> Matrix<T>
+where
+    T: Mul<Output = T> + Add<Output = T> + Default + Clone,
+    A: MatrixRef<T> + ?Sized,
+    K: MatrixRef<T> + ?Sized,
+{
+    let (n, m) = (input.rows() as isize, input.cols() as isize);
+    let (kn, km) = (kernel.rows() as isize, kernel.cols() as isize);
+    let (rows, cols) =
+        mode.output_shape((input.rows(), input.cols()), (kernel.rows(), kernel.cols()));
+    // where the output starts in the full output
+    let (start_rows, start_cols) = match mode {
+        ConvolutionMode::Full => (0, 0),
+        ConvolutionMode::Same => ((kn - 1) / 2, (km - 1) / 2),
+        ConvolutionMode::Valid => (kn - 1, km - 1),
+    };
+    let mut output = Matrix::new(rows, cols);
+    for i in 0..rows {
+        for j in 0..cols {
+            let (fi, fj) = (i as isize + start_rows, j as isize + start_cols);
+            let mut sum = T::default();
+            for p in 0..kn {
+                for q in 0..km {
+                    let (x, y) = (fi + p - (kn - 1), fj + q - (km - 1));
+                    if (0..n).contains(&x) && (0..m).contains(&y) {
+                        let value = input.entry(x as usize, y as usize).clone();
+                        sum = sum + value * kernel.entry(p as usize, q as usize).clone();
+                    }
+                }
+            }
+            output.set(i, j, sum);
+        }
+    }
+    output
+}
+
+/// the convolution straight from the definition, for testing
+pub fn convolve_naive<T, A, K>(input: &A, kernel: &K, mode: ConvolutionMode) -> Matrix<T>
+where
+    T: Mul<Output = T> + Add<Output = T> + Default + Clone,
+    A: MatrixRef<T> + ?Sized,
+    K: MatrixRef<T> + ?Sized,
+{
+    correlate_naive(input, &flipped(kernel), mode)
+}
+
+impl<T: Mul<Output = T> + Add<Output = T> + Default + Clone, S: Storage<T>> Matrix<T, S> {
+    /// the convolution with `kernel`, see [`convolve_into`]
+    pub fn convolve<K: MatrixRef<T> + ?Sized>(
+        &self,
+        kernel: &K,
+        mode: ConvolutionMode,
+    ) -> Matrix<T> {
+        let (rows, cols) =
+            mode.output_shape((self.rows(), self.cols()), (kernel.rows(), kernel.cols()));
+        let mut output = Matrix::new(rows, cols);
+        convolve_into(self, kernel, &mut output, mode);
+        output
+    }
+
+    /// the correlation with `kernel`, see [`correlate_into`]
+    pub fn correlate<K: MatrixRef<T> + ?Sized>(
+        &self,
+        kernel: &K,
+        mode: ConvolutionMode,
+    ) -> Matrix<T> {
+        let (rows, cols) =
+            mode.output_shape((self.rows(), self.cols()), (kernel.rows(), kernel.cols()));
+        let mut output = Matrix::new(rows, cols);
+        correlate_into(self, kernel, &mut output, mode);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ConvolutionMode::*;
+    use rand::distr::{Distribution, Uniform};
+    use rand::{SeedableRng, rngs::StdRng};
+
+    fn sample(n: usize, m: usize, seed: u64) -> Matrix<i64> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let uniform = Uniform::new(-9, 10).unwrap();
+        Matrix::from_vec(uniform.sample_iter(&mut rng).take(n * m).collect(), n, m)
+    }
+
+    #[test]
+    fn test_convolution_small() {
+        let input = Matrix::from_vec(vec![1, 2, 3, 4, 5, 6], 2, 3);
+        let kernel = Matrix::from_vec(vec![1, 0, 0, -1], 2, 2);
+        let full = Matrix::from_vec(vec![1, 2, 3, 0, 4, 4, 4, -3, 0, -4, -5, -6], 3, 4);
+        assert_eq!(input.convolve(&kernel, Full), full);
+        // the center with the input's shape, and the positions inside the input
+        assert_eq!(
+            input.convolve(&kernel, Same),
+            Matrix::from_vec(vec![1, 2, 3, 4, 4, 4], 2, 3)
+        );
+        assert_eq!(
+            input.convolve(&kernel, Valid),
+            Matrix::from_vec(vec![4, 4], 1, 2)
+        );
+        assert_eq!(
+            input.correlate(&kernel, Valid),
+            Matrix::from_vec(vec![-4, -4], 1, 2)
+        );
+        // a kernel larger than the input has no valid positions
+        assert_eq!(input.convolve(&sample(3, 1, 0), Valid).rows(), 0);
+    }
+
+    #[test]
+    fn test_convolution_matches_naive() {
+        for ((n, m), (kn, km)) in [
+            ((1, 1), (1, 1)),
+            ((40, 70), (3, 5)),
+            ((70, 40), (4, 4)),
+            ((33, 90), (20, 17)),
+            ((10, 60), (25, 40)),
+            ((100, 3), (7, 2)),
+        ] {
+            let input = sample(n, m, 1);
+            let kernel = sample(kn, km, 2);
+            for mode in [Full, Same, Valid] {
+                let expected = convolve_naive(&input, &kernel, mode);
+                assert_eq!(input.convolve(&kernel, mode), expected, "{mode:?}");
+                let expected = correlate_naive(&input, &kernel, mode);
+                assert_eq!(input.correlate(&kernel, mode), expected, "{mode:?}");
+            }
+        }
+
+        // same mode keeps the shape of the input, also for even kernels
+        let input = sample(31, 17, 3);
+        assert_eq!(
+            input.convolve(&sample(6, 3, 4), Same).iter().count(),
+            31 * 17
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Cache-oblivious dynamic programming: all-pairs shortest paths, longest common
+//! subsequence and edit distance.
+//!
+//! The sequence tables are filled like `matrix_transpose_cache_oblivious_fast_rec`
+//! walks a matrix: the longer side of the block is halved, and the top (left) half is
+//! done before the bottom (right) half, which is all the cell dependencies need.
+//! Floyd–Warshall updates d[i][j] from d[i][k] and d[k][j] of the same matrix, so its
+//! box of rows, columns and pivots is cut in all three sides at once and the eight
+//! parts run in the order of Park, Penner and Prasanna that keeps the pivots in order.
+
+use std::ops::Add;
+
+use super::cache_oblivious::Matrix;
+use super::matrix_view::MatrixMut;
+
+/// sides of a block at most this are done with loops
+const LEAF: usize = 16;
+
+/// the Floyd–Warshall leaves do n^3 work for n^2 entries, so they can be larger
+const FLOYD_WARSHALL_LEAF: usize = 64;
+
+/// d[i][j] = min(d[i][j], d[i][k] + d[k][j]) for i in rows, j in cols and k in pivots
+/// taken in order
+fn floyd_warshall_leaf<T, M>(
+    dist: &mut M,
+    (i0, i1): (usize, usize),
+    (j0, j1): (usize, usize),
+    (k0, k1): (usize, usize),
+) where
+    T: Add<Output = T> + PartialOrd + Clone,
+    M: MatrixMut<T> + ?Sized,
+{
+    for k in k0..k1 {
+        for i in i0..i1 {
+            let d_ik = dist.entry(i, k).clone();
+            for j in j0..j1 {
+                let through_k = d_ik.clone() + dist.entry(k, j).clone();
+                if through_k < *dist.entry(i, j) {
+                    *dist.entry_mut(i, j) = through_k;
+                }
+            }
+        }
+    }
+}
+
+fn floyd_warshall_rec<T, M>(
+    dist: &mut M,
+    rows: (usize, usize),
+    cols: (usize, usize),
+    pivots: (usize, usize),
+) where
+    T: Add<Output = T> + PartialOrd + Clone,
+    M: MatrixMut<T> + ?Sized,
+{
+    let side = |(start, end): (usize, usize)| end - start;
+    if side(rows).max(side(cols)).max(side(pivots)) <= FLOYD_WARSHALL_LEAF {
+        floyd_warshall_leaf(dist, rows, cols, pivots);
+        return;
+    }
+    let halves = |(start, end): (usize, usize)| {
+        let mid = start.midpoint(end);
+        [(start, mid), (mid, end)]
+    };
+    let ([r1, r2], [c1, c2], [k1, k2]) = (halves(rows), halves(cols), halves(pivots));
+    // the first pivots forward over the quadrants, the second ones backward
+    for (rows, cols, pivots) in [
+        (r1, c1, k1),
+        (r1, c2, k1),
+        (r2, c1, k1),
+        (r2, c2, k1),
+        (r2, c2, k2),
+        (r2, c1, k2),
+        (r1, c2, k2),
+        (r1, c1, k2),
+    ] {
+        if side(rows) > 0 && side(cols) > 0 && side(pivots) > 0 {
+            floyd_warshall_rec(dist, rows, cols, pivots);
+        }
+    }
+}
+
+/// turn the edge weights of a square matrix into shortest path lengths in place
+///
+/// Missing edges must be a value that stays larger than every path when added to
+/// another, like `f64::INFINITY` or `u64::MAX / 2`, and the diagonal is usually zero.
+pub fn floyd_warshall<T, M>(dist: &mut M)
+where
+    T: Add<Output = T> + PartialOrd + Clone,
+    M: MatrixMut<T> + ?Sized,
+{
+    assert_eq!(dist.rows(), dist.cols());
+    let n = dist.rows();
+    floyd_warshall_rec(dist, (0, n), (0, n), (0, n));
+}
+
+/// [`floyd_warshall`] with the textbook k-i-j loops
+pub fn floyd_warshall_naive<T, M>(dist: &mut M)
+where
+    T: Add<Output = T> + PartialOrd + Clone,
+    M: MatrixMut<T> + ?Sized,
+{
+    assert_eq!(dist.rows(), dist.cols());
+    let n = dist.rows();
+    floyd_warshall_leaf(dist, (0, n), (0, n), (0, n));
+}
+
+/// fill the cells (i, j) of the block with i, j >= 1 in an order that respects the
+/// dependencies on (i - 1, j), (i, j - 1) and (i - 1, j - 1)
+fn fill_table_rec<C: Fn(&Matrix<u32>, usize, usize) -> u32>(
+    table: &mut Matrix<u32>,
+    cell: &C,
+    (i0, i1): (usize, usize),
+    (j0, j1): (usize, usize),
+) {
+    if i1 - i0 <= LEAF && j1 - j0 <= LEAF {
+        for i in i0..i1 {
+            for j in j0..j1 {
+                let value = cell(table, i, j);
+                table.set(i, j, value);
+            }
+        }
+        return;
+    }
+    if i1 - i0 > j1 - j0 {
+        let mid = i0.midpoint(i1);
+        fill_table_rec(table, cell, (i0, mid), (j0, j1));
+        fill_table_rec(table, cell, (mid, i1), (j0, j1));
+        return;
+    }
+    let mid = j0.midpoint(j1);
+    fill_table_rec(table, cell, (i0, i1), (j0, mid));
+    fill_table_rec(table, cell, (i0, i1), (mid, j1));
+}
+
+/// an n + 1 x m + 1 table with `border` in the first row and column and the other cells
+/// filled by `cell`, recursively or row by row
+fn fill_table<C: Fn(&Matrix<u32>, usize, usize) -> u32>(
+    n: usize,
+    m: usize,
+    border: impl Fn(usize) -> u32,
+    cell: C,
+    recursive: bool,
+) -> Matrix<u32> {
+    let mut table = Matrix::new(n + 1, m + 1);
+    for i in 0..=n {
+        table.set(i, 0, border(i));
+    }
+    for j in 0..=m {
+        table.set(0, j, border(j));
+    }
+    if recursive {
+        fill_table_rec(&mut table, &cell, (1, n + 1), (1, m + 1));
+    } else {
+        for i in 1..=n {
+            for j in 1..=m {
+                let value = cell(&table, i, j);
+                table.set(i, j, value);
+            }
+        }
+    }
+    table
+}
+
+fn lcs_cell<T: Eq>(a: &[T], b: &[T]) -> impl Fn(&Matrix<u32>, usize, usize) -> u32 {
+    move |table, i, j| {
+        if a[i - 1] == b[j - 1] {
+            table.get(i - 1, j - 1) + 1
+        } else {
+            *table.get(i - 1, j).max(table.get(i, j - 1))
+        }
+    }
+}
+
+fn edit_distance_cell<T: Eq>(a: &[T], b: &[T]) -> impl Fn(&Matrix<u32>, usize, usize) -> u32 {
+    move |table, i, j| {
+        let replace = table.get(i - 1, j - 1) + u32::from(a[i - 1] != b[j - 1]);
+        let delete = table.get(i - 1, j) + 1;
+        let insert = table.get(i, j - 1) + 1;
+        replace.min(delete).min(insert)
+    }
+}
+
+/// the (a.len() + 1) x (b.len() + 1) table of the lengths of the longest common
+/// subsequences of the prefixes of a and b
+pub fn lcs_table<T: Eq>(a: &[T], b: &[T]) -> Matrix<u32> {
+    fill_table(a.len(), b.len(), |_| 0, lcs_cell(a, b), true)
+}
+
+/// [`lcs_table`] row by row
+pub fn lcs_table_naive<T: Eq>(a: &[T], b: &[T]) -> Matrix<u32> {
+    fill_table(a.len(), b.len(), |_| 0, lcs_cell(a, b), false)
+}
+
+/// a longest common subsequence, read back from the table
+pub fn lcs<T: Eq + Clone>(a: &[T], b: &[T]) -> Vec<T> {
+    let table = lcs_table(a, b);
+    let (mut i, mut j) = (a.len(), b.len());
+    let mut result = Vec::with_capacity(*table.get(i, j) as usize);
+    while i > 0 && j > 0 {
+        if a[i - 1] == b[j - 1] {
+            result.push(a[i - 1].clone());
+            (i, j) = (i - 1, j - 1);
+        } else if table.get(i - 1, j) >= table.get(i, j - 1) {
+            i -= 1;
+        } else {
+            j -= 1;
+        }
+    }
+    result.reverse();
+    result
+}
+
+/// the (a.len() + 1) x (b.len() + 1) table of the Levenshtein distances between the
+/// prefixes of a and b
+pub fn edit_distance_table<T: Eq>(a: &[T], b: &[T]) -> Matrix<u32> {
+    fill_table(
+        a.len(),
+        b.len(),
+        |i| i as u32,
+        edit_distance_cell(a, b),
+        true,
+    )
+}
+
+/// [`edit_distance_table`] row by row
+pub fn edit_distance_table_naive<T: Eq>(a: &[T], b: &[T]) -> Matrix<u32> {
+    fill_table(
+        a.len(),
+        b.len(),
+        |i| i as u32,
+        edit_distance_cell(a, b),
+        false,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::distr::{Distribution, Uniform};
+    use rand::{Rng, SeedableRng, rngs::StdRng};
+
+    const INF: u64 = u64::MAX / 2;
+
+    fn random_graph(n: usize, density: f64, rng: &mut StdRng) -> Matrix<u64> {
+        let mut dist = Matrix::new(n, n);
+        for i in 0..n {
+            for j in 0..n {
+                let weight = if i == j {
+                    0
+                } else if rng.random_bool(density) {
+                    rng.random_range(1..100)
+                } else {
+                    INF
+                };
+                dist.set(i, j, weight);
+            }
+        }
+        dist
+    }
+
+    #[test]
+    fn test_floyd_warshall() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (n, density) in [(1, 1.), (17, 0.2), (50, 0.05), (100, 0.03), (129, 0.5)] {
+            let graph = random_graph(n, density, &mut rng);
+            let mut expected = graph.clone();
+            floyd_warshall_naive(&mut expected);
+            let mut dist = graph;
+            floyd_warshall(&mut dist);
+            assert_eq!(dist, expected);
+        }
+
+        // the path 0 -> 1 -> ... -> n - 1 goes through every pivot
+        let n = 70;
+        let mut dist = Matrix::from_vec(vec![f64::INFINITY; n * n], n, n);
+        for i in 0..n {
+            dist.set(i, i, 0.);
+            if i + 1 < n {
+                dist.set(i, i + 1, 1.);
+            }
+        }
+        floyd_warshall(&mut dist.view_mut());
+        assert_eq!(*dist.get(0, n - 1), (n - 1) as f64);
+        assert_eq!(*dist.get(n - 1, 0), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_sequence_tables() {
+        assert_eq!(lcs(b"ABCBDAB", b"BDCABA").len(), 4);
+        assert_eq!(lcs(b"AGGTAB", b"GXTXAYB"), b"GTAB");
+        assert_eq!(*edit_distance_table(b"kitten", b"sitting").get(6, 7), 3);
+        assert_eq!(*edit_distance_table(b"", b"abc").get(0, 3), 3);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let letters = Uniform::new(0u8, 4).unwrap();
+        for (n, m) in [(0, 5), (40, 40), (100, 37), (20, 300)] {
+            let a: Vec<u8> = letters.sample_iter(&mut rng).take(n).collect();
+            let b: Vec<u8> = letters.sample_iter(&mut rng).take(m).collect();
+            assert_eq!(lcs_table(&a, &b), lcs_table_naive(&a, &b));
+            assert_eq!(
+                edit_distance_table(&a, &b),
+                edit_distance_table_naive(&a, &b)
+            );
+            let common = lcs(&a, &b);
+            assert_eq!(common.len(), *lcs_table(&a, &b).get(n, m) as usize);
+        }
+    }
+}
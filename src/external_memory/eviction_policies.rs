@@ -0,0 +1,876 @@
+//! Cache eviction policies for a cache of `capacity` keys.
+//!
+//! Every online policy answers an access in O(1) (amortized for CLOCK): the recency
+//! lists are doubly linked lists threaded through one slab, addressed from a hash map.
+//! [`belady`] replays a whole trace offline and gives the optimal hit count.
+
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::hash::Hash;
+use std::io::{self, BufRead};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// An online cache replacement policy.
+pub trait CachePolicy<K> {
+    /// access `key`, returns whether it was cached; a missing key is inserted,
+    /// evicting another one if the cache is full
+    fn access(&mut self, key: K) -> bool;
+
+    fn contains(&self, key: &K) -> bool;
+
+    /// number of cached keys
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn capacity(&self) -> usize;
+}
+
+const NIL: usize = usize::MAX;
+
+struct Node<T> {
+    value: Option<T>,
+    prev: usize,
+    next: usize,
+}
+
+/// A doubly linked list inside a [`Slab`], front is the most recent.
+#[derive(Debug, Clone, Copy)]
+struct List {
+    head: usize,
+    tail: usize,
+    len: usize,
+}
+
+impl List {
+    const fn new() -> Self {
+        List {
+            head: NIL,
+            tail: NIL,
+            len: 0,
+        }
+    }
+}
+
+/// Nodes of any number of [`List`]s, freed nodes are reused.
+struct Slab<T> {
+    nodes: Vec<Node<T>>,
+    free: Vec<usize>,
+}
+
+impl<T> Slab<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        Slab {
+            nodes: Vec::with_capacity(capacity),
+            free: Vec::new(),
+        }
+    }
+
+    fn value(&self, idx: usize) -> &T {
+        self.nodes[idx].value.as_ref().unwrap()
+    }
+
+    fn link_front(&mut self, list: &mut List, idx: usize) {
+        self.nodes[idx].prev = NIL;
+        self.nodes[idx].next = list.head;
+        if list.head != NIL {
+            self.nodes[list.head].prev = idx;
+        } else {
+            list.tail = idx;
+        }
+        list.head = idx;
+        list.len += 1;
+    }
+
+    fn unlink(&mut self, list: &mut List, idx: usize) {
+        let (prev, next) = (self.nodes[idx].prev, self.nodes[idx].next);
+        if prev != NIL {
+            self.nodes[prev].next = next;
+        } else {
+            list.head = next;
+        }
+        if next != NIL {
+            self.nodes[next].prev = prev;
+        } else {
+            list.tail = prev;
+        }
+        list.len -= 1;
+    }
+
+    fn push_front(&mut self, list: &mut List, value: T) -> usize {
+        let node = Node {
+            value: Some(value),
+            prev: NIL,
+            next: NIL,
+        };
+        let idx = match self.free.pop() {
+            Some(idx) => {
+                self.nodes[idx] = node;
+                idx
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        self.link_front(list, idx);
+        idx
+    }
+
+    fn remove(&mut self, list: &mut List, idx: usize) -> T {
+        self.unlink(list, idx);
+        self.free.push(idx);
+        self.nodes[idx].value.take().unwrap()
+    }
+
+    fn pop_back(&mut self, list: &mut List) -> Option<T> {
+        (list.tail != NIL).then(|| self.remove(list, list.tail))
+    }
+
+    /// move a node from one list to the front of another
+    fn move_to_front(&mut self, from: &mut List, to: &mut List, idx: usize) {
+        self.unlink(from, idx);
+        self.link_front(to, idx);
+    }
+}
+
+/// Least recently used.
+pub struct Lru<K> {
+    capacity: usize,
+    map: HashMap<K, usize>,
+    slab: Slab<K>,
+    list: List,
+}
+
+impl<K: Hash + Eq + Clone> Lru<K> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Lru {
+            capacity,
+            map: HashMap::with_capacity(capacity),
+            slab: Slab::with_capacity(capacity),
+            list: List::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> CachePolicy<K> for Lru<K> {
+    fn access(&mut self, key: K) -> bool {
+        if let Some(&idx) = self.map.get(&key) {
+            let list = &mut self.list;
+            self.slab.unlink(list, idx);
+            self.slab.link_front(list, idx);
+            return true;
+        }
+        if self.map.len() == self.capacity {
+            let victim = self.slab.pop_back(&mut self.list).unwrap();
+            self.map.remove(&victim);
+        }
+        let idx = self.slab.push_front(&mut self.list, key.clone());
+        self.map.insert(key, idx);
+        false
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        self.map.contains_key(key)
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// First in, first out: hits do not change the eviction order.
+pub struct Fifo<K> {
+    capacity: usize,
+    map: HashMap<K, usize>,
+    slab: Slab<K>,
+    queue: List,
+}
+
+impl<K: Hash + Eq + Clone> Fifo<K> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Fifo {
+            capacity,
+            map: HashMap::with_capacity(capacity),
+            slab: Slab::with_capacity(capacity),
+            queue: List::new(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> CachePolicy<K> for Fifo<K> {
+    fn access(&mut self, key: K) -> bool {
+        if self.map.contains_key(&key) {
+            return true;
+        }
+        if self.map.len() == self.capacity {
+            let victim = self.slab.pop_back(&mut self.queue).unwrap();
+            self.map.remove(&victim);
+        }
+        let idx = self.slab.push_front(&mut self.queue, key.clone());
+        self.map.insert(key, idx);
+        false
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        self.map.contains_key(key)
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// Least frequently used, ties are broken by recency.
+///
+/// Keys are kept in one list per access count, and the smallest non-empty count is
+/// tracked: a hit moves a key to the next list, and a new key always starts at 1.
+pub struct Lfu<K> {
+    capacity: usize,
+    /// key -> (access count, node)
+    map: HashMap<K, (u64, usize)>,
+    slab: Slab<K>,
+    buckets: HashMap<u64, List>,
+    min_count: u64,
+}
+
+impl<K: Hash + Eq + Clone> Lfu<K> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Lfu {
+            capacity,
+            map: HashMap::with_capacity(capacity),
+            slab: Slab::with_capacity(capacity),
+            buckets: HashMap::new(),
+            min_count: 0,
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> CachePolicy<K> for Lfu<K> {
+    fn access(&mut self, key: K) -> bool {
+        if let Some((count, idx)) = self.map.get_mut(&key) {
+            let mut from = self.buckets.remove(count).unwrap();
+            let mut to = self.buckets.remove(&(*count + 1)).unwrap_or(List::new());
+            self.slab.move_to_front(&mut from, &mut to, *idx);
+            if from.len > 0 {
+                self.buckets.insert(*count, from);
+            } else if self.min_count == *count {
+                self.min_count += 1;
+            }
+            *count += 1;
+            self.buckets.insert(*count, to);
+            return true;
+        }
+        if self.map.len() == self.capacity {
+            let bucket = self.buckets.get_mut(&self.min_count).unwrap();
+            let victim = self.slab.pop_back(bucket).unwrap();
+            if bucket.len == 0 {
+                self.buckets.remove(&self.min_count);
+            }
+            self.map.remove(&victim);
+        }
+        let bucket = self.buckets.entry(1).or_insert(List::new());
+        let idx = self.slab.push_front(bucket, key.clone());
+        self.map.insert(key, (1, idx));
+        self.min_count = 1;
+        false
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        self.map.contains_key(key)
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// Second chance: a ring of slots with a reference bit and a hand sweeping over them.
+pub struct Clock<K> {
+    capacity: usize,
+    map: HashMap<K, usize>,
+    /// (key, referenced)
+    slots: Vec<(K, bool)>,
+    hand: usize,
+}
+
+impl<K: Hash + Eq + Clone> Clock<K> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        Clock {
+            capacity,
+            map: HashMap::with_capacity(capacity),
+            slots: Vec::with_capacity(capacity),
+            hand: 0,
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> CachePolicy<K> for Clock<K> {
+    fn access(&mut self, key: K) -> bool {
+        if let Some(&slot) = self.map.get(&key) {
+            self.slots[slot].1 = true;
+            return true;
+        }
+        if self.slots.len() < self.capacity {
+            self.map.insert(key.clone(), self.slots.len());
+            self.slots.push((key, false));
+            return false;
+        }
+        // clear reference bits until an unreferenced slot comes under the hand
+        while self.slots[self.hand].1 {
+            self.slots[self.hand].1 = false;
+            self.hand = (self.hand + 1) % self.capacity;
+        }
+        let (victim, _) = std::mem::replace(&mut self.slots[self.hand], (key.clone(), false));
+        self.map.remove(&victim);
+        self.map.insert(key, self.hand);
+        self.hand = (self.hand + 1) % self.capacity;
+        false
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        self.map.contains_key(key)
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// Evicts a uniformly random key.
+pub struct RandomEviction<K> {
+    capacity: usize,
+    map: HashMap<K, usize>,
+    keys: Vec<K>,
+    rng: StdRng,
+}
+
+impl<K: Hash + Eq + Clone> RandomEviction<K> {
+    pub fn new(capacity: usize, seed: u64) -> Self {
+        assert!(capacity > 0);
+        RandomEviction {
+            capacity,
+            map: HashMap::with_capacity(capacity),
+            keys: Vec::with_capacity(capacity),
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> CachePolicy<K> for RandomEviction<K> {
+    fn access(&mut self, key: K) -> bool {
+        if self.map.contains_key(&key) {
+            return true;
+        }
+        if self.keys.len() == self.capacity {
+            let pos = self.rng.random_range(0..self.keys.len());
+            let victim = self.keys.swap_remove(pos);
+            self.map.remove(&victim);
+            if pos < self.keys.len() {
+                *self.map.get_mut(&self.keys[pos]).unwrap() = pos;
+            }
+        }
+        self.map.insert(key.clone(), self.keys.len());
+        self.keys.push(key);
+        false
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        self.map.contains_key(key)
+    }
+
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArcList {
+    T1,
+    T2,
+    B1,
+    B2,
+}
+
+/// Adaptive replacement cache (Megiddo and Modha).
+///
+/// T1 holds keys seen once recently and T2 keys seen at least twice, B1 and B2 remember
+/// the keys recently evicted from them. A hit in B1 means T1 was too small and grows
+/// the target size `p` of T1, a hit in B2 shrinks it.
+pub struct ArcCache<K> {
+    capacity: usize,
+    /// target size of T1
+    p: usize,
+    map: HashMap<K, (ArcList, usize)>,
+    slab: Slab<K>,
+    lists: [List; 4],
+}
+
+impl<K: Hash + Eq + Clone> ArcCache<K> {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0);
+        ArcCache {
+            capacity,
+            p: 0,
+            map: HashMap::with_capacity(2 * capacity),
+            slab: Slab::with_capacity(2 * capacity),
+            lists: [List::new(); 4],
+        }
+    }
+
+    #[inline]
+    fn list_len(&self, list: ArcList) -> usize {
+        self.lists[list as usize].len
+    }
+
+    /// move the node of `key` to the front of `to`
+    fn relink(&mut self, key: &K, to: ArcList) {
+        let (from, idx) = *self.map.get(key).unwrap();
+        self.slab.unlink(&mut self.lists[from as usize], idx);
+        self.slab.link_front(&mut self.lists[to as usize], idx);
+        self.map.insert(key.clone(), (to, idx));
+    }
+
+    /// move the LRU key of `from` to the front of the ghost list `to`
+    fn demote(&mut self, from: ArcList, to: ArcList) {
+        let tail = self.lists[from as usize].tail;
+        let key = self.slab.value(tail).clone();
+        self.relink(&key, to);
+    }
+
+    /// forget the LRU key of a list
+    fn drop_lru(&mut self, list: ArcList) {
+        let key = self.slab.pop_back(&mut self.lists[list as usize]).unwrap();
+        self.map.remove(&key);
+    }
+
+    /// make room in T1 + T2 by moving one key to a ghost list
+    fn replace(&mut self, in_b2: bool) {
+        let t1 = self.list_len(ArcList::T1);
+        if t1 > 0 && (t1 > self.p || (in_b2 && t1 == self.p)) {
+            self.demote(ArcList::T1, ArcList::B1);
+        } else {
+            self.demote(ArcList::T2, ArcList::B2);
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone> CachePolicy<K> for ArcCache<K> {
+    fn access(&mut self, key: K) -> bool {
+        let c = self.capacity;
+        match self.map.get(&key).map(|&(list, _)| list) {
+            Some(ArcList::T1 | ArcList::T2) => {
+                self.relink(&key, ArcList::T2);
+                true
+            }
+            Some(ArcList::B1) => {
+                let (b1, b2) = (self.list_len(ArcList::B1), self.list_len(ArcList::B2));
+                self.p = (self.p + (b2 / b1).max(1)).min(c);
+                self.replace(false);
+                self.relink(&key, ArcList::T2);
+                false
+            }
+            Some(ArcList::B2) => {
+                let (b1, b2) = (self.list_len(ArcList::B1), self.list_len(ArcList::B2));
+                self.p = self.p.saturating_sub((b1 / b2).max(1));
+                self.replace(true);
+                self.relink(&key, ArcList::T2);
+                false
+            }
+            None => {
+                let l1 = self.list_len(ArcList::T1) + self.list_len(ArcList::B1);
+                let total = l1 + self.list_len(ArcList::T2) + self.list_len(ArcList::B2);
+                if l1 == c {
+                    if self.list_len(ArcList::T1) < c {
+                        self.drop_lru(ArcList::B1);
+                        self.replace(false);
+                    } else {
+                        self.drop_lru(ArcList::T1);
+                    }
+                } else if total >= c {
+                    if total == 2 * c {
+                        self.drop_lru(ArcList::B2);
+                    }
+                    self.replace(false);
+                }
+                let idx = self
+                    .slab
+                    .push_front(&mut self.lists[ArcList::T1 as usize], key.clone());
+                self.map.insert(key, (ArcList::T1, idx));
+                false
+            }
+        }
+    }
+
+    fn contains(&self, key: &K) -> bool {
+        matches!(self.map.get(key), Some((ArcList::T1 | ArcList::T2, _)))
+    }
+
+    fn len(&self) -> usize {
+        self.list_len(ArcList::T1) + self.list_len(ArcList::T2)
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+/// Hits and misses of one replay.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReplayStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl ReplayStats {
+    pub fn hit_ratio(&self) -> f64 {
+        self.hits as f64 / (self.hits + self.misses).max(1) as f64
+    }
+}
+
+/// run `trace` through `policy`
+pub fn replay<K: Clone>(policy: &mut (impl CachePolicy<K> + ?Sized), trace: &[K]) -> ReplayStats {
+    let mut stats = ReplayStats::default();
+    for key in trace {
+        if policy.access(key.clone()) {
+            stats.hits += 1;
+        } else {
+            stats.misses += 1;
+        }
+    }
+    stats
+}
+
+/// Belady's optimal offline policy: on a miss evict the key whose next use is furthest away
+pub fn belady<K: Hash + Eq + Clone>(trace: &[K], capacity: usize) -> ReplayStats {
+    assert!(capacity > 0);
+    // next_use[i] is the next position of trace[i], or trace.len()
+    let mut next_use = vec![trace.len(); trace.len()];
+    let mut last_seen = HashMap::new();
+    for (i, key) in trace.iter().enumerate().rev() {
+        if let Some(next) = last_seen.insert(key, i) {
+            next_use[i] = next;
+        }
+    }
+
+    // cached key -> its next use, and a max-heap of (next use, key) with stale entries
+    let mut cached: HashMap<&K, usize> = HashMap::with_capacity(capacity);
+    let mut heap = BinaryHeap::with_capacity(capacity);
+    let mut stats = ReplayStats::default();
+    for (i, key) in trace.iter().enumerate() {
+        if cached.contains_key(key) {
+            stats.hits += 1;
+        } else {
+            stats.misses += 1;
+            if cached.len() == capacity {
+                loop {
+                    let (next, victim): (usize, usize) = heap.pop().unwrap();
+                    if cached.get(&trace[victim]) == Some(&next) {
+                        cached.remove(&trace[victim]);
+                        break;
+                    }
+                }
+            }
+        }
+        cached.insert(key, next_use[i]);
+        // the position stands for the key, so K needs no Ord
+        heap.push((next_use[i], i));
+    }
+    stats
+}
+
+/// replay `trace` through every policy with the same capacity, Belady last
+pub fn compare_policies<K: Hash + Eq + Clone + 'static>(
+    trace: &[K],
+    capacity: usize,
+) -> Vec<(&'static str, ReplayStats)> {
+    let policies: Vec<(&'static str, Box<dyn CachePolicy<K>>)> = vec![
+        ("LRU", Box::new(Lru::new(capacity))),
+        ("LFU", Box::new(Lfu::new(capacity))),
+        ("FIFO", Box::new(Fifo::new(capacity))),
+        ("CLOCK", Box::new(Clock::new(capacity))),
+        ("ARC", Box::new(ArcCache::new(capacity))),
+        ("random", Box::new(RandomEviction::new(capacity, 0))),
+    ];
+    let mut result: Vec<_> = policies
+        .into_iter()
+        .map(|(name, mut policy)| (name, replay(policy.as_mut(), trace)))
+        .collect();
+    result.push(("Belady", belady(trace, capacity)));
+    result
+}
+
+/// Hit ratios of every policy for a range of cache sizes.
+pub struct EvictionReport {
+    /// (capacity, per policy results)
+    pub rows: Vec<(usize, Vec<(&'static str, ReplayStats)>)>,
+}
+
+impl EvictionReport {
+    pub fn replay<K: Hash + Eq + Clone + 'static>(trace: &[K], capacities: &[usize]) -> Self {
+        let rows = capacities
+            .iter()
+            .map(|&capacity| (capacity, compare_policies(trace, capacity)))
+            .collect();
+        EvictionReport { rows }
+    }
+}
+
+impl fmt::Display for EvictionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some((_, first)) = self.rows.first() else {
+            return Ok(());
+        };
+        write!(f, "{:>10}", "capacity")?;
+        for (name, _) in first {
+            write!(f, "{name:>8}")?;
+        }
+        writeln!(f)?;
+        for (capacity, stats) in &self.rows {
+            write!(f, "{capacity:>10}")?;
+            for (_, stats) in stats {
+                write!(f, "{:>7.1}%", 100. * stats.hit_ratio())?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// one key per line, empty lines are skipped
+pub fn read_key_trace(reader: impl BufRead) -> io::Result<Vec<String>> {
+    let mut keys = Vec::new();
+    for line in reader.lines() {
+        let line = line?;
+        let key = line.trim();
+        if !key.is_empty() {
+            keys.push(key.to_string());
+        }
+    }
+    Ok(keys)
+}
+
+/// `len` keys in [0, keys) drawn from a Zipf distribution with the given exponent
+pub fn zipf_trace(keys: usize, len: usize, exponent: f64, seed: u64) -> Vec<u64> {
+    let mut cumulative = Vec::with_capacity(keys);
+    let mut sum = 0.;
+    for k in 1..=keys {
+        sum += 1. / (k as f64).powf(exponent);
+        cumulative.push(sum);
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..len)
+        .map(|_| {
+            let x = rng.random::<f64>() * sum;
+            cumulative.partition_point(|&c| c < x).min(keys - 1) as u64
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// O(capacity) LRU with the most recent key at the back
+    fn naive_lru(trace: &[u64], capacity: usize) -> ReplayStats {
+        let mut cache = VecDeque::new();
+        let mut stats = ReplayStats::default();
+        for &key in trace {
+            if let Some(pos) = cache.iter().position(|&k| k == key) {
+                cache.remove(pos);
+                stats.hits += 1;
+            } else {
+                if cache.len() == capacity {
+                    cache.pop_front();
+                }
+                stats.misses += 1;
+            }
+            cache.push_back(key);
+        }
+        stats
+    }
+
+    /// O(capacity) LFU, ties by least recent use
+    fn naive_lfu(trace: &[u64], capacity: usize) -> ReplayStats {
+        // (key, count, last use)
+        let mut cache: Vec<(u64, u64, usize)> = Vec::new();
+        let mut stats = ReplayStats::default();
+        for (time, &key) in trace.iter().enumerate() {
+            if let Some(entry) = cache.iter_mut().find(|entry| entry.0 == key) {
+                entry.1 += 1;
+                entry.2 = time;
+                stats.hits += 1;
+                continue;
+            }
+            if cache.len() == capacity {
+                let victim = (0..cache.len())
+                    .min_by_key(|&i| (cache[i].1, cache[i].2))
+                    .unwrap();
+                cache.swap_remove(victim);
+            }
+            cache.push((key, 1, time));
+            stats.misses += 1;
+        }
+        stats
+    }
+
+    /// O(2^n) optimal number of hits by trying every eviction
+    fn brute_force_optimal(trace: &[u64], capacity: usize, cache: &mut Vec<u64>) -> u64 {
+        let Some((&key, rest)) = trace.split_first() else {
+            return 0;
+        };
+        if cache.contains(&key) {
+            return 1 + brute_force_optimal(rest, capacity, cache);
+        }
+        if cache.len() < capacity {
+            cache.push(key);
+            let hits = brute_force_optimal(rest, capacity, cache);
+            cache.pop();
+            return hits;
+        }
+        let mut best = 0;
+        for i in 0..cache.len() {
+            let victim = std::mem::replace(&mut cache[i], key);
+            best = best.max(brute_force_optimal(rest, capacity, cache));
+            cache[i] = victim;
+        }
+        best
+    }
+
+    #[test]
+    fn test_against_naive_policies() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for capacity in [1, 2, 5, 16] {
+            let trace: Vec<u64> = (0..2000).map(|_| rng.random_range(0..24)).collect();
+            assert_eq!(
+                replay(&mut Lru::new(capacity), &trace),
+                naive_lru(&trace, capacity)
+            );
+            assert_eq!(
+                replay(&mut Lfu::new(capacity), &trace),
+                naive_lfu(&trace, capacity)
+            );
+        }
+    }
+
+    #[test]
+    fn test_policy_behavior() {
+        // FIFO ignores hits, LRU does not
+        let trace = [1, 2, 1, 3, 1];
+        assert_eq!(replay(&mut Fifo::new(2), &trace).hits, 1);
+        assert_eq!(replay(&mut Lru::new(2), &trace).hits, 2);
+
+        // CLOCK gives referenced keys a second chance
+        let mut clock = Clock::new(3);
+        for key in [1, 2, 3, 1, 4] {
+            clock.access(key);
+        }
+        assert!(clock.contains(&1) && !clock.contains(&2));
+        assert!(clock.contains(&3) && clock.contains(&4));
+
+        // LFU keeps the frequent key through a scan
+        let mut lfu = Lfu::new(2);
+        for key in [1, 1, 1, 2, 3, 4, 5] {
+            lfu.access(key);
+        }
+        assert!(lfu.contains(&1) && lfu.contains(&5));
+    }
+
+    #[test]
+    fn test_invariants() {
+        let trace = zipf_trace(100, 5000, 0.8, 1);
+        for capacity in [1, 3, 10, 50, 200] {
+            let policies: Vec<Box<dyn CachePolicy<u64>>> = vec![
+                Box::new(Lru::new(capacity)),
+                Box::new(Lfu::new(capacity)),
+                Box::new(Fifo::new(capacity)),
+                Box::new(Clock::new(capacity)),
+                Box::new(ArcCache::new(capacity)),
+                Box::new(RandomEviction::new(capacity, 7)),
+            ];
+            for mut policy in policies {
+                for &key in &trace {
+                    policy.access(key);
+                    assert!(policy.contains(&key));
+                    assert!(policy.len() <= capacity);
+                }
+            }
+            let stats = compare_policies(&trace, capacity);
+            let optimal = stats.last().unwrap().1;
+            for (name, stats) in &stats {
+                assert_eq!(stats.hits + stats.misses, trace.len() as u64);
+                assert!(stats.hits <= optimal.hits, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_belady() {
+        let mut rng = StdRng::seed_from_u64(2);
+        for capacity in [1, 2, 3] {
+            for _ in 0..20 {
+                let trace: Vec<u64> = (0..12).map(|_| rng.random_range(0..5)).collect();
+                assert_eq!(
+                    belady(&trace, capacity).hits,
+                    brute_force_optimal(&trace, capacity, &mut Vec::new())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_arc_adapts() {
+        // a hot set used twice per round, then a scan of cold keys:
+        // ARC keeps the hot keys in T2 while LRU lets the scan flush them
+        let mut trace = Vec::new();
+        for round in 0..50u64 {
+            trace.extend(0..8);
+            trace.extend(0..8);
+            trace.extend(1000 + 20 * round..1000 + 20 * round + 20);
+        }
+        let arc = replay(&mut ArcCache::new(16), &trace);
+        let lru = replay(&mut Lru::new(16), &trace);
+        assert!(2 * arc.hits > 3 * lru.hits, "{arc:?} {lru:?}");
+    }
+
+    #[test]
+    fn test_read_key_trace() {
+        let keys = read_key_trace("a\n b \n\nc\na\n".as_bytes()).unwrap();
+        assert_eq!(keys, ["a", "b", "c", "a"]);
+        let stats = compare_policies(&keys, 3);
+        assert!(stats.iter().all(|(_, stats)| stats.hits == 1));
+
+        let report = EvictionReport::replay(&keys, &[1, 3]).to_string();
+        assert_eq!(report.lines().count(), 3);
+        assert!(report.contains("Belady"));
+    }
+}
@@ -1,3 +1,12 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::record::{Record, RecordReader, RecordWriter};
+
 pub fn merge<T: Ord + Clone>(left: &[T], right: &[T]) -> Vec<T> {
     let mut merged = Vec::with_capacity(left.len() + right.len());
     let mut iter_left = left.iter().peekable();
@@ -20,9 +29,597 @@ pub fn merge<T: Ord + Clone>(left: &[T], right: &[T]) -> Vec<T> {
     merged
 }
 
+/// merge two sorted slices into `output`, which must have exactly `left.len() + right.len()` elements
+pub fn merge_into<T: Ord + Clone>(left: &[T], right: &[T], output: &mut [T]) {
+    assert_eq!(left.len() + right.len(), output.len());
+    let (mut i, mut j) = (0, 0);
+    for slot in output.iter_mut() {
+        if j == right.len() || (i < left.len() && left[i] <= right[j]) {
+            *slot = left[i].clone();
+            i += 1;
+        } else {
+            *slot = right[j].clone();
+            j += 1;
+        }
+    }
+}
+
+/// merge two sorted slices of primitives into `output` without branching on the comparison
+///
+/// The comparison result is turned into a conditional move and index increments,
+/// so random inputs do not cause branch mispredictions. NaNs are not supported.
+pub fn branchless_merge<T: Copy + PartialOrd>(left: &[T], right: &[T], output: &mut [T]) {
+    assert_eq!(left.len() + right.len(), output.len());
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    while i < left.len() && j < right.len() {
+        let (a, b) = (left[i], right[j]);
+        let take_left = a <= b;
+        output[k] = if take_left { a } else { b };
+        i += take_left as usize;
+        j += !take_left as usize;
+        k += 1;
+    }
+    output[k..k + left.len() - i].copy_from_slice(&left[i..]);
+    output[k + left.len() - i..].copy_from_slice(&right[j..]);
+}
+
+macro_rules! impl_bitonic_merge {
+    ($name:ident, $avx2_name:ident, $t:ty) => {
+        #[doc = concat!("merge two sorted `", stringify!($t), "` slices with an AVX2 bitonic merging network")]
+        ///
+        /// Eight elements are emitted per network, and only the choice of the next input
+        /// block branches. Falls back to [`branchless_merge`] when AVX2 is not available.
+        pub fn $name(left: &[$t], right: &[$t], output: &mut [$t]) {
+            assert_eq!(left.len() + right.len(), output.len());
+            #[cfg(target_arch = "x86_64")]
+            if std::arch::is_x86_feature_detected!("avx2") {
+                // SAFETY: AVX2 is supported
+                unsafe { bitonic::$avx2_name::merge(left, right, output) };
+                return;
+            }
+            branchless_merge(left, right, output);
+        }
+    };
+}
+
+impl_bitonic_merge!(bitonic_merge_i32, merge_i32, i32);
+impl_bitonic_merge!(bitonic_merge_f32, merge_f32, f32);
+
+#[cfg(target_arch = "x86_64")]
+mod bitonic {
+    use super::branchless_merge;
+    use std::arch::x86_64::*;
+
+    macro_rules! bitonic_merge_avx2 {
+        ($name:ident, $t:ty, $vec:ty, $load:ident, $store:ident, $min:ident, $max:ident, $permute:ident,
+         $reverse:expr, $shuffle:ident, $blend:ident) => {
+            pub mod $name {
+                use super::*;
+
+                /// sort a bitonic vector: compare-exchange at distance 4, 2 and 1
+                #[inline]
+                #[target_feature(enable = "avx2")]
+                fn sort_bitonic(v: $vec) -> $vec {
+                    let t = $permute(v, _mm256_setr_epi32(4, 5, 6, 7, 0, 1, 2, 3));
+                    let v = $blend::<0b1111_0000>($min(v, t), $max(v, t));
+                    let t = $shuffle::<0b01_00_11_10>(v);
+                    let v = $blend::<0b1100_1100>($min(v, t), $max(v, t));
+                    let t = $shuffle::<0b10_11_00_01>(v);
+                    $blend::<0b1010_1010>($min(v, t), $max(v, t))
+                }
+
+                /// merge two sorted vectors, returns the lower and the upper 8 elements
+                #[inline]
+                #[target_feature(enable = "avx2")]
+                fn merge_16(a: $vec, b: $vec) -> ($vec, $vec) {
+                    let b = $permute(b, $reverse);
+                    let (lo, hi) = ($min(a, b), $max(a, b));
+                    (sort_bitonic(lo), sort_bitonic(hi))
+                }
+
+                #[target_feature(enable = "avx2")]
+                pub fn merge(left: &[$t], right: &[$t], output: &mut [$t]) {
+                    let (mut i, mut j, mut k) = (0, 0, 0);
+                    if left.len() < 8 || right.len() < 8 {
+                        branchless_merge(left, right, output);
+                        return;
+                    }
+                    // SAFETY: every load and store below covers 8 in-bounds elements
+                    let load = |s: &[$t], at: usize| unsafe { $load(s[at..at + 8].as_ptr() as _) };
+                    let mut carry = load(left, 0);
+                    i += 8;
+                    // the input whose next element is smaller supplies the next block
+                    loop {
+                        let from_left = if i + 8 <= left.len() && j + 8 <= right.len() {
+                            left[i] <= right[j]
+                        } else if i == left.len() && j + 8 <= right.len() {
+                            false
+                        } else if j == right.len() && i + 8 <= left.len() {
+                            true
+                        } else {
+                            break;
+                        };
+                        let block = if from_left {
+                            i += 8;
+                            load(left, i - 8)
+                        } else {
+                            j += 8;
+                            load(right, j - 8)
+                        };
+                        let (lo, hi) = merge_16(carry, block);
+                        unsafe { $store(output[k..k + 8].as_mut_ptr() as _, lo) };
+                        k += 8;
+                        carry = hi;
+                    }
+
+                    // the carry and the two tails, at least one of which is shorter than 8
+                    let mut carry_buf = [<$t>::default(); 8];
+                    unsafe { $store(carry_buf.as_mut_ptr() as _, carry) };
+                    let (short, long) = if left.len() - i < 8 {
+                        (&left[i..], &right[j..])
+                    } else {
+                        (&right[j..], &left[i..])
+                    };
+                    let mut small = [<$t>::default(); 16];
+                    let small = &mut small[..8 + short.len()];
+                    branchless_merge(&carry_buf, short, small);
+                    branchless_merge(small, long, &mut output[k..]);
+                }
+            }
+        };
+    }
+
+    bitonic_merge_avx2!(
+        merge_i32,
+        i32,
+        __m256i,
+        _mm256_loadu_si256,
+        _mm256_storeu_si256,
+        _mm256_min_epi32,
+        _mm256_max_epi32,
+        _mm256_permutevar8x32_epi32,
+        _mm256_setr_epi32(7, 6, 5, 4, 3, 2, 1, 0),
+        shuffle_i32,
+        _mm256_blend_epi32
+    );
+    bitonic_merge_avx2!(
+        merge_f32,
+        f32,
+        __m256,
+        _mm256_loadu_ps,
+        _mm256_storeu_ps,
+        _mm256_min_ps,
+        _mm256_max_ps,
+        _mm256_permutevar8x32_ps,
+        _mm256_setr_epi32(7, 6, 5, 4, 3, 2, 1, 0),
+        shuffle_f32,
+        _mm256_blend_ps
+    );
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    fn shuffle_i32<const IMM: i32>(v: __m256i) -> __m256i {
+        _mm256_shuffle_epi32::<IMM>(v)
+    }
+
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    fn shuffle_f32<const IMM: i32>(v: __m256) -> __m256 {
+        _mm256_shuffle_ps::<IMM>(v, v)
+    }
+}
+
+/// k-way merge with a binary heap, the baseline for [`KWayMerger`]
+pub fn heap_merge<T: Ord + Clone>(runs: &[&[T]]) -> Vec<T> {
+    let mut merged = Vec::with_capacity(runs.iter().map(|run| run.len()).sum());
+    let mut heap = BinaryHeap::with_capacity(runs.len());
+    for (i, run) in runs.iter().enumerate() {
+        if let Some(first) = run.first() {
+            heap.push(Reverse((first, i, 0)));
+        }
+    }
+    while let Some(Reverse((value, i, j))) = heap.pop() {
+        merged.push(value.clone());
+        if let Some(next) = runs[i].get(j + 1) {
+            heap.push(Reverse((next, i, j + 1)));
+        }
+    }
+    merged
+}
+
+/// k-way merger based on a loser (tournament) tree.
+///
+/// Every internal node keeps the loser of the match played there and `tree[0]` keeps
+/// the overall winner, so replacing the winner only replays the matches on its path:
+/// ceil(log2(k)) comparisons per output element. Exhausted sources lose every match,
+/// and ties go to the source with the smaller index, so the merge is stable.
+pub struct KWayMerger<I: Iterator> {
+    sources: Vec<I>,
+    heads: Vec<Option<I::Item>>,
+    tree: Vec<usize>,
+}
+
+impl<I: Iterator> KWayMerger<I>
+where
+    I::Item: Ord,
+{
+    /// every source must be sorted
+    pub fn new(sources: impl IntoIterator<Item = I>) -> Self {
+        let mut sources: Vec<I> = sources.into_iter().collect();
+        let heads: Vec<_> = sources.iter_mut().map(|source| source.next()).collect();
+        let k = sources.len();
+        let mut merger = KWayMerger {
+            sources,
+            heads,
+            tree: vec![0; k.max(1)],
+        };
+        merger.build();
+        merger
+    }
+
+    /// whether source `a` wins against source `b`
+    #[inline]
+    fn beats(&self, a: usize, b: usize) -> bool {
+        match (&self.heads[a], &self.heads[b]) {
+            (Some(x), Some(y)) => x.cmp(y).then(a.cmp(&b)).is_lt(),
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+            (None, None) => a < b,
+        }
+    }
+
+    fn build(&mut self) {
+        let k = self.sources.len();
+        if k == 0 {
+            return;
+        }
+        // winners of the internal nodes 1..k, leaf k + i is source i
+        let mut winners = vec![0; k];
+        let winner_of =
+            |node: usize, winners: &[usize]| if node >= k { node - k } else { winners[node] };
+        for node in (1..k).rev() {
+            let left = winner_of(2 * node, &winners);
+            let right = winner_of(2 * node + 1, &winners);
+            let (winner, loser) = if self.beats(left, right) {
+                (left, right)
+            } else {
+                (right, left)
+            };
+            winners[node] = winner;
+            self.tree[node] = loser;
+        }
+        self.tree[0] = winner_of(1, &winners);
+    }
+
+    /// replay the matches from the leaf of `source` to the root
+    #[inline]
+    fn replay(&mut self, source: usize) {
+        let k = self.sources.len();
+        let mut winner = source;
+        let mut node = (source + k) / 2;
+        while node > 0 {
+            if self.beats(self.tree[node], winner) {
+                std::mem::swap(&mut self.tree[node], &mut winner);
+            }
+            node /= 2;
+        }
+        self.tree[0] = winner;
+    }
+
+    /// write the merged sequence into `output`, returns the number of elements written
+    ///
+    /// Stops when either the sources or `output` run out.
+    pub fn merge_into(&mut self, output: &mut [I::Item]) -> usize {
+        let mut written = 0;
+        for slot in output.iter_mut() {
+            match self.next() {
+                Some(value) => *slot = value,
+                None => break,
+            }
+            written += 1;
+        }
+        written
+    }
+}
+
+impl<'a, T: Ord + Clone> KWayMerger<std::iter::Cloned<std::slice::Iter<'a, T>>> {
+    /// every slice must be sorted
+    pub fn from_slices(slices: &[&'a [T]]) -> Self {
+        KWayMerger::new(slices.iter().map(|slice| slice.iter().cloned()))
+    }
+}
+
+impl<I: Iterator> Iterator for KWayMerger<I>
+where
+    I::Item: Ord,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        if self.sources.is_empty() {
+            return None;
+        }
+        let winner = self.tree[0];
+        let next = self.sources[winner].next();
+        let value = std::mem::replace(&mut self.heads[winner], next)?;
+        self.replay(winner);
+        Some(value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let mut lower = 0usize;
+        let mut upper = Some(0usize);
+        for (source, head) in self.sources.iter().zip(&self.heads) {
+            let (lo, hi) = source.size_hint();
+            let extra = head.is_some() as usize;
+            lower = lower.saturating_add(lo + extra);
+            upper = upper.zip(hi).and_then(|(a, b)| a.checked_add(b + extra));
+        }
+        (lower, upper)
+    }
+}
+
+/// I/O statistics of one external sort
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortStats {
+    /// number of sorted runs produced by the first pass
+    pub runs: usize,
+    /// number of passes over the data, including run formation
+    pub passes: usize,
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+}
+
+/// Sorts files of [`Record`]s that do not fit in memory.
+///
+/// The input is read in runs that fit in `memory_budget` bytes next to the I/O buffers,
+/// each run is sorted in place and spilled to a temporary directory, and the runs are
+/// k-way merged until one is left. Records are compared by their [`Record::key_prefix`]
+/// first.
+#[derive(Debug, Clone)]
+pub struct ExternalSorter {
+    memory_budget: usize,
+    fan_in: usize,
+    temp_dir: PathBuf,
+}
+
+/// Where [`ExternalSorter`] reads its input, spills its runs and writes its output.
+pub trait SortStorage {
+    type Reader<'a>: Read
+    where
+        Self: 'a;
+    type Writer<'a>: Write
+    where
+        Self: 'a;
+
+    /// size of the file in bytes
+    fn file_len(&self, path: &Path) -> io::Result<u64>;
+
+    fn open(&self, path: &Path) -> io::Result<Self::Reader<'_>>;
+
+    /// create or truncate a file
+    fn create(&self, path: &Path) -> io::Result<Self::Writer<'_>>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+
+    /// remove a directory and everything in it
+    fn remove_dir(&self, path: &Path) -> io::Result<()>;
+}
+
+/// The file system of the operating system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileSystem;
+
+impl SortStorage for FileSystem {
+    type Reader<'a> = File;
+    type Writer<'a> = File;
+
+    fn file_len(&self, path: &Path) -> io::Result<u64> {
+        Ok(fs::metadata(path)?.len())
+    }
+
+    fn open(&self, path: &Path) -> io::Result<File> {
+        File::open(path)
+    }
+
+    fn create(&self, path: &Path) -> io::Result<File> {
+        File::create(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn remove_dir(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+}
+
+/// Removes the temporary directory when dropped, also on errors.
+pub(crate) struct TempDirGuard<'a, S: SortStorage> {
+    storage: &'a S,
+    pub(crate) path: PathBuf,
+}
+
+impl<'a, S: SortStorage> TempDirGuard<'a, S> {
+    pub(crate) fn new(storage: &'a S, parent: &Path) -> io::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path = parent.join(format!("hpc-rs-sort-{}-{}", std::process::id(), id));
+        storage.create_dir(&path)?;
+        Ok(TempDirGuard { storage, path })
+    }
+}
+
+impl<S: SortStorage> Drop for TempDirGuard<'_, S> {
+    fn drop(&mut self) {
+        let _ = self.storage.remove_dir(&self.path);
+    }
+}
+
+impl ExternalSorter {
+    /// `memory_budget` is the number of bytes of records and I/O buffers kept in memory
+    /// at once
+    pub fn new(memory_budget: usize) -> Self {
+        ExternalSorter {
+            memory_budget,
+            fan_in: 16,
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+
+    /// the maximum number of runs merged at once, at least 2
+    pub fn fan_in(mut self, fan_in: usize) -> Self {
+        assert!(fan_in >= 2);
+        self.fan_in = fan_in;
+        self
+    }
+
+    /// where the runs are spilled, defaults to `std::env::temp_dir()`
+    pub fn temp_dir(mut self, temp_dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = temp_dir.into();
+        self
+    }
+
+    /// sort the records of `input` into `output`
+    pub fn sort_file<T: Record + Ord>(&self, input: &Path, output: &Path) -> io::Result<SortStats> {
+        self.sort_on::<T, _>(&FileSystem, input, output)
+    }
+
+    /// sort the records of `input` into `output`, both in `storage`
+    ///
+    /// The runs are spilled into `temp_dir` of the same storage.
+    pub fn sort_on<T: Record + Ord, S: SortStorage>(
+        &self,
+        storage: &S,
+        input: &Path,
+        output: &Path,
+    ) -> io::Result<SortStats> {
+        let input_len = storage.file_len(input)?;
+        if input_len % T::SIZE as u64 != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "file size is not a multiple of the record size",
+            ));
+        }
+        let guard = TempDirGuard::new(storage, &self.temp_dir)?;
+        let mut stats = SortStats::default();
+
+        let mut runs = self.form_runs::<T, S>(storage, input, &guard.path, &mut stats)?;
+        stats.runs = runs.len();
+        stats.passes = 1;
+        let mut next_id = runs.len();
+        while runs.len() > self.fan_in {
+            let mut merged = Vec::with_capacity(runs.len().div_ceil(self.fan_in));
+            for group in runs.chunks(self.fan_in) {
+                let path = guard.path.join(format!("run-{next_id}"));
+                next_id += 1;
+                self.merge_runs::<T, S>(storage, group, &path, &mut stats)?;
+                for run in group {
+                    storage.remove_file(run)?;
+                }
+                merged.push(path);
+            }
+            runs = merged;
+            stats.passes += 1;
+        }
+        self.merge_runs::<T, S>(storage, &runs, output, &mut stats)?;
+        if !runs.is_empty() {
+            stats.passes += 1;
+        }
+        Ok(stats)
+    }
+
+    /// a whole number of records, so that `fan_in + 1` buffers fit in the budget
+    fn io_buffer_size<T: Record>(&self) -> usize {
+        (self.memory_budget / (self.fan_in + 1) / T::SIZE).max(1) * T::SIZE
+    }
+
+    /// pass 1: cut the input into sorted runs
+    fn form_runs<T: Record + Ord, S: SortStorage>(
+        &self,
+        storage: &S,
+        input: &Path,
+        temp_dir: &Path,
+        stats: &mut SortStats,
+    ) -> io::Result<Vec<PathBuf>> {
+        // the run shares the budget with the reader and the writer
+        let buffer_size = self.io_buffer_size::<T>();
+        let run_len =
+            (self.memory_budget.saturating_sub(2 * buffer_size) / size_of::<T>().max(1)).max(1);
+        let mut reader = RecordReader::<_, T>::with_block_size(storage.open(input)?, buffer_size);
+        let mut runs = Vec::new();
+        let mut records = Vec::with_capacity(run_len);
+        loop {
+            records.clear();
+            if reader.read_records(&mut records, run_len)? == 0 {
+                break;
+            }
+            stats.bytes_read += (records.len() * T::SIZE) as u64;
+            // compare the prefixes first, the full records only on equal prefixes
+            if T::PREFIX_BYTES == T::SIZE {
+                records.sort_unstable_by_key(T::key_prefix);
+            } else {
+                records.sort_unstable_by(|a, b| {
+                    a.key_prefix().cmp(&b.key_prefix()).then_with(|| a.cmp(b))
+                });
+            }
+
+            let path = temp_dir.join(format!("run-{}", runs.len()));
+            let mut writer =
+                RecordWriter::<_, T>::with_block_size(storage.create(&path)?, buffer_size);
+            writer.write_records(&records)?;
+            writer.flush()?;
+            stats.bytes_written += writer.bytes_written();
+            runs.push(path);
+        }
+        Ok(runs)
+    }
+
+    /// k-way merge the runs into `output` with a binary heap keyed by the record prefixes
+    fn merge_runs<T: Record + Ord, S: SortStorage>(
+        &self,
+        storage: &S,
+        runs: &[PathBuf],
+        output: &Path,
+        stats: &mut SortStats,
+    ) -> io::Result<()> {
+        let buffer_size = self.io_buffer_size::<T>();
+        let mut readers = Vec::with_capacity(runs.len());
+        let mut heap = BinaryHeap::with_capacity(runs.len());
+        for (i, run) in runs.iter().enumerate() {
+            let mut reader = RecordReader::<_, T>::with_block_size(storage.open(run)?, buffer_size);
+            if let Some(record) = reader.read_record()? {
+                heap.push(Reverse((record.key_prefix(), record, i)));
+            }
+            readers.push(reader);
+        }
+
+        let mut writer =
+            RecordWriter::<_, T>::with_block_size(storage.create(output)?, buffer_size);
+        while let Some(Reverse((_, record, i))) = heap.pop() {
+            writer.write_record(&record)?;
+            stats.bytes_read += T::SIZE as u64;
+            stats.bytes_written += T::SIZE as u64;
+            if let Some(next) = readers[i].read_record()? {
+                heap.push(Reverse((next.key_prefix(), next, i)));
+            }
+        }
+        writer.flush()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::alloc::{GlobalAlloc, Layout, System};
+    use std::cell::Cell;
 
     #[test]
     fn test_merge() {
@@ -41,4 +638,315 @@ mod tests {
         let result = merge(&left, &right);
         assert_eq!(result, vec![1, 2]);
     }
+
+    #[test]
+    fn test_merge_variants() {
+        use rand::distr::{Distribution, Uniform};
+        let mut rng = rand::rng();
+        for (n, m, range) in [
+            (0, 0, 10),
+            (0, 5, 10),
+            (7, 9, 10),
+            (8, 8, 10),
+            (8, 100, 1000),
+            (64, 64, 5),
+            (123, 77, 1000),
+            (1000, 3, 100_000),
+            (1024, 1000, 100_000),
+        ] {
+            let uniform = Uniform::new(-range, range).unwrap();
+            let mut left: Vec<i32> = uniform.sample_iter(&mut rng).take(n).collect();
+            let mut right: Vec<i32> = uniform.sample_iter(&mut rng).take(m).collect();
+            left.sort_unstable();
+            right.sort_unstable();
+            let expected = merge(&left, &right);
+
+            let mut output = vec![0; n + m];
+            merge_into(&left, &right, &mut output);
+            assert_eq!(output, expected);
+            let mut output = vec![0; n + m];
+            branchless_merge(&left, &right, &mut output);
+            assert_eq!(output, expected);
+            let mut output = vec![0; n + m];
+            bitonic_merge_i32(&left, &right, &mut output);
+            assert_eq!(output, expected);
+            let mut output = vec![0; n + m];
+            bitonic_merge_i32(&right, &left, &mut output);
+            assert_eq!(output, expected);
+
+            let left: Vec<f32> = left.iter().map(|&x| x as f32 * 0.5).collect();
+            let right: Vec<f32> = right.iter().map(|&x| x as f32 * 0.5).collect();
+            let expected: Vec<f32> = expected.iter().map(|&x| x as f32 * 0.5).collect();
+            let mut output = vec![0.; n + m];
+            bitonic_merge_f32(&left, &right, &mut output);
+            assert_eq!(output, expected);
+        }
+
+        // disjoint inputs
+        let left: Vec<i32> = (0..100).collect();
+        let right: Vec<i32> = (100..300).collect();
+        let mut output = vec![0; 300];
+        bitonic_merge_i32(&right, &left, &mut output);
+        assert_eq!(output, (0..300).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_k_way_merger() {
+        use rand::distr::{Distribution, Uniform};
+        let mut rng = rand::rng();
+        let uniform = Uniform::new(0, 1000).unwrap();
+        for k in [0, 1, 2, 3, 5, 8, 17, 100] {
+            let runs: Vec<Vec<i32>> = (0..k)
+                .map(|i| {
+                    let mut run: Vec<i32> =
+                        uniform.sample_iter(&mut rng).take(i * 7 % 50).collect();
+                    run.sort_unstable();
+                    run
+                })
+                .collect();
+            let slices: Vec<&[i32]> = runs.iter().map(|run| run.as_slice()).collect();
+            let mut expected: Vec<i32> = runs.concat();
+            expected.sort_unstable();
+
+            let merger = KWayMerger::from_slices(&slices);
+            assert_eq!(merger.size_hint(), (expected.len(), Some(expected.len())));
+            assert_eq!(merger.collect::<Vec<_>>(), expected);
+            assert_eq!(heap_merge(&slices), expected);
+
+            let mut output = vec![0; expected.len() + 3];
+            let mut merger = KWayMerger::new(runs.iter().map(|run| run.iter().copied()));
+            assert_eq!(merger.merge_into(&mut output), expected.len());
+            assert_eq!(&output[..expected.len()], &expected[..]);
+        }
+
+        // a short output buffer can be filled again
+        let mut merger =
+            KWayMerger::new([vec![1, 4, 7], vec![2, 5], vec![3, 6]].map(|run| run.into_iter()));
+        let mut output = [0; 4];
+        assert_eq!(merger.merge_into(&mut output), 4);
+        assert_eq!(output, [1, 2, 3, 4]);
+        assert_eq!(merger.merge_into(&mut output), 3);
+        assert_eq!(&output[..3], &[5, 6, 7]);
+    }
+
+    #[test]
+    fn test_k_way_merger_is_stable() {
+        /// ordered by key only
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        struct Item(i32, usize);
+        impl PartialOrd for Item {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Item {
+            fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+                self.0.cmp(&other.0)
+            }
+        }
+        let runs: Vec<Vec<Item>> = (0..5).map(|i| vec![Item(1, i), Item(2, i)]).collect();
+        let merged: Vec<usize> = KWayMerger::new(runs.into_iter().map(|run| run.into_iter()))
+            .map(|item| item.1)
+            .collect();
+        assert_eq!(merged, vec![0, 1, 2, 3, 4, 0, 1, 2, 3, 4]);
+    }
+
+    /// Counts the bytes allocated by each thread, so a test can check the memory budget
+    /// without the other tests running next to it.
+    struct CountingAllocator;
+
+    thread_local! {
+        /// (live, peak) bytes allocated by this thread
+        static ALLOCATED: Cell<(isize, isize)> = const { Cell::new((0, 0)) };
+    }
+
+    fn count_allocation(bytes: isize) {
+        let _ = ALLOCATED.try_with(|allocated| {
+            let (live, peak) = allocated.get();
+            allocated.set((live + bytes, peak.max(live + bytes)));
+        });
+    }
+
+    /// the number of bytes this thread has allocated at its peak since the last call
+    fn peak_allocated() -> isize {
+        ALLOCATED.with(|allocated| {
+            let (live, peak) = allocated.get();
+            allocated.set((live, live));
+            peak - live
+        })
+    }
+
+    unsafe impl GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+            count_allocation(layout.size() as isize);
+            unsafe { System.alloc(layout) }
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+            count_allocation(-(layout.size() as isize));
+            unsafe { System.dealloc(ptr, layout) }
+        }
+
+        unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+            count_allocation(new_size as isize - layout.size() as isize);
+            unsafe { System.realloc(ptr, layout, new_size) }
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// write `values` as little-endian records into a new file
+    fn write_records<T: Record>(path: &Path, values: &[T]) {
+        let mut writer = RecordWriter::new(File::create(path).unwrap());
+        writer.write_records(values).unwrap();
+        writer.flush().unwrap();
+    }
+
+    fn read_records<T: Record>(path: &Path) -> Vec<T> {
+        let mut records = Vec::new();
+        let mut reader = RecordReader::new(File::open(path).unwrap());
+        reader.read_records(&mut records, usize::MAX).unwrap();
+        records
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hpc-rs-test-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_external_sort() {
+        use rand::distr::{Distribution, Uniform};
+        let dir = test_dir("external-sort");
+        let input = dir.join("input");
+        let output = dir.join("output");
+        let spill = dir.join("spill");
+        fs::create_dir_all(&spill).unwrap();
+
+        let mut rng = rand::rng();
+        let uniform = Uniform::new(0, u64::MAX).unwrap();
+        let values: Vec<u64> = uniform.sample_iter(&mut rng).take(10_000).collect();
+        write_records(&input, &values);
+        let mut expected = values.clone();
+        expected.sort_unstable();
+
+        // the reader and the writer take 160 bytes each, which leaves 60 records per
+        // run, 167 runs, merged 4 at a time: 1 + 4 passes
+        let sorter = ExternalSorter::new(800).fan_in(4).temp_dir(&spill);
+        let stats = sorter.sort_file::<u64>(&input, &output).unwrap();
+        assert_eq!(read_records::<u64>(&output), expected);
+        assert_eq!(stats.runs, 167);
+        assert_eq!(stats.passes, 5);
+        assert_eq!(stats.bytes_read, 5 * 80_000);
+        assert_eq!(stats.bytes_written, 5 * 80_000);
+        assert_eq!(fs::read_dir(&spill).unwrap().count(), 0);
+
+        // everything fits in memory: one run, then one merge into the output
+        let stats = ExternalSorter::new(1 << 20)
+            .temp_dir(&spill)
+            .sort_file::<u64>(&input, &output)
+            .unwrap();
+        assert_eq!(read_records::<u64>(&output), expected);
+        assert_eq!((stats.runs, stats.passes), (1, 2));
+
+        write_records::<u64>(&input, &[]);
+        let stats = sorter.sort_file::<u64>(&input, &output).unwrap();
+        assert!(read_records::<u64>(&output).is_empty());
+        assert_eq!((stats.runs, stats.passes), (0, 1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_external_sort_wide_records() {
+        use rand::Rng;
+        let dir = test_dir("external-sort-wide");
+        let input = dir.join("input");
+        let output = dir.join("output");
+        let mut rng = rand::rng();
+
+        // many records share a key prefix, so the full records break the ties
+        let values: Vec<(u32, i64, u8)> = (0..5000)
+            .map(|_| {
+                (
+                    rng.random_range(0..4),
+                    rng.random_range(-3..3),
+                    rng.random(),
+                )
+            })
+            .collect();
+        write_records(&input, &values);
+        let mut expected = values.clone();
+        expected.sort_unstable();
+        let sorter = ExternalSorter::new(1300).fan_in(3).temp_dir(&dir);
+        sorter.sort_file::<(u32, i64, u8)>(&input, &output).unwrap();
+        assert_eq!(read_records::<(u32, i64, u8)>(&output), expected);
+
+        let values: Vec<u128> = (0..5000)
+            .map(|_| (rng.random_range(0..3u128) << 100) | rng.random_range(0..1000))
+            .collect();
+        write_records(&input, &values);
+        let mut expected = values.clone();
+        expected.sort_unstable();
+        sorter.sort_file::<u128>(&input, &output).unwrap();
+        assert_eq!(read_records::<u128>(&output), expected);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_external_sort_memory_budget() {
+        use rand::Rng;
+        let dir = test_dir("external-sort-budget");
+        let input = dir.join("input");
+        let output = dir.join("output");
+        let mut rng = rand::rng();
+
+        let values: Vec<u64> = (0..1 << 17).map(|_| rng.random()).collect();
+        let wide: Vec<(u32, i64, u8)> = (0..1 << 15).map(|_| rng.random()).collect();
+        let wide_input = dir.join("wide");
+        write_records(&input, &values);
+        write_records(&wide_input, &wide);
+        for budget in [64 << 10, 300_000] {
+            // the paths of the runs are not part of the budget
+            let limit = (budget + budget / 8) as isize;
+            let sorter = ExternalSorter::new(budget).fan_in(4).temp_dir(&dir);
+            peak_allocated();
+            sorter.sort_file::<u64>(&input, &output).unwrap();
+            let peak = peak_allocated();
+            assert!(peak <= limit, "{peak} > {budget}");
+            sorter
+                .sort_file::<(u32, i64, u8)>(&wide_input, &output)
+                .unwrap();
+            let peak = peak_allocated();
+            assert!(peak <= limit, "{peak} > {budget}");
+        }
+        assert!(read_records::<(u32, i64, u8)>(&output).is_sorted());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_external_sort_error_cleans_up() {
+        let dir = test_dir("external-sort-error");
+        let input = dir.join("input");
+        let spill = dir.join("spill");
+        fs::create_dir_all(&spill).unwrap();
+        write_records(&input, &[3u64, 1, 2]);
+        let sorter = ExternalSorter::new(8).temp_dir(&spill);
+
+        // the output directory does not exist, so the final merge fails after spilling runs
+        let result = sorter.sort_file::<u64>(&input, &dir.join("missing").join("output"));
+        assert!(result.is_err());
+        assert_eq!(fs::read_dir(&spill).unwrap().count(), 0);
+
+        // 3 bytes is not a whole number of u32 records
+        fs::write(&input, [1, 2, 3]).unwrap();
+        assert!(
+            sorter
+                .sort_file::<u32>(&input, &dir.join("output"))
+                .is_err()
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,285 @@
+//! Lazy funnelsort, a cache-oblivious merge sort.
+//!
+//! The input is cut into about n^(1/3) segments that are sorted recursively and then
+//! merged by a k-funnel: a perfect binary tree of two-way mergers with a buffer on
+//! every edge. A k-funnel is laid out like the [`super::veb_tree`]: its top sqrt(k)-funnel
+//! comes first, then for each of its leaves the buffer of k^(3/2) elements below it,
+//! followed by the bottom sqrt(k)-funnel that fills that buffer. A merger only runs when
+//! its parent finds its buffer empty, and then fills the buffer completely (Brodal and
+//! Fagerberg), so the sort does O(n/B log_{M/B} n/B) block transfers under the tall
+//! cache assumption without knowing M or B.
+
+/// inputs of at most this many elements are sorted with `sort_unstable`
+const LEAF: usize = 1024;
+
+/// buffers are at least this large, so that the small funnels near the runs do not
+/// spend their time refilling a few elements at a time
+const MIN_BUFFER: usize = 256;
+
+/// the output buffer of one merger, `len - head` elements starting at `offset + head`
+#[derive(Debug, Clone, Copy, Default)]
+struct Node {
+    offset: usize,
+    capacity: usize,
+    head: usize,
+    len: usize,
+    /// the subtree has nothing left besides what is in the buffer
+    exhausted: bool,
+}
+
+/// A k-funnel over sorted runs. Node v has the children 2v and 2v + 1 like a binary
+/// heap, the nodes from `leaves` on are the runs themselves.
+struct Funnel<'a, T> {
+    runs: Vec<&'a [T]>,
+    output: &'a mut [T],
+    buffers: Vec<T>,
+    nodes: Vec<Node>,
+    leaves: usize,
+}
+
+/// lay out the buffers of the funnel of `height` levels rooted at `root`, except its
+/// own output buffer
+fn layout(root: usize, height: usize, nodes: &mut [Node], offset: &mut usize) {
+    if height <= 1 {
+        return;
+    }
+    let top = height / 2;
+    let capacity = (((1u64 << height) as f64).powf(1.5).ceil() as usize).max(MIN_BUFFER);
+    layout(root, top, nodes, offset);
+    for i in 0..1 << top {
+        let child = root << top | i;
+        nodes[child] = Node {
+            offset: *offset,
+            capacity,
+            ..Node::default()
+        };
+        *offset += capacity;
+        layout(child, height - top, nodes, offset);
+    }
+}
+
+/// merge a and b into out until one of them runs out, or copy the other one if it is
+/// already empty, and return the number of elements written and taken from a and b
+fn merge_some<T: Ord + Copy>(out: &mut [T], a: &[T], b: &[T]) -> (usize, usize, usize) {
+    if a.is_empty() || b.is_empty() {
+        let (source, a_taken) = if a.is_empty() { (b, 0) } else { (a, 1) };
+        let n = source.len().min(out.len());
+        out[..n].copy_from_slice(&source[..n]);
+        return (n, n * a_taken, n * (1 - a_taken));
+    }
+    let (mut i, mut j, mut written) = (0, 0, 0);
+    while written < out.len() && i < a.len() && j < b.len() {
+        if b[j] < a[i] {
+            out[written] = b[j];
+            j += 1;
+        } else {
+            out[written] = a[i];
+            i += 1;
+        }
+        written += 1;
+    }
+    (written, i, j)
+}
+
+impl<'a, T: Ord + Copy> Funnel<'a, T> {
+    fn new(runs: &[&'a [T]], output: &'a mut [T]) -> Self {
+        let leaves = runs.len().next_power_of_two().max(2);
+        let height = leaves.trailing_zeros() as usize;
+        let mut nodes = vec![Node::default(); leaves];
+        let mut size = 0;
+        layout(1, height, &mut nodes, &mut size);
+        // the root writes to the output, which comes after all the buffers
+        nodes[1] = Node {
+            offset: size,
+            capacity: output.len(),
+            ..Node::default()
+        };
+        let mut padded = runs.to_vec();
+        padded.resize(leaves, &[]);
+        let filler = runs.iter().find_map(|run| run.first()).copied();
+        Funnel {
+            runs: padded,
+            output,
+            buffers: filler.map_or(Vec::new(), |x| vec![x; size]),
+            nodes,
+            leaves,
+        }
+    }
+
+    fn is_empty(&self, v: usize) -> bool {
+        if v >= self.leaves {
+            self.runs[v - self.leaves].is_empty()
+        } else {
+            self.nodes[v].head == self.nodes[v].len
+        }
+    }
+
+    fn consume(&mut self, v: usize, n: usize) {
+        if v >= self.leaves {
+            let run = &mut self.runs[v - self.leaves];
+            *run = &run[n..];
+        } else {
+            self.nodes[v].head += n;
+        }
+    }
+
+    /// the elements left in child c of a node whose buffer is split off between
+    /// `before` and `after`
+    fn source<'b>(&'b self, node: &Node, c: usize, before: &'b [T], after: &'b [T]) -> &'b [T] {
+        if c >= self.leaves {
+            return self.runs[c - self.leaves];
+        }
+        let child = self.nodes[c];
+        let (start, end) = (child.offset + child.head, child.offset + child.len);
+        if child.offset < node.offset {
+            &before[start..end]
+        } else {
+            let skip = node.offset + node.capacity;
+            &after[start - skip..end - skip]
+        }
+    }
+
+    /// one call of `merge_some` from the children of v into its buffer
+    fn merge_step(&mut self, v: usize) {
+        let node = self.nodes[v];
+        let mut buffers = std::mem::take(&mut self.buffers);
+        let output = std::mem::take(&mut self.output);
+        let (out, before, after): (&mut [T], &[T], &[T]) = if v == 1 {
+            (&mut output[node.len..], &buffers, &[])
+        } else {
+            let (before, rest) = buffers.split_at_mut(node.offset);
+            let (out, after) = rest.split_at_mut(node.capacity);
+            (&mut out[node.len..], before, after)
+        };
+        let left = self.source(&node, 2 * v, before, after);
+        let right = self.source(&node, 2 * v + 1, before, after);
+        let (written, left_taken, right_taken) = merge_some(out, left, right);
+        self.buffers = buffers;
+        self.output = output;
+        self.nodes[v].len += written;
+        self.consume(2 * v, left_taken);
+        self.consume(2 * v + 1, right_taken);
+    }
+
+    /// refill the empty buffer of v, filling the buffers of its children as they run dry
+    fn fill(&mut self, v: usize) {
+        self.nodes[v].head = 0;
+        self.nodes[v].len = 0;
+        while self.nodes[v].len < self.nodes[v].capacity {
+            for c in [2 * v, 2 * v + 1] {
+                if c < self.leaves && self.is_empty(c) && !self.nodes[c].exhausted {
+                    self.fill(c);
+                }
+            }
+            if self.is_empty(2 * v) && self.is_empty(2 * v + 1) {
+                self.nodes[v].exhausted = true;
+                break;
+            }
+            self.merge_step(v);
+        }
+    }
+}
+
+/// merge sorted runs into `output`, whose length must be the total length of the runs
+pub fn funnel_merge<T: Ord + Copy>(runs: &[&[T]], output: &mut [T]) {
+    assert_eq!(
+        runs.iter().map(|run| run.len()).sum::<usize>(),
+        output.len()
+    );
+    Funnel::new(runs, output).fill(1);
+}
+
+fn funnelsort_rec<T: Ord + Copy>(data: &mut [T], scratch: &mut [T]) {
+    let n = data.len();
+    if n <= LEAF {
+        data.sort_unstable();
+        return;
+    }
+    let segment = n.div_ceil((n as f64).cbrt().ceil() as usize);
+    for (chunk, chunk_scratch) in data.chunks_mut(segment).zip(scratch.chunks_mut(segment)) {
+        funnelsort_rec(chunk, chunk_scratch);
+    }
+    let runs: Vec<&[T]> = data.chunks(segment).collect();
+    funnel_merge(&runs, scratch);
+    data.copy_from_slice(scratch);
+}
+
+/// sort `data` with lazy funnelsort, using a scratch copy of the same length
+pub fn funnelsort<T: Ord + Copy>(data: &mut [T]) {
+    if data.len() <= LEAF {
+        data.sort_unstable();
+        return;
+    }
+    let mut scratch = data.to_vec();
+    funnelsort_rec(data, &mut scratch);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::distr::{Distribution, Uniform};
+    use rand::{SeedableRng, rngs::StdRng};
+
+    #[test]
+    fn test_funnelsort() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (n, range) in [
+            (0, 10),
+            (1, 10),
+            (LEAF, 1000),
+            (LEAF + 1, 1000),
+            (5000, 3),
+            (100_000, 1 << 30),
+            (300_001, 1000),
+        ] {
+            let uniform = Uniform::new(0, range).unwrap();
+            let mut data: Vec<u32> = uniform.sample_iter(&mut rng).take(n).collect();
+            let mut expected = data.clone();
+            expected.sort_unstable();
+            funnelsort(&mut data);
+            assert_eq!(data, expected);
+        }
+
+        // already sorted, reversed and constant inputs
+        let n = 50_000;
+        let mut sorted: Vec<(u8, i64)> = (0..n).map(|x| ((x % 7) as u8, -x)).collect();
+        let mut expected = sorted.clone();
+        expected.sort_unstable();
+        for _ in 0..2 {
+            funnelsort(&mut sorted);
+            assert_eq!(sorted, expected);
+        }
+        sorted.reverse();
+        funnelsort(&mut sorted);
+        assert_eq!(sorted, expected);
+        let mut constant = vec![7u8; n as usize];
+        funnelsort(&mut constant);
+        assert!(constant.iter().all(|&x| x == 7));
+    }
+
+    #[test]
+    fn test_funnel_merge() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let uniform = Uniform::new(0, 10_000).unwrap();
+        for k in [1, 2, 3, 5, 16, 17, 100] {
+            // runs of very different lengths, some of them empty
+            let runs: Vec<Vec<i32>> = (0..k)
+                .map(|i| {
+                    let mut run: Vec<i32> = uniform
+                        .sample_iter(&mut rng)
+                        .take(i * i % 97 * 13)
+                        .collect();
+                    run.sort_unstable();
+                    run
+                })
+                .collect();
+            let slices: Vec<&[i32]> = runs.iter().map(|run| run.as_slice()).collect();
+            let mut expected = runs.concat();
+            expected.sort_unstable();
+            let mut output = vec![0; expected.len()];
+            funnel_merge(&slices, &mut output);
+            assert_eq!(output, expected);
+        }
+        funnel_merge::<u8>(&[], &mut []);
+    }
+}
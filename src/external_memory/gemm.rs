@@ -0,0 +1,490 @@
+//! Dense f64 and f32 matrix multiplication, C += A B.
+//!
+//! The blocking follows the BLIS scheme from the algorithmica case study: KC rows of NC
+//! columns of B are packed to stay in L3, MC rows of the same KC columns of A are packed
+//! to stay in L2, and an MR x NR micro-kernel keeps its block of C in registers while it
+//! streams a KC x NR panel of B from L1. The panels are stored in the order the kernel
+//! reads them, and partial panels are padded with zeros, so the kernel only ever computes
+//! full tiles and the edges are cut when the tile is added to C.
+
+use std::any::TypeId;
+use std::ops::{Add, Mul};
+
+use super::matrix_view::{MatrixMut, MatrixRef, MatrixView, MatrixViewMut};
+
+/// rows of the micro-kernel tile
+pub const MR: usize = 6;
+
+/// the largest tile a micro-kernel writes, 6 x 16 for f32
+pub const TILE: usize = MR * 16;
+
+/// `tile[i * NR + j] = sum of a[k * MR + i] * b[k * NR + j] over k < kc`
+///
+/// # Safety
+///
+/// `a` must point to kc * MR and `b` to kc * NR readable elements, and the CPU must
+/// support the features the kernel is compiled for.
+pub type MicroKernel<T> = unsafe fn(kc: usize, a: *const T, b: *const T, tile: &mut [T; TILE]);
+
+/// A floating point type with a micro-kernel and blocking parameters.
+pub trait GemmScalar: Copy + Default + Add<Output = Self> + Mul<Output = Self> + 'static {
+    /// columns of the micro-kernel tile, two AVX registers wide
+    const NR: usize;
+    /// rows of A packed at once, a multiple of MR
+    const MC: usize;
+    /// the common dimension packed at once
+    const KC: usize;
+    /// columns of B packed at once, a multiple of NR
+    const NC: usize;
+
+    /// the vectorized micro-kernel, if the CPU supports it
+    fn simd_kernel() -> Option<MicroKernel<Self>>;
+}
+
+// KC x NR of B takes 16 KiB of L1, MC x KC of A 192 KiB (f64) or 96 KiB (f32) of L2,
+// and KC x NC of B 4 MiB of L3
+impl GemmScalar for f64 {
+    const NR: usize = 8;
+    const MC: usize = 96;
+    const KC: usize = 256;
+    const NC: usize = 2048;
+
+    fn simd_kernel() -> Option<MicroKernel<f64>> {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+            return Some(avx2::kernel_f64);
+        }
+        None
+    }
+}
+
+impl GemmScalar for f32 {
+    const NR: usize = 16;
+    const MC: usize = 96;
+    const KC: usize = 256;
+    const NC: usize = 4096;
+
+    fn simd_kernel() -> Option<MicroKernel<f32>> {
+        #[cfg(target_arch = "x86_64")]
+        if is_x86_feature_detected!("avx2") && is_x86_feature_detected!("fma") {
+            return Some(avx2::kernel_f32);
+        }
+        None
+    }
+}
+
+/// the portable micro-kernel
+///
+/// # Safety
+///
+/// As in [`MicroKernel`].
+unsafe fn kernel_scalar<T: GemmScalar>(kc: usize, a: *const T, b: *const T, tile: &mut [T; TILE]) {
+    // SAFETY: the lengths are the caller's contract
+    let (a, b) = unsafe {
+        (
+            std::slice::from_raw_parts(a, kc * MR),
+            std::slice::from_raw_parts(b, kc * T::NR),
+        )
+    };
+    tile.fill(T::default());
+    for k in 0..kc {
+        let b = &b[k * T::NR..(k + 1) * T::NR];
+        for i in 0..MR {
+            let a = a[k * MR + i];
+            for (c, &b) in tile[i * T::NR..(i + 1) * T::NR].iter_mut().zip(b) {
+                *c = *c + a * b;
+            }
+        }
+    }
+}
+
+/// An operand: the entry (i, j) is at `ptr + i * row_step + j * col_step`.
+#[derive(Clone, Copy)]
+struct Strided<P> {
+    ptr: P,
+    row_step: usize,
+    col_step: usize,
+}
+
+/// pack mc x kc of A into panels of MR rows, each stored column by column
+///
+/// # Safety
+///
+/// All mc x kc entries of `a` must be readable.
+unsafe fn pack_a<T: GemmScalar>(a: Strided<*const T>, mc: usize, kc: usize, packed: &mut [T]) {
+    for (panel, i0) in packed.chunks_exact_mut(MR * kc).zip((0..mc).step_by(MR)) {
+        let rows = MR.min(mc - i0);
+        for k in 0..kc {
+            for i in 0..MR {
+                panel[k * MR + i] = if i < rows {
+                    // SAFETY: (i0 + i, k) is inside the block
+                    unsafe { *a.ptr.add((i0 + i) * a.row_step + k * a.col_step) }
+                } else {
+                    T::default()
+                };
+            }
+        }
+    }
+}
+
+/// pack kc x nc of B into panels of NR columns, each stored row by row
+///
+/// # Safety
+///
+/// All kc x nc entries of `b` must be readable.
+unsafe fn pack_b<T: GemmScalar>(b: Strided<*const T>, kc: usize, nc: usize, packed: &mut [T]) {
+    for (panel, j0) in packed
+        .chunks_exact_mut(T::NR * kc)
+        .zip((0..nc).step_by(T::NR))
+    {
+        let cols = T::NR.min(nc - j0);
+        for k in 0..kc {
+            for j in 0..T::NR {
+                panel[k * T::NR + j] = if j < cols {
+                    // SAFETY: (k, j0 + j) is inside the block
+                    unsafe { *b.ptr.add(k * b.row_step + (j0 + j) * b.col_step) }
+                } else {
+                    T::default()
+                };
+            }
+        }
+    }
+}
+
+/// c += a * b for m x k and k x n operands
+///
+/// # Safety
+///
+/// Every entry of the operands must be valid, the entries of `c` must be distinct and
+/// must not alias `a` or `b`, and `kernel` must be supported by the CPU.
+unsafe fn gemm_strided<T: GemmScalar>(
+    (m, k, n): (usize, usize, usize),
+    a: Strided<*const T>,
+    b: Strided<*const T>,
+    c: Strided<*mut T>,
+    kernel: MicroKernel<T>,
+) {
+    if m == 0 || k == 0 || n == 0 {
+        return;
+    }
+    let mut packed_a = vec![T::default(); T::MC.min(m.next_multiple_of(MR)) * T::KC.min(k)];
+    let mut packed_b = vec![T::default(); T::KC.min(k) * T::NC.min(n.next_multiple_of(T::NR))];
+    let mut tile = [T::default(); TILE];
+
+    for jc in (0..n).step_by(T::NC) {
+        let nc = T::NC.min(n - jc);
+        for pc in (0..k).step_by(T::KC) {
+            let kc = T::KC.min(k - pc);
+            let b_block = Strided {
+                // SAFETY: (pc, jc) is an entry of b
+                ptr: unsafe { b.ptr.add(pc * b.row_step + jc * b.col_step) },
+                ..b
+            };
+            let packed_b = &mut packed_b[..kc * nc.next_multiple_of(T::NR)];
+            // SAFETY: the block is inside b
+            unsafe { pack_b(b_block, kc, nc, packed_b) };
+
+            for ic in (0..m).step_by(T::MC) {
+                let mc = T::MC.min(m - ic);
+                let a_block = Strided {
+                    // SAFETY: (ic, pc) is an entry of a
+                    ptr: unsafe { a.ptr.add(ic * a.row_step + pc * a.col_step) },
+                    ..a
+                };
+                let packed_a = &mut packed_a[..mc.next_multiple_of(MR) * kc];
+                // SAFETY: the block is inside a
+                unsafe { pack_a(a_block, mc, kc, packed_a) };
+
+                for jr in (0..nc).step_by(T::NR) {
+                    let nr = T::NR.min(nc - jr);
+                    for ir in (0..mc).step_by(MR) {
+                        let mr = MR.min(mc - ir);
+                        // SAFETY: the panels hold kc * MR and kc * NR elements, and the
+                        // kernel is supported by the caller's contract
+                        unsafe {
+                            kernel(
+                                kc,
+                                packed_a.as_ptr().add(ir * kc),
+                                packed_b.as_ptr().add(jr * kc),
+                                &mut tile,
+                            );
+                        }
+                        let (i0, j0) = (ic + ir, jc + jr);
+                        for i in 0..mr {
+                            for j in 0..nr {
+                                // SAFETY: (i0 + i, j0 + j) is an entry of c
+                                unsafe {
+                                    let entry =
+                                        c.ptr.add((i0 + i) * c.row_step + (j0 + j) * c.col_step);
+                                    *entry = *entry + tile[i * T::NR + j];
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn strided<T>(view: &MatrixView<'_, T>) -> Strided<*const T> {
+    let (ptr, row_step, col_step) = view.raw_parts();
+    Strided {
+        ptr,
+        row_step,
+        col_step,
+    }
+}
+
+fn gemm_with<T: GemmScalar>(
+    a: MatrixView<'_, T>,
+    b: MatrixView<'_, T>,
+    mut c: MatrixViewMut<'_, T>,
+    kernel: MicroKernel<T>,
+) {
+    assert_eq!(a.cols(), b.rows());
+    assert_eq!((c.rows(), c.cols()), (a.rows(), b.cols()));
+    // empty views can point past their slice, so take no pointers to them
+    if a.rows() == 0 || a.cols() == 0 || b.cols() == 0 {
+        return;
+    }
+    let (ptr, row_step, col_step) = c.raw_parts_mut();
+    let c_strided = Strided {
+        ptr,
+        row_step,
+        col_step,
+    };
+    // SAFETY: the views are valid, c is borrowed uniquely and has distinct entries, and
+    // the kernel is either portable or was detected
+    unsafe {
+        gemm_strided(
+            (a.rows(), a.cols(), b.cols()),
+            strided(&a),
+            strided(&b),
+            c_strided,
+            kernel,
+        );
+    }
+}
+
+/// c += a * b with the vectorized micro-kernel, falling back to [`gemm_scalar`]
+pub fn gemm<T: GemmScalar>(a: MatrixView<'_, T>, b: MatrixView<'_, T>, c: MatrixViewMut<'_, T>) {
+    gemm_with(a, b, c, T::simd_kernel().unwrap_or(kernel_scalar::<T>));
+}
+
+/// c += a * b with the same blocking and a portable micro-kernel
+pub fn gemm_scalar<T: GemmScalar>(
+    a: MatrixView<'_, T>,
+    b: MatrixView<'_, T>,
+    c: MatrixViewMut<'_, T>,
+) {
+    gemm_with(a, b, c, kernel_scalar::<T>);
+}
+
+/// run [`gemm`] if T is f64 or f32 and all three matrices are in memory,
+/// returns whether it did
+pub(crate) fn try_gemm<T, A, B, C>(a: &A, b: &B, c: &mut C) -> bool
+where
+    T: 'static,
+    A: MatrixRef<T> + ?Sized,
+    B: MatrixRef<T> + ?Sized,
+    C: MatrixMut<T> + ?Sized,
+{
+    let is_f64 = TypeId::of::<T>() == TypeId::of::<f64>();
+    if !is_f64 && TypeId::of::<T>() != TypeId::of::<f32>() {
+        return false;
+    }
+    let (Some(a), Some(b), Some(c)) = (a.strided(), b.strided(), c.strided_mut()) else {
+        return false;
+    };
+    // SAFETY: T is the type the views are cast to
+    unsafe {
+        if is_f64 {
+            gemm::<f64>(a.cast(), b.cast(), c.cast());
+        } else {
+            gemm::<f32>(a.cast(), b.cast(), c.cast());
+        }
+    }
+    true
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use super::{MR, TILE};
+    use std::arch::x86_64::*;
+
+    /// 6 x 8 tile in 12 registers, B is loaded once and each entry of A is broadcast
+    ///
+    /// # Safety
+    ///
+    /// As in [`super::MicroKernel`], with AVX2 and FMA.
+    #[target_feature(enable = "avx2,fma")]
+    pub(super) unsafe fn kernel_f64(
+        kc: usize,
+        a: *const f64,
+        b: *const f64,
+        tile: &mut [f64; TILE],
+    ) {
+        let mut c = [[_mm256_setzero_pd(); 2]; MR];
+        for k in 0..kc {
+            // SAFETY: the panels hold kc rows
+            unsafe {
+                let b0 = _mm256_loadu_pd(b.add(k * 8));
+                let b1 = _mm256_loadu_pd(b.add(k * 8 + 4));
+                for (i, c) in c.iter_mut().enumerate() {
+                    let a = _mm256_broadcast_sd(&*a.add(k * MR + i));
+                    c[0] = _mm256_fmadd_pd(a, b0, c[0]);
+                    c[1] = _mm256_fmadd_pd(a, b1, c[1]);
+                }
+            }
+        }
+        for (i, c) in c.iter().enumerate() {
+            // SAFETY: the tile holds MR rows of 8
+            unsafe {
+                _mm256_storeu_pd(tile.as_mut_ptr().add(i * 8), c[0]);
+                _mm256_storeu_pd(tile.as_mut_ptr().add(i * 8 + 4), c[1]);
+            }
+        }
+    }
+
+    /// 6 x 16 tile in 12 registers, the same schedule as [`kernel_f64`]
+    ///
+    /// # Safety
+    ///
+    /// As in [`super::MicroKernel`], with AVX2 and FMA.
+    #[target_feature(enable = "avx2,fma")]
+    pub(super) unsafe fn kernel_f32(
+        kc: usize,
+        a: *const f32,
+        b: *const f32,
+        tile: &mut [f32; TILE],
+    ) {
+        let mut c = [[_mm256_setzero_ps(); 2]; MR];
+        for k in 0..kc {
+            // SAFETY: the panels hold kc rows
+            unsafe {
+                let b0 = _mm256_loadu_ps(b.add(k * 16));
+                let b1 = _mm256_loadu_ps(b.add(k * 16 + 8));
+                for (i, c) in c.iter_mut().enumerate() {
+                    let a = _mm256_broadcast_ss(&*a.add(k * MR + i));
+                    c[0] = _mm256_fmadd_ps(a, b0, c[0]);
+                    c[1] = _mm256_fmadd_ps(a, b1, c[1]);
+                }
+            }
+        }
+        for (i, c) in c.iter().enumerate() {
+            // SAFETY: the tile holds MR rows of 16
+            unsafe {
+                _mm256_storeu_ps(tile.as_mut_ptr().add(i * 16), c[0]);
+                _mm256_storeu_ps(tile.as_mut_ptr().add(i * 16 + 8), c[1]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_memory::cache_oblivious::Matrix;
+    use crate::external_memory::matrix_view::Layout;
+    use rand::distr::{Distribution, Uniform};
+    use rand::{SeedableRng, rngs::StdRng};
+
+    fn random<T: GemmScalar>(n: usize, m: usize, rng: &mut StdRng) -> Matrix<T>
+    where
+        f32: Into<T>,
+    {
+        let uniform = Uniform::new(-1f32, 1.).unwrap();
+        Matrix::from_vec(
+            uniform
+                .sample_iter(rng)
+                .take(n * m)
+                .map(Into::into)
+                .collect(),
+            n,
+            m,
+        )
+    }
+
+    fn assert_close(actual: &Matrix<f64>, expected: &Matrix<f64>, eps: f64) {
+        assert_eq!(
+            (actual.rows(), actual.cols()),
+            (expected.rows(), expected.cols())
+        );
+        for (x, y) in actual.iter().zip(expected.iter()) {
+            assert!((x - y).abs() <= eps, "{x} != {y}");
+        }
+    }
+
+    #[test]
+    fn test_gemm_shapes() {
+        let mut rng = StdRng::seed_from_u64(0);
+        // edges in every blocking dimension, and sizes past MC, KC and NC
+        for (m, k, n) in [
+            (1, 1, 1),
+            (7, 13, 5),
+            (6, 256, 8),
+            (100, 37, 250),
+            (97, 300, 17),
+            (13, 20, 2100),
+        ] {
+            let a = random::<f64>(m, k, &mut rng);
+            let b = random::<f64>(k, n, &mut rng);
+            let expected = a.simple_mul(&b);
+            let eps = 1e-12 * k as f64;
+
+            let mut c = Matrix::new(m, n);
+            gemm(a.view(), b.view(), c.view_mut());
+            assert_close(&c, &expected, eps);
+            let mut c = Matrix::new(m, n);
+            gemm_scalar(a.view(), b.view(), c.view_mut());
+            assert_close(&c, &expected, eps);
+
+            // the dispatch in mul, and f32 with a looser tolerance
+            assert_close(&a.mul(&b), &expected, eps);
+            let product = a.map(|&x| x as f32).mul(&b.map(|&x| x as f32));
+            assert_close(&product.map(|&x| x as f64), &expected, 1e-5 * k as f64);
+        }
+    }
+
+    #[test]
+    fn test_gemm_views() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let a = random::<f32>(40, 50, &mut rng);
+        let b = random::<f32>(60, 30, &mut rng);
+
+        // a transposed operand, a submatrix, and a column-major target that is added to
+        let a_t = a.transpose();
+        let a_view = a_t.view().t().submatrix(3..40, 0..50);
+        let b_view = b.view().submatrix(5..55, 1..30).t().t();
+        let mut data = vec![1f32; 37 * 31];
+        let c = MatrixViewMut::from_parts(&mut data, 1, 37, 29, 37, Layout::ColMajor);
+        gemm(a_view, b_view, c);
+
+        let expected = a_view.to_matrix().simple_mul(&b_view);
+        let c = MatrixView::from_parts(&data, 1, 37, 29, 37, Layout::ColMajor);
+        for i in 0..37 {
+            for j in 0..29 {
+                assert!((c.get(i, j) - 1. - expected.get(i, j)).abs() < 1e-4);
+            }
+        }
+        assert_eq!(data[0], 1.);
+
+        // the bottom half of a split at the last row is empty, with its offset past the
+        // end of the slice
+        let data = vec![1f64; 8];
+        let (_, empty) =
+            MatrixView::from_parts(&data, 0, 2, 3, 5, Layout::RowMajor).split_at_row(2);
+        let b = vec![1f64; 6];
+        let mut c_data = vec![0f64; 8];
+        let (_, c) =
+            MatrixViewMut::from_parts(&mut c_data, 0, 2, 2, 5, Layout::RowMajor).split_at_row(2);
+        gemm(empty, MatrixView::new(&b, 3, 2), c);
+        gemm_scalar(
+            MatrixView::new(&b, 2, 3),
+            MatrixView::new(&b, 3, 0),
+            MatrixViewMut::new(&mut [], 2, 0),
+        );
+        assert_eq!(c_data, [0.; 8]);
+    }
+}
@@ -0,0 +1,623 @@
+//! List ranking and Euler tours of trees stored in files that do not fit in memory.
+//!
+//! A list is a file of `(node, next)` pairs, with [`NIL`] as the next of a tail. Each
+//! level of the ranking removes an independent set of nodes chosen by coin flips, which
+//! takes a constant number of sorts and scans and shrinks the list by a constant factor
+//! in expectation. The shorter list is ranked recursively and the removed nodes are put
+//! back, so the total is O(sort(N)) I/Os. Lists that fit in the memory budget are
+//! ranked in memory.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::external_sorting::{ExternalSorter, FileSystem, SortStorage, TempDirGuard};
+use super::record::{Record, RecordReader, RecordWriter};
+
+/// The next of the last node of a list.
+pub const NIL: u64 = u64::MAX;
+
+/// (node, next, weight of the link to next)
+type Link = (u64, u64, u64);
+
+/// the memory [`rank_in_memory`] takes per link: the link, its next index, whether it
+/// has a predecessor and its rank
+const IN_MEMORY_LINK_SIZE: usize =
+    size_of::<Link>() + size_of::<usize>() + size_of::<bool>() + size_of::<u64>();
+
+fn open<'a, S: SortStorage, T: Record>(
+    storage: &'a S,
+    path: &Path,
+) -> io::Result<RecordReader<S::Reader<'a>, T>> {
+    Ok(RecordReader::new(storage.open(path)?))
+}
+
+fn create<'a, S: SortStorage, T: Record>(
+    storage: &'a S,
+    path: &Path,
+) -> io::Result<RecordWriter<S::Writer<'a>, T>> {
+    Ok(RecordWriter::new(storage.create(path)?))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A record reader that can look at its next record.
+struct Peekable<R, T> {
+    reader: RecordReader<R, T>,
+    head: Option<T>,
+}
+
+impl<R: io::Read, T: Record> Peekable<R, T> {
+    fn new(mut reader: RecordReader<R, T>) -> io::Result<Self> {
+        let head = reader.read_record()?;
+        Ok(Peekable { reader, head })
+    }
+
+    fn peek(&self) -> Option<&T> {
+        self.head.as_ref()
+    }
+
+    fn advance(&mut self) -> io::Result<Option<T>> {
+        let next = self.reader.read_record()?;
+        Ok(std::mem::replace(&mut self.head, next))
+    }
+}
+
+/// a pseudo random coin flip per node and level
+#[inline]
+fn coin(node: u64, seed: u64) -> bool {
+    let mut x = node ^ seed;
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    (x ^ (x >> 31)) & 1 == 1
+}
+
+/// Ranks linked lists and builds Euler tours with external sorting.
+#[derive(Debug, Clone)]
+pub struct ListRanker {
+    memory_budget: usize,
+    temp_dir: PathBuf,
+}
+
+impl ListRanker {
+    /// `memory_budget` is the number of bytes of records kept in memory at once
+    pub fn new(memory_budget: usize) -> Self {
+        ListRanker {
+            memory_budget,
+            temp_dir: std::env::temp_dir(),
+        }
+    }
+
+    /// where the intermediate files go, defaults to `std::env::temp_dir()`
+    pub fn temp_dir(mut self, temp_dir: impl Into<PathBuf>) -> Self {
+        self.temp_dir = temp_dir.into();
+        self
+    }
+
+    /// rank the `(node, next)` pairs of `input` into `output`
+    pub fn rank_file(&self, input: &Path, output: &Path) -> io::Result<()> {
+        self.rank_on(&FileSystem, input, output)
+    }
+
+    /// rank the `(node, next)` pairs of `input`, both files in `storage`
+    ///
+    /// `output` gets a `(node, rank)` pair for every node, sorted by node, where the rank
+    /// is the number of nodes before it. The input may hold several disjoint lists.
+    pub fn rank_on<S: SortStorage>(
+        &self,
+        storage: &S,
+        input: &Path,
+        output: &Path,
+    ) -> io::Result<()> {
+        let guard = TempDirGuard::new(storage, &self.temp_dir)?;
+        let (links, list) = (guard.path.join("links"), guard.path.join("list"));
+        let mut reader = open::<_, (u64, u64)>(storage, input)?;
+        let mut writer = create::<_, Link>(storage, &links)?;
+        while let Some((node, next)) = reader.read_record()? {
+            if node == NIL {
+                return Err(invalid("NIL is not a node"));
+            }
+            writer.write_record(&(node, next, 1))?;
+        }
+        writer.flush()?;
+        drop(writer);
+        self.sorter(&guard.path)
+            .sort_on::<Link, _>(storage, &links, &list)?;
+        storage.remove_file(&links)?;
+        self.rank_level(storage, &guard.path, &list, output, 0)
+    }
+
+    fn sorter(&self, temp_dir: &Path) -> ExternalSorter {
+        ExternalSorter::new(self.memory_budget).temp_dir(temp_dir)
+    }
+
+    /// rank a `Link` file sorted by node into `(node, weighted rank)` pairs
+    fn rank_level<S: SortStorage>(
+        &self,
+        storage: &S,
+        dir: &Path,
+        list: &Path,
+        output: &Path,
+        level: u64,
+    ) -> io::Result<()> {
+        let len = storage.file_len(list)? as usize / Link::SIZE;
+        // cycles of one or two nodes never shrink, so they have to fit in memory
+        if len * IN_MEMORY_LINK_SIZE <= self.memory_budget.max(2 * IN_MEMORY_LINK_SIZE) {
+            return rank_in_memory(storage, list, output);
+        }
+        let path = |name: &str| dir.join(format!("{name}-{level}"));
+        let sorter = self.sorter(dir);
+        let seed = level.wrapping_mul(0x9e3779b97f4a7c15);
+
+        // tell every node the coin of its predecessor: (next, node, coin)
+        let (preds, preds_sorted) = (path("preds"), path("preds-sorted"));
+        let mut reader = open::<_, Link>(storage, list)?;
+        let mut writer = create::<_, (u64, u64, u64)>(storage, &preds)?;
+        while let Some((node, next, _)) = reader.read_record()? {
+            if next != NIL {
+                writer.write_record(&(next, node, coin(node, seed) as u64))?;
+            }
+        }
+        writer.flush()?;
+        drop(writer);
+        sorter.sort_on::<(u64, u64, u64), _>(storage, &preds, &preds_sorted)?;
+        storage.remove_file(&preds)?;
+
+        // remove the nodes with heads whose predecessor has tails, no two of them are
+        // adjacent and no head of a list is removed: (pred, node, next, weight)
+        // single nodes are ranked already and leave the recursion
+        let (kept, splices, splices_sorted, singles) = (
+            path("kept"),
+            path("splices"),
+            path("splices-sorted"),
+            path("singles"),
+        );
+        {
+            let mut reader = open::<_, Link>(storage, list)?;
+            let mut preds = Peekable::new(open::<_, (u64, u64, u64)>(storage, &preds_sorted)?)?;
+            let mut kept = create::<_, Link>(storage, &kept)?;
+            let mut splices = create::<_, (u64, u64, u64, u64)>(storage, &splices)?;
+            let mut singles = create::<_, (u64, u64)>(storage, &singles)?;
+            while let Some((node, next, weight)) = reader.read_record()? {
+                let mut pred = None;
+                while let Some(&(target, u, u_coin)) = preds.peek() {
+                    if target > node {
+                        break;
+                    }
+                    if target == node {
+                        pred = Some((u, u_coin));
+                    }
+                    preds.advance()?;
+                }
+                match pred {
+                    Some((u, 0)) if coin(node, seed) => {
+                        splices.write_record(&(u, node, next, weight))?
+                    }
+                    None if next == NIL => singles.write_record(&(node, 0))?,
+                    _ => kept.write_record(&(node, next, weight))?,
+                }
+            }
+            kept.flush()?;
+            splices.flush()?;
+            singles.flush()?;
+        }
+        storage.remove_file(&preds_sorted)?;
+        sorter.sort_on::<(u64, u64, u64, u64), _>(storage, &splices, &splices_sorted)?;
+        storage.remove_file(&splices)?;
+
+        // the predecessors skip the removed nodes, remember (pred, node, weight of pred)
+        let (shorter, removed) = (path("shorter"), path("removed"));
+        {
+            let mut reader = open::<_, Link>(storage, &kept)?;
+            let mut splices =
+                Peekable::new(open::<_, (u64, u64, u64, u64)>(storage, &splices_sorted)?)?;
+            let mut shorter = create::<_, Link>(storage, &shorter)?;
+            let mut removed = create::<_, (u64, u64, u64)>(storage, &removed)?;
+            while let Some((node, mut next, mut weight)) = reader.read_record()? {
+                if let Some(&(u, v, v_next, v_weight)) = splices.peek()
+                    && u == node
+                {
+                    removed.write_record(&(u, v, weight))?;
+                    next = v_next;
+                    weight += v_weight;
+                    splices.advance()?;
+                }
+                shorter.write_record(&(node, next, weight))?;
+            }
+            shorter.flush()?;
+            removed.flush()?;
+        }
+        storage.remove_file(&kept)?;
+        storage.remove_file(&splices_sorted)?;
+
+        let ranks = path("ranks");
+        self.rank_level(storage, dir, &shorter, &ranks, level + 1)?;
+        storage.remove_file(&shorter)?;
+
+        // a removed node is ranked right after its predecessor
+        let (back, back_sorted) = (path("back"), path("back-sorted"));
+        {
+            let mut removed = open::<_, (u64, u64, u64)>(storage, &removed)?;
+            let mut ranks = Peekable::new(open::<_, (u64, u64)>(storage, &ranks)?)?;
+            let mut back = create::<_, (u64, u64)>(storage, &back)?;
+            while let Some((u, v, u_weight)) = removed.read_record()? {
+                while ranks.peek().is_some_and(|&(node, _)| node < u) {
+                    ranks.advance()?;
+                }
+                let &(_, rank) = ranks.peek().unwrap();
+                back.write_record(&(v, rank + u_weight))?;
+            }
+            back.flush()?;
+        }
+        storage.remove_file(&removed)?;
+        sorter.sort_on::<(u64, u64), _>(storage, &back, &back_sorted)?;
+        storage.remove_file(&back)?;
+
+        merge_sorted(storage, &[&ranks, &back_sorted, &singles], output)?;
+        for file in [&ranks, &back_sorted, &singles] {
+            storage.remove_file(file)?;
+        }
+        Ok(())
+    }
+
+    /// write the Euler tour of a tree into `output`, both files in `storage`
+    ///
+    /// The tree is a file of `(u, v)` edges, each given once. The tour is a depth-first
+    /// traversal from `root` written as its 2(n - 1) arcs `(source, target)`: it starts
+    /// with the smallest neighbour of the root, and leaves a vertex entered from `p`
+    /// through its next larger neighbour after `p`, wrapping around.
+    pub fn euler_tour_on<S: SortStorage>(
+        &self,
+        storage: &S,
+        edges: &Path,
+        root: u64,
+        output: &Path,
+    ) -> io::Result<()> {
+        let guard = TempDirGuard::new(storage, &self.temp_dir)?;
+        let path = |name: &str| guard.path.join(format!("tour-{name}"));
+        let sorter = self.sorter(&guard.path);
+
+        // both directions of every edge sorted by (source, target), an arc is named
+        // by its position in this file
+        let (arcs_unsorted, arcs) = (path("arcs-unsorted"), path("arcs"));
+        let mut reader = open::<_, (u64, u64)>(storage, edges)?;
+        let mut writer = create::<_, (u64, u64)>(storage, &arcs_unsorted)?;
+        while let Some((u, v)) = reader.read_record()? {
+            writer.write_record(&(u, v))?;
+            writer.write_record(&(v, u))?;
+        }
+        writer.flush()?;
+        drop(writer);
+        sorter.sort_on::<(u64, u64), _>(storage, &arcs_unsorted, &arcs)?;
+        storage.remove_file(&arcs_unsorted)?;
+
+        // sorting the arcs by (target, source) lists the twins in arc order
+        let (flipped, flipped_sorted) = (path("flipped"), path("flipped-sorted"));
+        let (twins, twins_sorted) = (path("twins"), path("twins-sorted"));
+        let mut reader = open::<_, (u64, u64)>(storage, &arcs)?;
+        let mut writer = create::<_, (u64, u64, u64)>(storage, &flipped)?;
+        let mut id = 0;
+        while let Some((s, t)) = reader.read_record()? {
+            writer.write_record(&(t, s, id))?;
+            id += 1;
+        }
+        writer.flush()?;
+        drop(writer);
+        sorter.sort_on::<(u64, u64, u64), _>(storage, &flipped, &flipped_sorted)?;
+        storage.remove_file(&flipped)?;
+        let mut reader = open::<_, (u64, u64, u64)>(storage, &flipped_sorted)?;
+        let mut writer = create::<_, (u64, u64)>(storage, &twins)?;
+        let mut twin = 0;
+        while let Some((_, _, id)) = reader.read_record()? {
+            writer.write_record(&(id, twin))?;
+            twin += 1;
+        }
+        writer.flush()?;
+        drop(writer);
+        sorter.sort_on::<(u64, u64), _>(storage, &twins, &twins_sorted)?;
+        storage.remove_file(&flipped_sorted)?;
+        storage.remove_file(&twins)?;
+
+        // the arc after (w_i, v) is (v, w_i+1) over the sorted neighbours w of v,
+        // the tour starts with the first arc of the root so the one before it ends the list
+        let successors = path("successors");
+        {
+            let mut arcs = open::<_, (u64, u64)>(storage, &arcs)?;
+            let mut twins = open::<_, (u64, u64)>(storage, &twins_sorted)?;
+            let mut successors = create::<_, (u64, u64)>(storage, &successors)?;
+            // (vertex, its first arc, twin of the previous arc)
+            let mut group: Option<(u64, u64, u64)> = None;
+            let mut found_root = false;
+            let mut id = 0;
+            let close = |(v, first, last_twin): (u64, u64, u64)| {
+                (last_twin, if v == root { NIL } else { first })
+            };
+            while let Some((s, _)) = arcs.read_record()? {
+                let (_, twin) = twins.read_record()?.unwrap();
+                match group {
+                    Some((v, first, previous)) if v == s => {
+                        successors.write_record(&(previous, id))?;
+                        group = Some((v, first, twin));
+                    }
+                    _ => {
+                        if let Some(group) = group {
+                            successors.write_record(&close(group))?;
+                        }
+                        found_root |= s == root;
+                        group = Some((s, id, twin));
+                    }
+                }
+                id += 1;
+            }
+            if let Some(group) = group {
+                successors.write_record(&close(group))?;
+                if !found_root {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "the root is not in the tree",
+                    ));
+                }
+            }
+            successors.flush()?;
+        }
+        storage.remove_file(&twins_sorted)?;
+
+        let ranks = path("ranks");
+        self.rank_on(storage, &successors, &ranks)?;
+        storage.remove_file(&successors)?;
+
+        let (tour, tour_sorted) = (path("tour"), path("tour-sorted"));
+        let mut arcs_reader = open::<_, (u64, u64)>(storage, &arcs)?;
+        let mut ranks_reader = open::<_, (u64, u64)>(storage, &ranks)?;
+        let mut writer = create::<_, (u64, u64, u64)>(storage, &tour)?;
+        while let Some((s, t)) = arcs_reader.read_record()? {
+            let (_, rank) = ranks_reader.read_record()?.unwrap();
+            writer.write_record(&(rank, s, t))?;
+        }
+        writer.flush()?;
+        drop(writer);
+        sorter.sort_on::<(u64, u64, u64), _>(storage, &tour, &tour_sorted)?;
+
+        let mut reader = open::<_, (u64, u64, u64)>(storage, &tour_sorted)?;
+        let mut writer = create::<_, (u64, u64)>(storage, output)?;
+        while let Some((_, s, t)) = reader.read_record()? {
+            writer.write_record(&(s, t))?;
+        }
+        writer.flush()
+    }
+
+    /// write the Euler tour of the tree in `edges` into `output`
+    pub fn euler_tour_file(&self, edges: &Path, root: u64, output: &Path) -> io::Result<()> {
+        self.euler_tour_on(&FileSystem, edges, root, output)
+    }
+}
+
+/// rank a `Link` file sorted by node that fits in memory
+fn rank_in_memory<S: SortStorage>(storage: &S, list: &Path, output: &Path) -> io::Result<()> {
+    let links = open::<_, Link>(storage, list)?.collect::<io::Result<Vec<_>>>()?;
+    if links.windows(2).any(|w| w[0].0 == w[1].0) {
+        return Err(invalid("duplicate node"));
+    }
+    let mut next_index = vec![usize::MAX; links.len()];
+    let mut has_pred = vec![false; links.len()];
+    for (i, &(_, next, _)) in links.iter().enumerate() {
+        if next == NIL {
+            continue;
+        }
+        let j = links
+            .binary_search_by_key(&next, |&(node, _, _)| node)
+            .map_err(|_| invalid("next is not a node of the list"))?;
+        if std::mem::replace(&mut has_pred[j], true) {
+            return Err(invalid("node with two predecessors"));
+        }
+        next_index[i] = j;
+    }
+
+    let mut ranks = vec![NIL; links.len()];
+    for head in (0..links.len()).filter(|&i| !has_pred[i]) {
+        let (mut i, mut rank) = (head, 0);
+        loop {
+            ranks[i] = rank;
+            rank += links[i].2;
+            if next_index[i] == usize::MAX {
+                break;
+            }
+            i = next_index[i];
+        }
+    }
+    // nodes not reached from a head are on a cycle
+    if ranks.contains(&NIL) {
+        return Err(invalid("the list has a cycle"));
+    }
+
+    let mut writer = create::<_, (u64, u64)>(storage, output)?;
+    for (&(node, _, _), &rank) in links.iter().zip(&ranks) {
+        writer.write_record(&(node, rank))?;
+    }
+    writer.flush()
+}
+
+/// merge files of pairs sorted by their first element
+fn merge_sorted<S: SortStorage>(storage: &S, inputs: &[&Path], output: &Path) -> io::Result<()> {
+    let mut inputs = inputs
+        .iter()
+        .map(|path| Peekable::new(open::<_, (u64, u64)>(storage, path)?))
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut writer = create::<_, (u64, u64)>(storage, output)?;
+    while let Some(input) = inputs
+        .iter_mut()
+        .filter(|input| input.peek().is_some())
+        .min_by_key(|input| input.peek().copied())
+    {
+        writer.write_record(&input.advance()?.unwrap())?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_memory::block_device::BlockDevice;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashMap;
+
+    fn to_bytes<T: Record>(records: &[T]) -> Vec<u8> {
+        let mut writer = RecordWriter::new(Vec::new());
+        writer.write_records(records).unwrap();
+        writer.into_inner().unwrap()
+    }
+
+    fn from_bytes<T: Record>(bytes: &[u8]) -> Vec<T> {
+        RecordReader::new(bytes).collect::<io::Result<_>>().unwrap()
+    }
+
+    type Pairs = Vec<(u64, u64)>;
+
+    /// shuffled `(node, next)` pairs of lists with the given lengths and random node ids
+    fn random_lists(lengths: &[usize], rng: &mut StdRng) -> (Pairs, Pairs) {
+        let total: usize = lengths.iter().sum();
+        let mut ids: Vec<u64> = (0..total as u64)
+            .map(|i| i * 1_000_003 % (1 << 40))
+            .collect();
+        ids.shuffle(rng);
+        let (mut links, mut ranks) = (Vec::new(), Vec::new());
+        let mut start = 0;
+        for &len in lengths {
+            let list = &ids[start..start + len];
+            for (rank, &node) in list.iter().enumerate() {
+                links.push((node, list.get(rank + 1).copied().unwrap_or(NIL)));
+                ranks.push((node, rank as u64));
+            }
+            start += len;
+        }
+        links.shuffle(rng);
+        ranks.sort_unstable();
+        (links, ranks)
+    }
+
+    fn rank(links: &[(u64, u64)], memory_budget: usize) -> io::Result<Vec<(u64, u64)>> {
+        let device = BlockDevice::new(1 << 16, 1 << 10);
+        device.write_file("list", to_bytes(links));
+        ListRanker::new(memory_budget).temp_dir("tmp").rank_on(
+            &device,
+            Path::new("list"),
+            Path::new("ranks"),
+        )?;
+        Ok(from_bytes(&device.read_file(Path::new("ranks"))?))
+    }
+
+    #[test]
+    fn test_list_ranking() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for lengths in [
+            vec![],
+            vec![1],
+            vec![20_000],
+            vec![1; 5000],
+            vec![2; 3000],
+            vec![5000, 1, 3, 1, 700, 2, 2, 4000],
+        ] {
+            let (links, expected) = random_lists(&lengths, &mut rng);
+            assert_eq!(rank(&links, 4 << 10).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_list_ranking_files() {
+        let (links, expected) = random_lists(&[30_000], &mut StdRng::seed_from_u64(1));
+        let dir = std::env::temp_dir().join(format!("hpc-rs-rank-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (input, output) = (dir.join("list"), dir.join("ranks"));
+        std::fs::write(&input, to_bytes(&links)).unwrap();
+        ListRanker::new(64 << 10)
+            .temp_dir(&dir)
+            .rank_file(&input, &output)
+            .unwrap();
+        let ranks: Vec<(u64, u64)> = from_bytes(&std::fs::read(&output).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(ranks, expected);
+    }
+
+    #[test]
+    fn test_list_ranking_invalid() {
+        let cycle = [(1, 2), (2, 3), (3, 1), (4, NIL)];
+        let two_preds = [(1, 3), (2, 3), (3, NIL)];
+        let missing = [(1, 2), (2, 5)];
+        for links in [&cycle[..], &two_preds, &missing] {
+            let error = rank(links, 4 << 10).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+        // a long cycle is only seen once it fits in memory
+        let mut links: Vec<(u64, u64)> = (0..5000).map(|i| (i, (i + 1) % 5000)).collect();
+        links.push((5000, NIL));
+        assert!(rank(&links, 4 << 10).is_err());
+        // short cycles are found even if not a single link fits in the budget
+        for links in [&[(7, 7)][..], &[(1, 2), (2, 1), (3, NIL)]] {
+            let error = rank(links, 1).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    /// the tour by following the successor rule in memory
+    fn euler_tour_in_memory(edges: &[(u64, u64)], root: u64) -> Vec<(u64, u64)> {
+        let mut neighbours: HashMap<u64, Vec<u64>> = HashMap::new();
+        for &(u, v) in edges {
+            neighbours.entry(u).or_default().push(v);
+            neighbours.entry(v).or_default().push(u);
+        }
+        for list in neighbours.values_mut() {
+            list.sort_unstable();
+        }
+        let mut tour = Vec::new();
+        if edges.is_empty() {
+            return tour;
+        }
+        let mut arc = (root, neighbours[&root][0]);
+        for _ in 0..2 * edges.len() {
+            tour.push(arc);
+            let (from, v) = arc;
+            let list = &neighbours[&v];
+            let i = list.binary_search(&from).unwrap();
+            arc = (v, list[(i + 1) % list.len()]);
+        }
+        tour
+    }
+
+    #[test]
+    fn test_euler_tour() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let device = BlockDevice::new(1 << 16, 1 << 10);
+        let ranker = ListRanker::new(8 << 10).temp_dir("tmp");
+        for n in [1, 2, 3, 10, 3000] {
+            // random tree with shuffled labels and edge directions
+            let mut labels: Vec<u64> = (0..n).map(|i| 7 * i + 3).collect();
+            labels.shuffle(&mut rng);
+            let mut edges: Vec<(u64, u64)> = (1..n as usize)
+                .map(|v| {
+                    let parent = rng.random_range(0..v);
+                    (labels[v], labels[parent])
+                })
+                .collect();
+            edges.shuffle(&mut rng);
+            let root = labels[rng.random_range(0..n as usize)];
+
+            device.write_file("edges", to_bytes(&edges));
+            ranker
+                .euler_tour_on(&device, Path::new("edges"), root, Path::new("tour"))
+                .unwrap();
+            let tour: Vec<(u64, u64)> = from_bytes(&device.read_file(Path::new("tour")).unwrap());
+            assert_eq!(tour.len(), 2 * (n as usize - 1));
+            assert!(tour.windows(2).all(|w| w[0].1 == w[1].0));
+            assert_eq!(tour, euler_tour_in_memory(&edges, root));
+        }
+
+        device.write_file("edges", to_bytes(&[(1u64, 2u64)]));
+        let error = ranker
+            .euler_tour_on(&device, Path::new("edges"), 3, Path::new("tour"))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
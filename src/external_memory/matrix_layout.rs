@@ -0,0 +1,710 @@
+//! Matrices stored in Z-order (Morton) or in square tiles instead of row by row.
+//!
+//! In both orders the position of (i, j) is `row_offset(i) + col_offset(j)`. Morton
+//! interleaves the bits of i and j, which is one `pdep` each with BMI2, and keeps every
+//! aligned power-of-two block in one piece of memory, at every size. A tiled matrix is
+//! a row-major grid of row-major tiles of side 2^s, found with shifts and masks. The
+//! kernels recurse on aligned blocks and look the offsets of their rows and columns up
+//! in tables, so the inner loops do no bit manipulation.
+
+use std::fmt;
+use std::ops::{Add, Mul};
+
+use super::cache_oblivious::Matrix;
+use super::matrix_view::{MatrixMut, MatrixRef};
+use super::storage::Storage;
+
+/// The order of the entries of a rows x cols matrix in a padded buffer.
+pub trait ElementOrder: Clone + fmt::Debug {
+    fn rows(&self) -> usize;
+
+    fn cols(&self) -> usize;
+
+    /// the length of the buffer, padding included
+    fn size(&self) -> usize;
+
+    fn row_offset(&self, i: usize) -> usize;
+
+    fn col_offset(&self, j: usize) -> usize;
+
+    #[inline]
+    fn index(&self, i: usize, j: usize) -> usize {
+        self.row_offset(i) + self.col_offset(j)
+    }
+
+    /// call `f` with the row and column of every slot of the buffer in order, also for
+    /// the padding outside of the matrix
+    fn for_each_position(&self, f: impl FnMut(usize, usize));
+
+    /// the same kind of order for a rows x cols matrix
+    fn with_shape(&self, rows: usize, cols: usize) -> Self;
+
+    /// the columns stored next to each other come in aligned runs of this power of two
+    fn run(&self) -> usize;
+}
+
+/// put the low 32 bits of x in the even bits
+fn spread(x: u64) -> u64 {
+    let mut x = x & 0xFFFF_FFFF;
+    x = (x | x << 16) & 0x0000_FFFF_0000_FFFF;
+    x = (x | x << 8) & 0x00FF_00FF_00FF_00FF;
+    x = (x | x << 4) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | x << 2) & 0x3333_3333_3333_3333;
+    (x | x << 1) & 0x5555_5555_5555_5555
+}
+
+/// the inverse of [`spread`], the odd bits are dropped
+fn compact(x: u64) -> u64 {
+    let mut x = x & 0x5555_5555_5555_5555;
+    x = (x | x >> 1) & 0x3333_3333_3333_3333;
+    x = (x | x >> 2) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | x >> 4) & 0x00FF_00FF_00FF_00FF;
+    x = (x | x >> 8) & 0x0000_FFFF_0000_FFFF;
+    (x | x >> 16) & 0xFFFF_FFFF
+}
+
+/// Z-order of a matrix padded to 2^row_bits x 2^col_bits.
+///
+/// The low bits of i and j alternate, with the bits of i above those of j, and the
+/// leftover high bits of the longer side come on top. So a square matrix is one Z curve
+/// and a tall or wide one is a column or row of square Z-ordered blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Morton {
+    rows: usize,
+    cols: usize,
+    row_bits: u32,
+    col_bits: u32,
+    row_mask: u64,
+    col_mask: u64,
+    /// use `pdep` and `pext`
+    bmi2: bool,
+}
+
+impl Morton {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        let row_bits = rows.next_power_of_two().trailing_zeros();
+        let col_bits = cols.next_power_of_two().trailing_zeros();
+        assert!(row_bits + col_bits < 64, "matrix too large");
+        let shared = row_bits.min(col_bits);
+        let low = (1u64 << (2 * shared)) - 1;
+        let high = |bits: u32| ((1u64 << bits) - 1) >> shared << (2 * shared);
+        #[cfg(target_arch = "x86_64")]
+        let bmi2 = is_x86_feature_detected!("bmi2");
+        #[cfg(not(target_arch = "x86_64"))]
+        let bmi2 = false;
+        Morton {
+            rows,
+            cols,
+            row_bits,
+            col_bits,
+            row_mask: (0xAAAA_AAAA_AAAA_AAAA & low) | high(row_bits),
+            col_mask: (0x5555_5555_5555_5555 & low) | high(col_bits),
+            bmi2,
+        }
+    }
+
+    /// the same order computed without BMI2
+    pub fn portable(self) -> Self {
+        Morton {
+            bmi2: false,
+            ..self
+        }
+    }
+
+    fn shared_bits(&self) -> u32 {
+        self.row_bits.min(self.col_bits)
+    }
+
+    /// the row and column of a slot, the inverse of [`ElementOrder::index`]
+    #[inline]
+    pub fn position(&self, index: usize) -> (usize, usize) {
+        let z = index as u64;
+        #[cfg(target_arch = "x86_64")]
+        if self.bmi2 {
+            // SAFETY: the CPU supports BMI2
+            return unsafe { (bmi2::pext(z, self.row_mask), bmi2::pext(z, self.col_mask)) };
+        }
+        let shared = self.shared_bits();
+        let low = (1 << shared) - 1;
+        let high = z >> (2 * shared) << shared;
+        let (mut i, mut j) = (compact(z >> 1) & low, compact(z) & low);
+        if self.row_bits > self.col_bits {
+            i |= high;
+        } else {
+            j |= high;
+        }
+        (i as usize, j as usize)
+    }
+}
+
+impl ElementOrder for Morton {
+    fn rows(&self) -> usize {
+        self.rows
+    }
+
+    fn cols(&self) -> usize {
+        self.cols
+    }
+
+    fn size(&self) -> usize {
+        1 << (self.row_bits + self.col_bits)
+    }
+
+    #[inline]
+    fn row_offset(&self, i: usize) -> usize {
+        #[cfg(target_arch = "x86_64")]
+        if self.bmi2 {
+            // SAFETY: the CPU supports BMI2
+            return unsafe { bmi2::pdep(i as u64, self.row_mask) };
+        }
+        let shared = self.shared_bits();
+        let i = i as u64;
+        (spread(i & ((1 << shared) - 1)) << 1 | i >> shared << (2 * shared)) as usize
+    }
+
+    #[inline]
+    fn col_offset(&self, j: usize) -> usize {
+        #[cfg(target_arch = "x86_64")]
+        if self.bmi2 {
+            // SAFETY: the CPU supports BMI2
+            return unsafe { bmi2::pdep(j as u64, self.col_mask) };
+        }
+        let shared = self.shared_bits();
+        let j = j as u64;
+        (spread(j & ((1 << shared) - 1)) | j >> shared << (2 * shared)) as usize
+    }
+
+    fn for_each_position(&self, mut f: impl FnMut(usize, usize)) {
+        for index in 0..self.size() {
+            let (i, j) = self.position(index);
+            f(i, j);
+        }
+    }
+
+    fn with_shape(&self, rows: usize, cols: usize) -> Self {
+        Morton {
+            bmi2: self.bmi2,
+            ..Morton::new(rows, cols)
+        }
+    }
+
+    fn run(&self) -> usize {
+        // bit 0 belongs to the columns unless there is only one
+        if self.col_bits > 0 { 2 } else { 1 }
+    }
+}
+
+/// Row-major tiles of side 2^tile_bits in a row-major grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tiled {
+    rows: usize,
+    cols: usize,
+    tile_bits: u32,
+    tiles_per_row: usize,
+}
+
+impl Tiled {
+    /// `tile` is the side of the tiles, a power of two
+    pub fn new(rows: usize, cols: usize, tile: usize) -> Self {
+        assert!(
+            tile.is_power_of_two(),
+            "the tile side must be a power of two"
+        );
+        Tiled {
+            rows,
+            cols,
+            tile_bits: tile.trailing_zeros(),
+            tiles_per_row: cols.div_ceil(tile),
+        }
+    }
+
+    pub fn tile(&self) -> usize {
+        1 << self.tile_bits
+    }
+
+    fn tile_rows(&self) -> usize {
+        self.rows.div_ceil(self.tile())
+    }
+}
+
+impl ElementOrder for Tiled {
+    fn rows(&self) -> usize {
+        self.rows
+    }
+
+    fn cols(&self) -> usize {
+        self.cols
+    }
+
+    fn size(&self) -> usize {
+        (self.tile_rows() * self.tiles_per_row) << (2 * self.tile_bits)
+    }
+
+    #[inline]
+    fn row_offset(&self, i: usize) -> usize {
+        let s = self.tile_bits;
+        (((i >> s) * self.tiles_per_row) << (2 * s)) + ((i & ((1 << s) - 1)) << s)
+    }
+
+    #[inline]
+    fn col_offset(&self, j: usize) -> usize {
+        let s = self.tile_bits;
+        (j >> s << (2 * s)) + (j & ((1 << s) - 1))
+    }
+
+    fn for_each_position(&self, mut f: impl FnMut(usize, usize)) {
+        let t = self.tile();
+        for ti in 0..self.tile_rows() {
+            for tj in 0..self.tiles_per_row {
+                for r in 0..t {
+                    for c in 0..t {
+                        f(ti * t + r, tj * t + c);
+                    }
+                }
+            }
+        }
+    }
+
+    fn with_shape(&self, rows: usize, cols: usize) -> Self {
+        Tiled::new(rows, cols, self.tile())
+    }
+
+    fn run(&self) -> usize {
+        self.tile()
+    }
+}
+
+/// A rows x cols matrix stored in the order `L`, the padding holds `T::default()`.
+#[derive(Clone)]
+pub struct LayoutMatrix<T, L> {
+    data: Vec<T>,
+    order: L,
+}
+
+pub type MortonMatrix<T> = LayoutMatrix<T, Morton>;
+
+pub type TiledMatrix<T> = LayoutMatrix<T, Tiled>;
+
+impl<T, L: ElementOrder> LayoutMatrix<T, L> {
+    pub fn order(&self) -> &L {
+        &self.order
+    }
+
+    pub fn rows(&self) -> usize {
+        self.order.rows()
+    }
+
+    pub fn cols(&self) -> usize {
+        self.order.cols()
+    }
+
+    /// the buffer in the order of the layout, with the padding
+    pub fn as_slice(&self) -> &[T] {
+        &self.data
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> &T {
+        assert!(i < self.rows() && j < self.cols(), "index out of bounds");
+        &self.data[self.order.index(i, j)]
+    }
+
+    pub fn get_mut(&mut self, i: usize, j: usize) -> &mut T {
+        assert!(i < self.rows() && j < self.cols(), "index out of bounds");
+        &mut self.data[self.order.index(i, j)]
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, value: T) {
+        *self.get_mut(i, j) = value;
+    }
+}
+
+impl<T: Default + Clone, L: ElementOrder> LayoutMatrix<T, L> {
+    pub fn new(order: L) -> Self {
+        LayoutMatrix {
+            data: vec![T::default(); order.size()],
+            order,
+        }
+    }
+
+    /// copy a matrix of the shape of `order`, in the order of the buffer
+    pub fn from_matrix<M: MatrixRef<T> + ?Sized>(source: &M, order: L) -> Self {
+        let (rows, cols) = (order.rows(), order.cols());
+        assert_eq!((source.rows(), source.cols()), (rows, cols));
+        let mut data = Vec::with_capacity(order.size());
+        order.for_each_position(|i, j| {
+            data.push(if i < rows && j < cols {
+                source.entry(i, j).clone()
+            } else {
+                T::default()
+            });
+        });
+        LayoutMatrix { data, order }
+    }
+
+    /// the entries in row-major order
+    pub fn to_matrix(&self) -> Matrix<T> {
+        let (rows, cols) = (self.rows(), self.cols());
+        let mut result = Matrix::new(rows, cols);
+        let mut slots = self.data.iter();
+        self.order.for_each_position(|i, j| {
+            let value = slots.next().unwrap();
+            if i < rows && j < cols {
+                result.set(i, j, value.clone());
+            }
+        });
+        result
+    }
+}
+
+impl<T: Default + Clone, S: Storage<T>> Matrix<T, S> {
+    pub fn to_morton(&self) -> MortonMatrix<T> {
+        LayoutMatrix::from_matrix(self, Morton::new(self.rows(), self.cols()))
+    }
+
+    /// `tile` is the side of the tiles, a power of two
+    pub fn to_tiled(&self, tile: usize) -> TiledMatrix<T> {
+        LayoutMatrix::from_matrix(self, Tiled::new(self.rows(), self.cols(), tile))
+    }
+}
+
+/// transpose a square Z-ordered block in place: the quarters are transposed and the
+/// two off the diagonal swapped, which are contiguous
+fn transpose_z_block<T>(block: &mut [T]) {
+    if block.len() <= 4 {
+        if block.len() == 4 {
+            block.swap(1, 2);
+        }
+        return;
+    }
+    let quarter = block.len() / 4;
+    let (q0, rest) = block.split_at_mut(quarter);
+    let (q1, rest) = rest.split_at_mut(quarter);
+    let (q2, q3) = rest.split_at_mut(quarter);
+    for q in [&mut *q0, &mut *q1, &mut *q2, &mut *q3] {
+        transpose_z_block(q);
+    }
+    q1.swap_with_slice(q2);
+}
+
+impl<T: Clone> MortonMatrix<T> {
+    /// transpose without a second buffer, a rows x cols matrix becomes cols x rows
+    ///
+    /// The square Z-ordered blocks are transposed one by one and stay where they are,
+    /// since the transpose of a column of blocks is a row of them in the same order.
+    pub fn transpose_in_place(&mut self) {
+        let block = 1 << (2 * self.order.shared_bits());
+        for chunk in self.data.chunks_mut(block) {
+            transpose_z_block(chunk);
+        }
+        self.order = self.order.with_shape(self.cols(), self.rows());
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut result = self.clone();
+        result.transpose_in_place();
+        result
+    }
+}
+
+impl<T: Default + Clone> TiledMatrix<T> {
+    /// tile (I, J) is transposed into tile (J, I), one pair of tiles at a time
+    pub fn transpose(&self) -> Self {
+        let mut result = LayoutMatrix::new(self.order.with_shape(self.cols(), self.rows()));
+        let (t, area) = (self.order.tile(), 1 << (2 * self.order.tile_bits));
+        for ti in 0..self.order.tile_rows() {
+            for tj in 0..self.order.tiles_per_row {
+                let source = &self.data[(ti * self.order.tiles_per_row + tj) * area..][..area];
+                let target =
+                    &mut result.data[(tj * result.order.tiles_per_row + ti) * area..][..area];
+                for r in 0..t {
+                    for c in 0..t {
+                        target[c * t + r] = source[r * t + c].clone();
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+/// blocks with all sides at most this are multiplied with loops
+const LEAF: usize = 32;
+
+/// the offsets of the rows and columns of the three matrices of c += a * b
+struct Product<'a, T> {
+    a: &'a [T],
+    b: &'a [T],
+    a_rows: Vec<usize>,
+    a_cols: Vec<usize>,
+    b_rows: Vec<usize>,
+    b_cols: Vec<usize>,
+    c_rows: Vec<usize>,
+    c_cols: Vec<usize>,
+    run: usize,
+    scratch: Vec<T>,
+    /// the row of c that a packed leaf is adding to
+    row: Vec<T>,
+}
+
+fn offsets<L: ElementOrder>(order: &L) -> (Vec<usize>, Vec<usize>) {
+    (
+        (0..order.rows()).map(|i| order.row_offset(i)).collect(),
+        (0..order.cols()).map(|j| order.col_offset(j)).collect(),
+    )
+}
+
+impl<T: Mul<Output = T> + Add<Output = T> + Clone> Product<'_, T> {
+    fn leaf(
+        &mut self,
+        c: &mut [T],
+        (i0, i1): (usize, usize),
+        (k0, k1): (usize, usize),
+        (j0, j1): (usize, usize),
+    ) {
+        let run = self.run;
+        if run < 8 {
+            self.packed_leaf(c, (i0, i1), (k0, k1), (j0, j1));
+            return;
+        }
+        for i in i0..i1 {
+            let (a_row, c_row) = (self.a_rows[i], self.c_rows[i]);
+            for k in k0..k1 {
+                let x = self.a[a_row + self.a_cols[k]].clone();
+                let b_row = self.b_rows[k];
+                // the columns of b and c in the same run of the layout are contiguous
+                let mut j = j0;
+                while j < j1 {
+                    let len = (run - (j & (run - 1))).min(j1 - j);
+                    let b_run = &self.b[b_row + self.b_cols[j]..][..len];
+                    let c_run = &mut c[c_row + self.c_cols[j]..][..len];
+                    for (z, y) in c_run.iter_mut().zip(b_run) {
+                        *z = z.clone() + x.clone() * y.clone();
+                    }
+                    j += len;
+                }
+            }
+        }
+    }
+
+    /// the leaf for short runs: the block of b and one row of c at a time are copied
+    /// into `scratch` row by row, so the innermost loop runs over slices
+    fn packed_leaf(
+        &mut self,
+        c: &mut [T],
+        (i0, i1): (usize, usize),
+        (k0, k1): (usize, usize),
+        (j0, j1): (usize, usize),
+    ) {
+        let width = j1 - j0;
+        self.scratch.clear();
+        for k in k0..k1 {
+            let b_row = self.b_rows[k];
+            let b_cols = &self.b_cols[j0..j1];
+            self.scratch
+                .extend(b_cols.iter().map(|&col| self.b[b_row + col].clone()));
+        }
+        let c_cols = &self.c_cols[j0..j1];
+        for i in i0..i1 {
+            let (a_row, c_row) = (self.a_rows[i], self.c_rows[i]);
+            self.row.clear();
+            self.row
+                .extend(c_cols.iter().map(|&col| c[c_row + col].clone()));
+            for k in k0..k1 {
+                let x = self.a[a_row + self.a_cols[k]].clone();
+                let b_row = &self.scratch[(k - k0) * width..][..width];
+                for (z, y) in self.row.iter_mut().zip(b_row) {
+                    *z = z.clone() + x.clone() * y.clone();
+                }
+            }
+            for (&col, z) in c_cols.iter().zip(self.row.drain(..)) {
+                c[c_row + col] = z;
+            }
+        }
+    }
+
+    /// halve the longest side at a power of two, so that the blocks stay aligned
+    fn rec(
+        &mut self,
+        c: &mut [T],
+        rows: (usize, usize),
+        inner: (usize, usize),
+        cols: (usize, usize),
+    ) {
+        let side = |(start, end): (usize, usize)| end - start;
+        let longest = side(rows).max(side(inner)).max(side(cols));
+        if longest <= LEAF {
+            self.leaf(c, rows, inner, cols);
+            return;
+        }
+        let halves = |(start, end): (usize, usize)| {
+            let mid = start + (end - start).next_power_of_two() / 2;
+            [(start, mid), (mid, end)]
+        };
+        if side(inner) == longest {
+            for inner in halves(inner) {
+                self.rec(c, rows, inner, cols);
+            }
+        } else if side(rows) == longest {
+            for rows in halves(rows) {
+                self.rec(c, rows, inner, cols);
+            }
+        } else {
+            for cols in halves(cols) {
+                self.rec(c, rows, inner, cols);
+            }
+        }
+    }
+}
+
+impl<T, L> LayoutMatrix<T, L>
+where
+    T: Mul<Output = T> + Add<Output = T> + Default + Clone,
+    L: ElementOrder,
+{
+    /// self * other in the same kind of order, recursing on aligned blocks, which are
+    /// contiguous in Z-order and made of whole tiles in a tiled order
+    pub fn mul(&self, other: &Self) -> Self {
+        assert_eq!(self.cols(), other.rows());
+        let mut result = LayoutMatrix::new(self.order.with_shape(self.rows(), other.cols()));
+        let (a_rows, a_cols) = offsets(&self.order);
+        let (b_rows, b_cols) = offsets(&other.order);
+        let (c_rows, c_cols) = offsets(&result.order);
+        let mut product = Product {
+            a: &self.data,
+            b: &other.data,
+            a_rows,
+            a_cols,
+            b_rows,
+            b_cols,
+            c_rows,
+            c_cols,
+            run: self.order.run().min(other.order.run()),
+            scratch: Vec::with_capacity(LEAF * LEAF),
+            row: Vec::with_capacity(LEAF),
+        };
+        let (n, m, p) = (self.rows(), self.cols(), other.cols());
+        product.rec(&mut result.data, (0, n), (0, m), (0, p));
+        result
+    }
+}
+
+impl<T, L: ElementOrder> MatrixRef<T> for LayoutMatrix<T, L> {
+    #[inline]
+    fn rows(&self) -> usize {
+        self.order.rows()
+    }
+
+    #[inline]
+    fn cols(&self) -> usize {
+        self.order.cols()
+    }
+
+    #[inline]
+    fn entry(&self, i: usize, j: usize) -> &T {
+        self.get(i, j)
+    }
+}
+
+impl<T, L: ElementOrder> MatrixMut<T> for LayoutMatrix<T, L> {
+    #[inline]
+    fn entry_mut(&mut self, i: usize, j: usize) -> &mut T {
+        self.get_mut(i, j)
+    }
+}
+
+impl<T: fmt::Debug, L: ElementOrder> fmt::Debug for LayoutMatrix<T, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LayoutMatrix")
+            .field("order", &self.order)
+            .field("data", &self.data)
+            .finish()
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod bmi2 {
+    use std::arch::x86_64::{_pdep_u64, _pext_u64};
+
+    /// # Safety
+    ///
+    /// The CPU must support BMI2.
+    #[target_feature(enable = "bmi2")]
+    pub(super) unsafe fn pdep(x: u64, mask: u64) -> usize {
+        _pdep_u64(x, mask) as usize
+    }
+
+    /// # Safety
+    ///
+    /// The CPU must support BMI2.
+    #[target_feature(enable = "bmi2")]
+    pub(super) unsafe fn pext(x: u64, mask: u64) -> usize {
+        _pext_u64(x, mask) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iota(n: usize, m: usize) -> Matrix<i64> {
+        Matrix::from_vec((0..(n * m) as i64).collect(), n, m)
+    }
+
+    #[test]
+    fn test_layout_indexing() {
+        let morton = Morton::new(4, 4);
+        let z: Vec<usize> = (0..4).map(|j| morton.index(1, j)).collect();
+        assert_eq!(z, [2, 3, 6, 7]);
+        assert_eq!(morton.index(3, 3), 15);
+        let tiled = Tiled::new(5, 6, 4);
+        assert_eq!(tiled.size(), 2 * 2 * 16);
+        assert_eq!(tiled.index(1, 5), 16 + 4 + 1);
+        assert_eq!(tiled.index(4, 2), 32 + 2);
+
+        for (n, m) in [(1, 1), (3, 5), (8, 8), (16, 3), (2, 33), (100, 7)] {
+            let order = Morton::new(n, m);
+            for index in 0..order.size() {
+                let (i, j) = order.position(index);
+                assert_eq!(order.index(i, j), index);
+                assert_eq!(order.portable().index(i, j), index);
+                assert_eq!(order.portable().position(index), (i, j));
+            }
+
+            let matrix = iota(n, m);
+            let morton = matrix.to_morton();
+            let tiled = matrix.to_tiled(4);
+            assert_eq!(morton.to_matrix(), matrix);
+            assert_eq!(tiled.to_matrix(), matrix);
+            assert_eq!(*morton.get(n - 1, m / 2), *matrix.get(n - 1, m / 2));
+            assert_eq!(*tiled.get(n / 2, m - 1), *matrix.get(n / 2, m - 1));
+            assert_eq!(morton.transpose().to_matrix(), matrix.transpose());
+            assert_eq!(tiled.transpose().to_matrix(), matrix.transpose());
+        }
+    }
+
+    #[test]
+    fn test_layout_mul() {
+        for (n, m, p) in [
+            (1, 1, 1),
+            (5, 7, 3),
+            (33, 40, 17),
+            (64, 64, 64),
+            (70, 129, 50),
+        ] {
+            let a = iota(n, m).map(|x| x % 7 - 3);
+            let b = iota(m, p).map(|x| x % 5 - 2);
+            let expected = a.simple_mul(&b);
+            assert_eq!(a.to_morton().mul(&b.to_morton()).to_matrix(), expected);
+            for tile in [1, 4, 16] {
+                assert_eq!(
+                    a.to_tiled(tile).mul(&b.to_tiled(tile)).to_matrix(),
+                    expected
+                );
+            }
+            // the layouts work with the kernels written against MatrixRef
+            let mut result = Matrix::new(n, p);
+            a.mul_into(&b.to_tiled(8), &mut result);
+            assert_eq!(result, expected);
+        }
+    }
+}
@@ -0,0 +1,710 @@
+//! Borrowed strided views of matrices.
+//!
+//! A view is a rows x cols block of a slice: the entry (i, j) is at
+//! `offset + i * stride + j` in row-major layout and at `offset + j * stride + i` in
+//! column-major layout. Views of submatrices and transposes are made without copying,
+//! and mutable views split into disjoint parts like `split_at_mut`.
+
+use std::marker::PhantomData;
+use std::ops::Range;
+
+use super::cache_oblivious::{
+    Matrix, matrix_transpose_cache_oblivious_fast_rec, mul_into_rec, transpose_into_rec,
+};
+use super::storage::Storage;
+
+/// Read access to the entries of a matrix, which the matrix kernels are written against.
+pub trait MatrixRef<T> {
+    fn rows(&self) -> usize;
+
+    fn cols(&self) -> usize;
+
+    fn entry(&self, i: usize, j: usize) -> &T;
+
+    /// a strided view of the entries, if they are in memory
+    fn strided(&self) -> Option<MatrixView<'_, T>> {
+        None
+    }
+}
+
+/// Write access to the entries of a matrix.
+pub trait MatrixMut<T>: MatrixRef<T> {
+    fn entry_mut(&mut self, i: usize, j: usize) -> &mut T;
+
+    /// a strided mutable view of the entries, if they are in memory
+    fn strided_mut(&mut self) -> Option<MatrixViewMut<'_, T>> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    RowMajor,
+    ColMajor,
+}
+
+impl Layout {
+    #[inline]
+    fn index(self, i: usize, j: usize, stride: usize) -> usize {
+        match self {
+            Layout::RowMajor => i * stride + j,
+            Layout::ColMajor => j * stride + i,
+        }
+    }
+
+    fn transposed(self) -> Self {
+        match self {
+            Layout::RowMajor => Layout::ColMajor,
+            Layout::ColMajor => Layout::RowMajor,
+        }
+    }
+}
+
+/// the position and shape of a view, shared by both kinds
+#[derive(Debug, Clone, Copy)]
+struct Shape {
+    offset: usize,
+    rows: usize,
+    cols: usize,
+    stride: usize,
+    layout: Layout,
+}
+
+impl Shape {
+    /// panics unless every entry is inside `len` elements and no two entries overlap
+    fn checked(
+        len: usize,
+        offset: usize,
+        rows: usize,
+        cols: usize,
+        stride: usize,
+        layout: Layout,
+    ) -> Self {
+        let shape = Shape {
+            offset,
+            rows,
+            cols,
+            stride,
+            layout,
+        };
+        if rows == 0 || cols == 0 {
+            assert!(offset <= len, "view out of bounds");
+            return shape;
+        }
+        let (outer, inner) = match layout {
+            Layout::RowMajor => (rows, cols),
+            Layout::ColMajor => (cols, rows),
+        };
+        assert!(
+            outer == 1 || inner <= stride,
+            "stride is shorter than a row"
+        );
+        // the last entry, every other index of the view is smaller and cannot overflow
+        let last = (outer - 1)
+            .checked_mul(stride)
+            .and_then(|x| x.checked_add(inner - 1))
+            .and_then(|x| x.checked_add(offset));
+        assert!(last.is_some_and(|last| last < len), "view out of bounds");
+        shape
+    }
+
+    #[inline]
+    fn index(&self, i: usize, j: usize) -> usize {
+        assert!(i < self.rows && j < self.cols, "index out of bounds");
+        // at most the last entry, which `checked` bounded
+        self.offset + self.layout.index(i, j, self.stride)
+    }
+
+    fn submatrix(&self, rows: Range<usize>, cols: Range<usize>) -> Self {
+        assert!(
+            rows.start <= rows.end && rows.end <= self.rows,
+            "rows out of bounds"
+        );
+        assert!(
+            cols.start <= cols.end && cols.end <= self.cols,
+            "cols out of bounds"
+        );
+        Shape {
+            offset: self.offset + self.layout.index(rows.start, cols.start, self.stride),
+            rows: rows.len(),
+            cols: cols.len(),
+            ..*self
+        }
+    }
+
+    fn split_at_row(&self, i: usize) -> (Self, Self) {
+        (
+            self.submatrix(0..i, 0..self.cols),
+            self.submatrix(i..self.rows, 0..self.cols),
+        )
+    }
+
+    fn split_at_col(&self, j: usize) -> (Self, Self) {
+        (
+            self.submatrix(0..self.rows, 0..j),
+            self.submatrix(0..self.rows, j..self.cols),
+        )
+    }
+
+    /// the distances between two rows and between two columns
+    fn steps(&self) -> (usize, usize) {
+        match self.layout {
+            Layout::RowMajor => (self.stride, 1),
+            Layout::ColMajor => (1, self.stride),
+        }
+    }
+
+    fn transposed(&self) -> Self {
+        Shape {
+            rows: self.cols,
+            cols: self.rows,
+            layout: self.layout.transposed(),
+            ..*self
+        }
+    }
+}
+
+/// A borrowed view of a matrix.
+#[derive(Debug)]
+pub struct MatrixView<'a, T> {
+    ptr: *const T,
+    shape: Shape,
+    _data: PhantomData<&'a [T]>,
+}
+
+impl<T> Clone for MatrixView<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for MatrixView<'_, T> {}
+
+// SAFETY: a view is a shared borrow of the entries
+unsafe impl<T: Sync> Send for MatrixView<'_, T> {}
+unsafe impl<T: Sync> Sync for MatrixView<'_, T> {}
+
+/// A borrowed mutable view of a matrix, views split from it never overlap.
+#[derive(Debug)]
+pub struct MatrixViewMut<'a, T> {
+    ptr: *mut T,
+    /// length of the borrowed slice
+    len: usize,
+    shape: Shape,
+    _data: PhantomData<&'a mut [T]>,
+}
+
+// SAFETY: a mutable view is a unique borrow of its entries
+unsafe impl<T: Send> Send for MatrixViewMut<'_, T> {}
+unsafe impl<T: Sync> Sync for MatrixViewMut<'_, T> {}
+
+macro_rules! impl_view_shape {
+    ($view:ident) => {
+        impl<'a, T> $view<'a, T> {
+            pub fn rows(&self) -> usize {
+                self.shape.rows
+            }
+
+            pub fn cols(&self) -> usize {
+                self.shape.cols
+            }
+
+            /// position of the entry (0, 0) in the slice
+            pub fn offset(&self) -> usize {
+                self.shape.offset
+            }
+
+            /// distance between the starts of two rows, or columns in column-major layout
+            pub fn stride(&self) -> usize {
+                self.shape.stride
+            }
+
+            pub fn layout(&self) -> Layout {
+                self.shape.layout
+            }
+
+            pub fn get(&self, i: usize, j: usize) -> &T {
+                // SAFETY: the index is inside the view, which is inside the slice
+                unsafe { &*self.ptr.add(self.shape.index(i, j)) }
+            }
+
+            /// the entries of row i as a slice, if the view is row-major
+            pub fn row_slice(&self, i: usize) -> Option<&[T]> {
+                if self.shape.layout != Layout::RowMajor {
+                    return None;
+                }
+                assert!(i < self.shape.rows, "index out of bounds");
+                let start = self.shape.offset + i * self.shape.stride;
+                // SAFETY: the row is inside the view, which is inside the slice
+                Some(unsafe { std::slice::from_raw_parts(self.ptr.add(start), self.shape.cols) })
+            }
+
+            /// the view of the transpose, swapping the layout
+            pub fn t(self) -> Self {
+                $view {
+                    shape: self.shape.transposed(),
+                    ..self
+                }
+            }
+
+            /// the rows in `rows` and the columns in `cols`
+            pub fn submatrix(self, rows: Range<usize>, cols: Range<usize>) -> Self {
+                $view {
+                    shape: self.shape.submatrix(rows, cols),
+                    ..self
+                }
+            }
+
+            /// the rows before `i` and the rows from `i` on
+            pub fn split_at_row(self, i: usize) -> (Self, Self) {
+                let (top, bottom) = self.shape.split_at_row(i);
+                let top = $view { shape: top, ..self };
+                let bottom = $view {
+                    shape: bottom,
+                    ..self
+                };
+                (top, bottom)
+            }
+
+            /// the columns before `j` and the columns from `j` on
+            pub fn split_at_col(self, j: usize) -> (Self, Self) {
+                let (left, right) = self.shape.split_at_col(j);
+                let left = $view {
+                    shape: left,
+                    ..self
+                };
+                let right = $view {
+                    shape: right,
+                    ..self
+                };
+                (left, right)
+            }
+        }
+
+        impl<T> MatrixRef<T> for $view<'_, T> {
+            #[inline]
+            fn rows(&self) -> usize {
+                self.shape.rows
+            }
+
+            #[inline]
+            fn cols(&self) -> usize {
+                self.shape.cols
+            }
+
+            #[inline]
+            fn entry(&self, i: usize, j: usize) -> &T {
+                self.get(i, j)
+            }
+
+            fn strided(&self) -> Option<MatrixView<'_, T>> {
+                Some(MatrixView {
+                    ptr: self.ptr,
+                    shape: self.shape,
+                    _data: PhantomData,
+                })
+            }
+        }
+
+        impl<T: Default + Clone> $view<'_, T> {
+            pub fn to_matrix(&self) -> Matrix<T> {
+                let mut matrix = Matrix::new(self.rows(), self.cols());
+                for i in 0..self.rows() {
+                    for j in 0..self.cols() {
+                        matrix.set(i, j, self.get(i, j).clone());
+                    }
+                }
+                matrix
+            }
+
+            pub fn transpose(&self) -> Matrix<T> {
+                let mut target = Matrix::new(self.cols(), self.rows());
+                self.transpose_into(&mut target);
+                target
+            }
+
+            /// write the transpose into `target`, which must be cols x rows
+            pub fn transpose_into<M: MatrixMut<T> + ?Sized>(&self, target: &mut M) {
+                transpose_into_rec(self, target);
+            }
+        }
+
+        impl<T: std::ops::Mul<Output = T> + std::ops::Add<Output = T> + Default + Clone + 'static>
+            $view<'_, T>
+        {
+            pub fn mul<B: MatrixRef<T> + ?Sized>(&self, other: &B) -> Matrix<T> {
+                let mut result = Matrix::new(self.rows(), other.cols());
+                self.mul_into(other, &mut result);
+                result
+            }
+
+            /// result += self * other, result must be rows x other.cols
+            pub fn mul_into<B: MatrixRef<T> + ?Sized, C: MatrixMut<T> + ?Sized>(
+                &self,
+                other: &B,
+                result: &mut C,
+            ) {
+                mul_into_rec(self, other, result);
+            }
+        }
+    };
+}
+
+impl_view_shape!(MatrixView);
+impl_view_shape!(MatrixViewMut);
+
+impl<'a, T> MatrixView<'a, T> {
+    /// the row-major rows x cols matrix in `data`
+    pub fn new(data: &'a [T], rows: usize, cols: usize) -> Self {
+        Self::from_parts(data, 0, rows, cols, cols, Layout::RowMajor)
+    }
+
+    /// panics unless all entries are inside `data` and a row (column) fits in `stride`
+    pub fn from_parts(
+        data: &'a [T],
+        offset: usize,
+        rows: usize,
+        cols: usize,
+        stride: usize,
+        layout: Layout,
+    ) -> Self {
+        MatrixView {
+            ptr: data.as_ptr(),
+            shape: Shape::checked(data.len(), offset, rows, cols, stride, layout),
+            _data: PhantomData,
+        }
+    }
+
+    /// a pointer to the entry (0, 0) and the distances between rows and columns, only
+    /// valid to read if the view is not empty
+    pub(crate) fn raw_parts(&self) -> (*const T, usize, usize) {
+        let (row_step, col_step) = self.shape.steps();
+        // the offset of an empty view can be past the end of the slice, so the pointer
+        // is only valid when the view has entries
+        (self.ptr.wrapping_add(self.shape.offset), row_step, col_step)
+    }
+
+    /// the same view with the element type `U`
+    ///
+    /// # Safety
+    ///
+    /// `U` must be `T`.
+    pub(crate) unsafe fn cast<U>(self) -> MatrixView<'a, U> {
+        MatrixView {
+            ptr: self.ptr.cast(),
+            shape: self.shape,
+            _data: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> MatrixViewMut<'a, T> {
+    /// the row-major rows x cols matrix in `data`
+    pub fn new(data: &'a mut [T], rows: usize, cols: usize) -> Self {
+        Self::from_parts(data, 0, rows, cols, cols, Layout::RowMajor)
+    }
+
+    /// panics unless all entries are inside `data` and a row (column) fits in `stride`
+    pub fn from_parts(
+        data: &'a mut [T],
+        offset: usize,
+        rows: usize,
+        cols: usize,
+        stride: usize,
+        layout: Layout,
+    ) -> Self {
+        MatrixViewMut {
+            ptr: data.as_mut_ptr(),
+            len: data.len(),
+            shape: Shape::checked(data.len(), offset, rows, cols, stride, layout),
+            _data: PhantomData,
+        }
+    }
+
+    pub fn get_mut(&mut self, i: usize, j: usize) -> &mut T {
+        // SAFETY: the index is inside the view, and the view borrows its entries uniquely
+        unsafe { &mut *self.ptr.add(self.shape.index(i, j)) }
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, value: T) {
+        *self.get_mut(i, j) = value;
+    }
+
+    /// as [`MatrixViewMut::row_slice`], for writing
+    pub fn row_slice_mut(&mut self, i: usize) -> Option<&mut [T]> {
+        if self.shape.layout != Layout::RowMajor {
+            return None;
+        }
+        assert!(i < self.shape.rows, "index out of bounds");
+        let start = self.shape.offset + i * self.shape.stride;
+        // SAFETY: the row is inside the view, which borrows its entries uniquely
+        Some(unsafe { std::slice::from_raw_parts_mut(self.ptr.add(start), self.shape.cols) })
+    }
+
+    /// as [`MatrixView::raw_parts`], for writing
+    pub(crate) fn raw_parts_mut(&mut self) -> (*mut T, usize, usize) {
+        let (row_step, col_step) = self.shape.steps();
+        // the offset of an empty view can be past the end of the slice, so the pointer
+        // is only valid when the view has entries
+        (self.ptr.wrapping_add(self.shape.offset), row_step, col_step)
+    }
+
+    /// the same view with the element type `U`
+    ///
+    /// # Safety
+    ///
+    /// `U` must be `T`.
+    pub(crate) unsafe fn cast<U>(self) -> MatrixViewMut<'a, U> {
+        MatrixViewMut {
+            ptr: self.ptr.cast(),
+            len: self.len,
+            shape: self.shape,
+            _data: PhantomData,
+        }
+    }
+
+    /// a shorter-lived view of the same entries
+    pub fn reborrow(&mut self) -> MatrixViewMut<'_, T> {
+        MatrixViewMut {
+            ptr: self.ptr,
+            len: self.len,
+            shape: self.shape,
+            _data: PhantomData,
+        }
+    }
+
+    pub fn as_view(&self) -> MatrixView<'_, T> {
+        MatrixView {
+            ptr: self.ptr,
+            shape: self.shape,
+            _data: PhantomData,
+        }
+    }
+
+    /// transpose a square view in place with the cache-oblivious slice transpose
+    pub fn transpose_in_place(&mut self) {
+        assert_eq!(self.rows(), self.cols());
+        // swapping (i, j) with (j, i) is the same in both layouts
+        let mut entries = ViewEntries {
+            ptr: self.ptr,
+            len: self.len,
+            _data: PhantomData,
+        };
+        let (offset, n, stride) = (self.offset(), self.rows(), self.stride());
+        matrix_transpose_cache_oblivious_fast_rec(&mut entries, offset, 0, 0, n, n, stride);
+    }
+}
+
+impl<T> MatrixMut<T> for MatrixViewMut<'_, T> {
+    #[inline]
+    fn entry_mut(&mut self, i: usize, j: usize) -> &mut T {
+        self.get_mut(i, j)
+    }
+
+    fn strided_mut(&mut self) -> Option<MatrixViewMut<'_, T>> {
+        Some(self.reborrow())
+    }
+}
+
+/// The slice under a mutable view, only the entries of the view are touched.
+struct ViewEntries<'v, T> {
+    ptr: *mut T,
+    len: usize,
+    _data: PhantomData<&'v mut [T]>,
+}
+
+impl<T> Storage<T> for ViewEntries<'_, T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn element(&self, i: usize) -> &T {
+        assert!(i < self.len);
+        // SAFETY: in bounds, and only the entries of the borrowed view are passed in
+        unsafe { &*self.ptr.add(i) }
+    }
+
+    #[inline]
+    fn element_mut(&mut self, i: usize) -> &mut T {
+        assert!(i < self.len);
+        // SAFETY: as in element
+        unsafe { &mut *self.ptr.add(i) }
+    }
+
+    #[inline]
+    fn swap(&mut self, i: usize, j: usize) {
+        assert!(i < self.len && j < self.len);
+        // SAFETY: as in element, ptr::swap allows i == j
+        unsafe { std::ptr::swap(self.ptr.add(i), self.ptr.add(j)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iota(n: usize, m: usize) -> Matrix<i64> {
+        Matrix::from_vec((0..(n * m) as i64).collect(), n, m)
+    }
+
+    #[test]
+    fn test_view_indexing() {
+        let data: Vec<i32> = (0..24).collect();
+        let view = MatrixView::new(&data, 4, 6);
+        assert_eq!(*view.get(2, 3), 15);
+        let sub = view.submatrix(1..3, 2..5);
+        assert_eq!(
+            (sub.rows(), sub.cols(), sub.offset(), sub.stride()),
+            (2, 3, 8, 6)
+        );
+        assert_eq!(*sub.get(1, 2), 16);
+        assert_eq!(sub.row_slice(1), Some(&data[14..17]));
+        let t = sub.t();
+        assert_eq!((t.rows(), t.cols(), t.layout()), (3, 2, Layout::ColMajor));
+        assert_eq!(*t.get(2, 1), 16);
+
+        let (top, bottom) = view.split_at_row(1);
+        assert_eq!((top.rows(), bottom.rows(), *bottom.get(0, 0)), (1, 3, 6));
+        let (left, right) = view.split_at_col(4);
+        assert_eq!((left.cols(), right.cols(), *right.get(3, 1)), (4, 2, 23));
+
+        // a column-major 3 x 4 matrix with leading dimension 5
+        let col_major = MatrixView::from_parts(&data, 2, 3, 4, 5, Layout::ColMajor);
+        assert_eq!(*col_major.get(2, 3), 2 + 3 * 5 + 2);
+        assert_eq!(col_major.row_slice(0), None);
+        assert_eq!(
+            col_major.to_matrix().into_storage(),
+            col_major.t().transpose().into_storage()
+        );
+        assert_eq!(*col_major.to_matrix().get(1, 2), 13);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_view_out_of_bounds() {
+        let data = [0; 10];
+        MatrixView::from_parts(&data, 2, 3, 3, 3, Layout::RowMajor);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_view_huge_stride() {
+        // offset + stride + 1 wraps around to 1
+        let mut data = [10u64, 20];
+        let view = MatrixViewMut::from_parts(&mut data, 1, 2, 2, usize::MAX, Layout::RowMajor);
+        let (mut top, mut bottom) = view.split_at_row(1);
+        assert_ne!(
+            top.get_mut(0, 0) as *mut u64,
+            bottom.get_mut(0, 1) as *mut u64
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "out of bounds")]
+    fn test_view_huge_offset() {
+        let data = [0; 10];
+        MatrixView::from_parts(&data, usize::MAX, 1, 2, 2, Layout::RowMajor);
+    }
+
+    #[test]
+    #[should_panic(expected = "stride")]
+    fn test_view_overlapping_rows() {
+        let mut data = [0; 10];
+        MatrixViewMut::from_parts(&mut data, 0, 2, 4, 3, Layout::RowMajor);
+    }
+
+    #[test]
+    fn test_split_mut() {
+        let mut matrix = Matrix::<i32>::new(6, 8);
+        let view = matrix.view_mut();
+        let (top, bottom) = view.split_at_row(2);
+        let (mut top_left, mut top_right) = top.split_at_col(3);
+        let (mut bottom_left, mut bottom_right) = bottom.split_at_col(5);
+        top_left.set(1, 2, 1);
+        top_right.set(0, 4, 2);
+        bottom_left.set(3, 4, 3);
+        bottom_right.set(0, 0, 4);
+        assert_eq!(*matrix.get(1, 2), 1);
+        assert_eq!(*matrix.get(0, 7), 2);
+        assert_eq!(*matrix.get(5, 4), 3);
+        assert_eq!(*matrix.get(2, 5), 4);
+    }
+
+    #[test]
+    fn test_view_kernels() {
+        let (a, b) = (iota(40, 50), iota(60, 30));
+        // blocks of larger matrices without copying
+        let a_block = a.view().submatrix(5..35, 10..47);
+        let b_block = b.view().submatrix(20..57, 3..23);
+        let expected = a_block.to_matrix().simple_mul(&b_block.to_matrix());
+        assert_eq!(
+            a_block.mul(&b_block).into_storage(),
+            expected.into_storage().clone()
+        );
+
+        let mut c = Matrix::new(50, 50);
+        let mut c_block = c.view_mut().submatrix(7..37, 11..31);
+        a_block.mul_into(&b_block, &mut c_block);
+        let product = a_block.mul(&b_block);
+        for i in 0..50 {
+            for j in 0..50 {
+                let inside = (7..37).contains(&i) && (11..31).contains(&j);
+                let expected = if inside {
+                    *product.get(i - 7, j - 11)
+                } else {
+                    0
+                };
+                assert_eq!(*c.get(i, j), expected);
+            }
+        }
+
+        // matrices take views as operands and targets
+        let b_square = b.view().submatrix(0..50, 0..30);
+        assert_eq!(
+            a.mul(&b_square).into_storage(),
+            a.mul(&b_square.to_matrix()).into_storage()
+        );
+        let mut target = Matrix::new(60, 60);
+        a.transpose_into(&mut target.view_mut().submatrix(10..60, 20..60));
+        assert_eq!(*target.get(10 + 3, 20 + 7), *a.get(7, 3));
+        assert_eq!(*target.get(0, 0), 0);
+
+        // transposed views
+        let at = a.view().t();
+        assert_eq!(at.to_matrix().into_storage(), a.transpose().into_storage());
+        assert_eq!(
+            at.transpose().into_storage(),
+            a.view().to_matrix().into_storage()
+        );
+    }
+
+    #[test]
+    fn test_view_transpose_in_place() {
+        let mut matrix = iota(50, 70);
+        let original = iota(50, 70);
+        matrix
+            .view_mut()
+            .submatrix(3..43, 20..60)
+            .transpose_in_place();
+        for i in 0..50 {
+            for j in 0..70 {
+                let expected = if (3..43).contains(&i) && (20..60).contains(&j) {
+                    *original.get(j - 20 + 3, i - 3 + 20)
+                } else {
+                    *original.get(i, j)
+                };
+                assert_eq!(*matrix.get(i, j), expected);
+            }
+        }
+
+        let mut data: Vec<i64> = (0..36).collect();
+        let mut col_major = MatrixViewMut::from_parts(&mut data, 1, 5, 5, 7, Layout::ColMajor);
+        let before = col_major.as_view().to_matrix();
+        col_major.transpose_in_place();
+        assert_eq!(
+            col_major.as_view().to_matrix().into_storage(),
+            before.transpose().into_storage()
+        );
+    }
+}
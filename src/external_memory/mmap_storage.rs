@@ -0,0 +1,357 @@
+//! Matrices backed by a memory-mapped file.
+//!
+//! The operating system pages the file in and out on demand, so a matrix can be much
+//! larger than the RAM, and the cache-oblivious kernels keep the page faults close to
+//! the external memory bound without knowing the page size or the memory size.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use memmap2::MmapMut;
+
+use super::cache_oblivious::Matrix;
+use super::storage::Storage;
+
+/// Plain data that can be read from any bytes of its size.
+///
+/// # Safety
+///
+/// Every bit pattern must be a valid value, and the type must not contain pointers.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64
+);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// A file of `len` elements of `T` mapped into memory, writes go to the file.
+///
+/// Like [`MmapMut::map_mut`], the constructors are unsafe: the elements alias the file,
+/// so nobody else may modify or truncate it while it is mapped.
+pub struct MmapStorage<T> {
+    map: MmapMut,
+    len: usize,
+    _element: PhantomData<T>,
+}
+
+impl<T: Pod> MmapStorage<T> {
+    /// the element size, zero-sized types would make every file length ambiguous
+    const ELEMENT_SIZE: usize = {
+        assert!(size_of::<T>() > 0, "zero-sized elements cannot be mapped");
+        size_of::<T>()
+    };
+
+    /// create or truncate the file at `path` to `len` zeroed elements
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated by anyone else while it is mapped.
+    pub unsafe fn create(path: impl AsRef<Path>, len: usize) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len((len * Self::ELEMENT_SIZE) as u64)?;
+        // SAFETY: forwarded to the caller
+        unsafe { Self::map(&file, len) }
+    }
+
+    /// map an existing file, its length must be a multiple of the element size
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated by anyone else while it is mapped.
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let bytes = file.metadata()?.len() as usize;
+        if !bytes.is_multiple_of(Self::ELEMENT_SIZE) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "file length is not a multiple of the element size",
+            ));
+        }
+        // SAFETY: forwarded to the caller
+        unsafe { Self::map(&file, bytes / Self::ELEMENT_SIZE) }
+    }
+
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated by anyone else while it is mapped.
+    unsafe fn map(file: &File, len: usize) -> io::Result<Self> {
+        // SAFETY: forwarded to the caller
+        let map = unsafe { MmapMut::map_mut(file)? };
+        // mappings are page aligned, so this only fails for exotic alignments
+        assert!(map.as_ptr().cast::<T>().is_aligned());
+        Ok(MmapStorage {
+            map,
+            len,
+            _element: PhantomData,
+        })
+    }
+
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY: the mapping is aligned and holds len elements, and T: Pod
+        unsafe { std::slice::from_raw_parts(self.map.as_ptr().cast(), self.len) }
+    }
+
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        // SAFETY: as in as_slice, and the mapping is borrowed mutably
+        unsafe { std::slice::from_raw_parts_mut(self.map.as_mut_ptr().cast(), self.len) }
+    }
+
+    /// write the modified pages back to the file
+    pub fn flush(&self) -> io::Result<()> {
+        self.map.flush()
+    }
+}
+
+impl<T: Pod> AsRef<[T]> for MmapStorage<T> {
+    fn as_ref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T: Pod> AsMut<[T]> for MmapStorage<T> {
+    fn as_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T: Pod> Storage<T> for MmapStorage<T> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    fn element(&self, i: usize) -> &T {
+        &self.as_slice()[i]
+    }
+
+    #[inline]
+    fn element_mut(&mut self, i: usize) -> &mut T {
+        &mut self.as_mut_slice()[i]
+    }
+
+    #[inline]
+    fn swap(&mut self, i: usize, j: usize) {
+        self.as_mut_slice().swap(i, j);
+    }
+
+    fn contiguous(&self) -> Option<&[T]> {
+        Some(self.as_slice())
+    }
+
+    fn contiguous_mut(&mut self) -> Option<&mut [T]> {
+        Some(self.as_mut_slice())
+    }
+}
+
+/// An out-of-core row-major matrix stored in a file.
+pub type MmapMatrix<T> = Matrix<T, MmapStorage<T>>;
+
+impl<T: Pod> MmapMatrix<T> {
+    /// create or truncate the file at `path` to a zero n x m matrix
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated by anyone else while it is mapped.
+    pub unsafe fn create(path: impl AsRef<Path>, n: usize, m: usize) -> io::Result<Self> {
+        // SAFETY: forwarded to the caller
+        let storage = unsafe { MmapStorage::create(path, n * m)? };
+        Ok(Matrix::from_storage(storage, n, m))
+    }
+
+    /// map a file holding an n x m matrix in row-major order
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated by anyone else while it is mapped.
+    pub unsafe fn open(path: impl AsRef<Path>, n: usize, m: usize) -> io::Result<Self> {
+        // SAFETY: forwarded to the caller
+        let storage = unsafe { MmapStorage::open(path)? };
+        if storage.len() != n * m {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "file length does not match the matrix size",
+            ));
+        }
+        Ok(Matrix::from_storage(storage, n, m))
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.storage().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::external_memory::block_device::{
+        BlockDevice, DeviceArray, matrix_mul_bound, scan_bound,
+    };
+    use crate::external_memory::cache_oblivious::matrix_transpose_cache_oblivious_fast;
+    use std::path::PathBuf;
+
+    /// the memory the kernels run in: every access to a file goes through a
+    /// [`BlockDevice`] of this many bytes, which counts the blocks moved in and out
+    const WORKING_SET: usize = 16 << 10;
+
+    /// small enough for the tall-cache assumption M >= B^2
+    const BLOCK_SIZE: usize = 128;
+
+    /// a file in the temp dir, removed on drop
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let name = format!("hpc-rs-mmap-{}-{name}", std::process::id());
+            TempFile(std::env::temp_dir().join(name))
+        }
+
+        /// a zero n x m matrix in the file
+        fn create_matrix<T: Pod>(&self, n: usize, m: usize) -> MmapMatrix<T> {
+            // SAFETY: nothing else touches the temp files of a test
+            unsafe { MmapMatrix::create(&self.0, n, m) }.unwrap()
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// a matrix file whose accesses go through a [`BlockDevice`]
+    type WindowedMatrix<'d, T> = Matrix<T, DeviceArray<'d, T, MmapStorage<T>>>;
+
+    /// every file is at least twice the working set, so the kernels run out of core
+    fn windowed<T: Pod>(device: &BlockDevice, matrix: MmapMatrix<T>) -> WindowedMatrix<'_, T> {
+        let (n, m) = (matrix.rows(), matrix.cols());
+        assert!(n * m * size_of::<T>() >= 2 * WORKING_SET);
+        Matrix::from_storage(DeviceArray::new(device, matrix.into_storage()), n, m)
+    }
+
+    /// block transfers of `f` from a cold memory, including the final write-back
+    fn transfers(device: &BlockDevice, f: impl FnOnce()) -> u64 {
+        device.evict_all();
+        device.reset_stats();
+        f();
+        device.evict_all();
+        device.stats().total()
+    }
+
+    fn fill(matrix: &mut Matrix<u32, impl Storage<u32>>, n: usize, m: usize) {
+        for i in 0..n {
+            for j in 0..m {
+                matrix.set(i, j, ((i * 31 + j * 17) % 10) as u32);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mmap_transpose() {
+        let device = BlockDevice::new(WORKING_SET, BLOCK_SIZE);
+        let (n, m) = (128, 256);
+        let bytes = n * m * size_of::<u32>();
+        let (file, target_file) = (TempFile::new("transpose"), TempFile::new("transposed"));
+        let mut matrix = file.create_matrix::<u32>(n, m);
+        fill(&mut matrix, n, m);
+        let mut expected = Matrix::new(n, m);
+        fill(&mut expected, n, m);
+
+        let matrix = windowed(&device, matrix);
+        let mut target = windowed(&device, target_file.create_matrix(m, n));
+        let moved = transfers(&device, || matrix.transpose_into(&mut target));
+        let bound = scan_bound(bytes, BLOCK_SIZE);
+        assert!(moved as f64 <= 3. * bound, "{moved} > 3 * {bound}");
+        assert_eq!(
+            target.storage().inner().as_slice(),
+            expected.transpose().into_storage()
+        );
+
+        // square matrices are also transposed in place
+        let square_file = TempFile::new("square");
+        // SAFETY: nothing else touches the temp files of a test
+        let mut square = unsafe { MmapStorage::<u64>::create(&square_file.0, 128 * 128) }.unwrap();
+        assert!(square.len() * size_of::<u64>() >= 2 * WORKING_SET);
+        for i in 0..square.len() {
+            *square.element_mut(i) = i as u64;
+        }
+        let mut square = DeviceArray::new(&device, square);
+        let moved = transfers(&device, || {
+            matrix_transpose_cache_oblivious_fast(&mut square, 128)
+        });
+        let bound = scan_bound(128 * 128 * 8, BLOCK_SIZE);
+        assert!(moved as f64 <= 3. * bound, "{moved} > 3 * {bound}");
+        for i in 0..128 {
+            for j in 0..128 {
+                assert_eq!(square.inner().as_slice()[i * 128 + j], (j * 128 + i) as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn test_mmap_mul() {
+        let device = BlockDevice::new(WORKING_SET, BLOCK_SIZE);
+        let n = 96;
+        let files = [
+            TempFile::new("a"),
+            TempFile::new("b"),
+            TempFile::new("b-transposed"),
+            TempFile::new("product"),
+        ];
+        let mut a = files[0].create_matrix::<u32>(n, n);
+        fill(&mut a, n, n);
+        let mut b = files[1].create_matrix(n, n);
+        fill(&mut b, n, n);
+        let mut expected = Matrix::new(n, n);
+        fill(&mut expected, n, n);
+        let expected = expected.simple_mul(&expected.transpose());
+
+        // multiply by the transpose, so a wrong operand order is caught
+        let (a, b) = (windowed(&device, a), windowed(&device, b));
+        let mut b_transposed = windowed(&device, files[2].create_matrix(n, n));
+        b.transpose_into(&mut b_transposed);
+        let mut product = windowed(&device, files[3].create_matrix(n, n));
+        let moved = transfers(&device, || a.mul_into(&b_transposed, &mut product));
+        let bound = matrix_mul_bound(n, size_of::<u32>(), WORKING_SET, BLOCK_SIZE);
+        assert!(moved as f64 <= 6. * bound, "{moved} > 6 * {bound}");
+        assert_eq!(
+            product.storage().inner().as_slice(),
+            expected.into_storage()
+        );
+    }
+
+    #[test]
+    fn test_mmap_reopen() {
+        let file = TempFile::new("reopen");
+        {
+            let mut matrix = file.create_matrix::<f64>(3, 4);
+            matrix.set(2, 3, 1.5);
+            matrix.flush().unwrap();
+        }
+        assert_eq!(std::fs::metadata(&file.0).unwrap().len(), 12 * 8);
+        // SAFETY: nothing else touches the temp files of a test
+        unsafe {
+            let matrix = MmapMatrix::<f64>::open(&file.0, 3, 4).unwrap();
+            assert_eq!(*matrix.get(2, 3), 1.5);
+            assert_eq!(*matrix.get(0, 0), 0.);
+
+            assert!(MmapMatrix::<f64>::open(&file.0, 4, 4).is_err());
+            assert!(MmapStorage::<[u8; 5]>::open(&file.0).is_err());
+            assert_eq!(MmapStorage::<u32>::open(&file.0).unwrap().len(), 24);
+        }
+    }
+}
@@ -1,2 +1,17 @@
 pub mod external_sorting;
+pub mod record;
 pub mod cache_oblivious;
+pub mod storage;
+pub mod block_device;
+pub mod eviction_policies;
+pub mod mmap_storage;
+pub mod list_ranking;
+pub mod matrix_view;
+pub mod gemm;
+pub mod parallel;
+pub mod strassen;
+pub mod convolution;
+pub mod dynamic_programming;
+pub mod veb_tree;
+pub mod funnelsort;
+pub mod matrix_layout;
@@ -0,0 +1,313 @@
+//! Multithreaded matrix multiplication and transpose.
+//!
+//! The result is split in halves along its longer side, each half gets its share of the
+//! threads, and one half runs on a new scoped thread while the current one runs the
+//! other. The common dimension is never split, so every entry is summed by one thread in
+//! the same order as the sequential kernels and the result does not depend on the number
+//! of threads, also for floats.
+
+use std::num::NonZeroUsize;
+use std::ops::{Add, Mul, Range};
+use std::thread::{self, Scope};
+
+use super::cache_oblivious::{Matrix, mul_into_rec, transpose_into_rec};
+use super::matrix_view::{MatrixRef, MatrixView, MatrixViewMut};
+use super::storage::Storage;
+
+/// How the parallel kernels split their work.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Parallelism {
+    threads: usize,
+    grain: usize,
+}
+
+impl Parallelism {
+    /// result entries below which a part is not split further
+    pub const DEFAULT_GRAIN: usize = 1 << 14;
+
+    pub fn new(threads: usize) -> Self {
+        assert!(threads > 0);
+        Parallelism {
+            threads,
+            grain: Self::DEFAULT_GRAIN,
+        }
+    }
+
+    /// one thread per core
+    pub fn available() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+
+    /// parts of the result with fewer than `grain` entries stay on one thread
+    pub fn grain(mut self, grain: usize) -> Self {
+        self.grain = grain.max(1);
+        self
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+}
+
+impl Default for Parallelism {
+    fn default() -> Self {
+        Self::available()
+    }
+}
+
+/// The rows and columns of a shared matrix that a task reads.
+struct Block<'a, M: ?Sized> {
+    matrix: &'a M,
+    rows: Range<usize>,
+    cols: Range<usize>,
+}
+
+impl<M: ?Sized> Clone for Block<'_, M> {
+    fn clone(&self) -> Self {
+        Block {
+            matrix: self.matrix,
+            rows: self.rows.clone(),
+            cols: self.cols.clone(),
+        }
+    }
+}
+
+impl<'a, M: ?Sized> Block<'a, M> {
+    fn new<T>(matrix: &'a M) -> Self
+    where
+        M: MatrixRef<T>,
+    {
+        Block {
+            matrix,
+            rows: 0..matrix.rows(),
+            cols: 0..matrix.cols(),
+        }
+    }
+
+    fn split_at_row(self, i: usize) -> (Self, Self) {
+        let (mut top, mut bottom) = (self.clone(), self);
+        top.rows.end = top.rows.start + i;
+        bottom.rows.start = top.rows.end;
+        (top, bottom)
+    }
+
+    fn split_at_col(self, j: usize) -> (Self, Self) {
+        let (mut left, mut right) = (self.clone(), self);
+        left.cols.end = left.cols.start + j;
+        right.cols.start = left.cols.end;
+        (left, right)
+    }
+}
+
+impl<T, M: MatrixRef<T> + ?Sized> MatrixRef<T> for Block<'_, M> {
+    #[inline]
+    fn rows(&self) -> usize {
+        self.rows.len()
+    }
+
+    #[inline]
+    fn cols(&self) -> usize {
+        self.cols.len()
+    }
+
+    #[inline]
+    fn entry(&self, i: usize, j: usize) -> &T {
+        assert!(i < self.rows.len() && j < self.cols.len());
+        self.matrix.entry(self.rows.start + i, self.cols.start + j)
+    }
+
+    fn strided(&self) -> Option<MatrixView<'_, T>> {
+        Some(
+            self.matrix
+                .strided()?
+                .submatrix(self.rows.clone(), self.cols.clone()),
+        )
+    }
+}
+
+/// the position to split `len` at to give `threads / 2` of `threads` to the first part
+fn split_point(len: usize, threads: usize) -> usize {
+    len * (threads / 2) / threads
+}
+
+fn par_mul_rec<'s, 'e, T, A, B>(
+    scope: &'s Scope<'s, 'e>,
+    a: Block<'e, A>,
+    b: Block<'e, B>,
+    mut c: MatrixViewMut<'e, T>,
+    threads: usize,
+    grain: usize,
+) where
+    T: Mul<Output = T> + Add<Output = T> + Clone + Send + Sync + 'static,
+    A: MatrixRef<T> + Sync + ?Sized,
+    B: MatrixRef<T> + Sync + ?Sized,
+{
+    if threads == 1 || c.rows() * c.cols() < 2 * grain {
+        mul_into_rec(&a, &b, &mut c);
+        return;
+    }
+    let (first, second) = (threads / 2, threads - threads / 2);
+    if c.rows() >= c.cols() {
+        let i = split_point(c.rows(), threads);
+        let (a0, a1) = a.split_at_row(i);
+        let (c0, c1) = c.split_at_row(i);
+        let b0 = b.clone();
+        scope.spawn(move || par_mul_rec(scope, a0, b0, c0, first, grain));
+        par_mul_rec(scope, a1, b, c1, second, grain);
+    } else {
+        let j = split_point(c.cols(), threads);
+        let (b0, b1) = b.split_at_col(j);
+        let (c0, c1) = c.split_at_col(j);
+        let a0 = a.clone();
+        scope.spawn(move || par_mul_rec(scope, a0, b0, c0, first, grain));
+        par_mul_rec(scope, a, b1, c1, second, grain);
+    }
+}
+
+/// result += a * b on `parallelism.threads()` threads, result must be a.rows x b.cols
+pub fn par_mul_into<T, A, B>(a: &A, b: &B, result: MatrixViewMut<'_, T>, parallelism: Parallelism)
+where
+    T: Mul<Output = T> + Add<Output = T> + Clone + Send + Sync + 'static,
+    A: MatrixRef<T> + Sync + ?Sized,
+    B: MatrixRef<T> + Sync + ?Sized,
+{
+    assert_eq!(a.cols(), b.rows());
+    assert_eq!((result.rows(), result.cols()), (a.rows(), b.cols()));
+    let Parallelism { threads, grain } = parallelism;
+    thread::scope(|scope| {
+        par_mul_rec(scope, Block::new(a), Block::new(b), result, threads, grain);
+    });
+}
+
+fn par_transpose_rec<'s, 'e, T, A>(
+    scope: &'s Scope<'s, 'e>,
+    source: Block<'e, A>,
+    mut target: MatrixViewMut<'e, T>,
+    threads: usize,
+    grain: usize,
+) where
+    T: Clone + Send + Sync,
+    A: MatrixRef<T> + Sync + ?Sized,
+{
+    if threads == 1 || target.rows() * target.cols() < 2 * grain {
+        transpose_into_rec(&source, &mut target);
+        return;
+    }
+    let (first, second) = (threads / 2, threads - threads / 2);
+    if target.rows() >= target.cols() {
+        let i = split_point(target.rows(), threads);
+        let (s0, s1) = source.split_at_col(i);
+        let (t0, t1) = target.split_at_row(i);
+        scope.spawn(move || par_transpose_rec(scope, s0, t0, first, grain));
+        par_transpose_rec(scope, s1, t1, second, grain);
+    } else {
+        let j = split_point(target.cols(), threads);
+        let (s0, s1) = source.split_at_row(j);
+        let (t0, t1) = target.split_at_col(j);
+        scope.spawn(move || par_transpose_rec(scope, s0, t0, first, grain));
+        par_transpose_rec(scope, s1, t1, second, grain);
+    }
+}
+
+/// write the transpose of `source` into `target` on `parallelism.threads()` threads,
+/// target must be cols x rows
+pub fn par_transpose_into<T, A>(source: &A, target: MatrixViewMut<'_, T>, parallelism: Parallelism)
+where
+    T: Clone + Send + Sync,
+    A: MatrixRef<T> + Sync + ?Sized,
+{
+    assert_eq!(
+        (target.rows(), target.cols()),
+        (source.cols(), source.rows())
+    );
+    let Parallelism { threads, grain } = parallelism;
+    thread::scope(|scope| {
+        par_transpose_rec(scope, Block::new(source), target, threads, grain);
+    });
+}
+
+impl<T, S> Matrix<T, S>
+where
+    T: Mul<Output = T> + Add<Output = T> + Default + Clone + Send + Sync + 'static,
+    S: Storage<T> + Sync,
+{
+    /// [`Matrix::mul`] on `parallelism.threads()` threads, with the same result
+    pub fn par_mul<B: MatrixRef<T> + Sync + ?Sized>(
+        &self,
+        other: &B,
+        parallelism: Parallelism,
+    ) -> Matrix<T> {
+        let mut result = Matrix::new(self.rows(), other.cols());
+        par_mul_into(self, other, result.view_mut(), parallelism);
+        result
+    }
+}
+
+impl<T: Default + Clone + Send + Sync, S: Storage<T> + Sync> Matrix<T, S> {
+    /// [`Matrix::transpose`] on `parallelism.threads()` threads
+    pub fn par_transpose(&self, parallelism: Parallelism) -> Matrix<T> {
+        let mut target = Matrix::new(self.cols(), self.rows());
+        par_transpose_into(self, target.view_mut(), parallelism);
+        target
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::distr::{Distribution, Uniform};
+    use rand::{SeedableRng, rngs::StdRng};
+
+    fn random(n: usize, m: usize, rng: &mut StdRng) -> Matrix<f64> {
+        let uniform = Uniform::new(-1., 1.).unwrap();
+        Matrix::from_vec(uniform.sample_iter(rng).take(n * m).collect(), n, m)
+    }
+
+    #[test]
+    fn test_par_mul_is_deterministic() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for (n, k, m) in [(1, 1, 1), (130, 70, 90), (33, 500, 301)] {
+            let a = random(n, k, &mut rng);
+            let b = random(k, m, &mut rng);
+            let expected = a.mul(&b);
+            for threads in [1, 2, 3, 4, 7] {
+                let parallelism = Parallelism::new(threads).grain(64);
+                // bitwise equal, not only close
+                assert_eq!(a.par_mul(&b, parallelism), expected);
+            }
+        }
+
+        // the generic kernel on integers, and a view as the right operand
+        let a = Matrix::from_vec((0..60 * 50).map(|x| x % 13).collect::<Vec<u64>>(), 60, 50);
+        let b = a.transpose();
+        let expected = a.mul(&b.view().submatrix(0..50, 5..45));
+        let product = a.par_mul(
+            &b.view().submatrix(0..50, 5..45),
+            Parallelism::new(5).grain(1),
+        );
+        assert_eq!(product, expected);
+    }
+
+    #[test]
+    fn test_par_transpose() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for (n, m) in [(1, 1), (1, 100), (257, 129), (400, 400)] {
+            let matrix = random(n, m, &mut rng);
+            let expected = matrix.transpose();
+            for threads in [1, 2, 5, 8] {
+                let parallelism = Parallelism::new(threads).grain(16);
+                assert_eq!(matrix.par_transpose(parallelism), expected);
+            }
+        }
+
+        // a transposed view goes back to the original
+        let matrix = random(50, 70, &mut rng);
+        let mut target = Matrix::new(50, 70);
+        par_transpose_into(
+            &matrix.view().t(),
+            target.view_mut(),
+            Parallelism::new(3).grain(1),
+        );
+        assert_eq!(target, matrix);
+    }
+}
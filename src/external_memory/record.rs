@@ -0,0 +1,623 @@
+//! Fixed-width little-endian records and buffered readers and writers for them.
+//!
+//! Every record also provides an 8-byte key prefix that preserves its order, so
+//! sorting can compare `u64`s and only look at the full records on ties.
+
+use std::io::{self, Read, Write};
+
+/// A fixed-size record with a little-endian binary encoding.
+pub trait Record: Sized {
+    /// encoded size in bytes
+    const SIZE: usize;
+
+    /// number of high-order bytes of [`Record::key_prefix`] that carry information,
+    /// the remaining low-order bytes are zero
+    ///
+    /// When `PREFIX_BYTES == SIZE` the prefix determines the record, so equal prefixes
+    /// mean equal records.
+    const PREFIX_BYTES: usize;
+
+    /// `bytes.len()` is always `SIZE`
+    fn encode(&self, bytes: &mut [u8]);
+
+    /// `bytes.len()` is always `SIZE`
+    fn decode(bytes: &[u8]) -> Self;
+
+    /// an order-preserving key: `a < b` implies `a.key_prefix() <= b.key_prefix()`
+    ///
+    /// The most significant part of the record is left-aligned in the high bytes.
+    /// Floats are ordered by `total_cmp`.
+    fn key_prefix(&self) -> u64;
+}
+
+macro_rules! impl_record_unsigned {
+    ($($t:ty),*) => {
+        $(
+            impl Record for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+                const PREFIX_BYTES: usize = if Self::SIZE < 8 { Self::SIZE } else { 8 };
+
+                #[inline]
+                fn encode(&self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                }
+
+                #[inline]
+                fn decode(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().unwrap())
+                }
+
+                #[inline]
+                fn key_prefix(&self) -> u64 {
+                    // left-align the value in 128 bits and keep the high half
+                    ((*self as u128) << (128 - 8 * Self::SIZE) >> 64) as u64
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_record_signed {
+    ($($t:ty => $u:ty),*) => {
+        $(
+            impl Record for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+                const PREFIX_BYTES: usize = <$u as Record>::PREFIX_BYTES;
+
+                #[inline]
+                fn encode(&self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                }
+
+                #[inline]
+                fn decode(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().unwrap())
+                }
+
+                #[inline]
+                fn key_prefix(&self) -> u64 {
+                    // flipping the sign bit maps the signed order onto the unsigned one
+                    ((*self as $u) ^ (1 << (<$u>::BITS - 1))).key_prefix()
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_record_float {
+    ($($t:ty => $u:ty),*) => {
+        $(
+            impl Record for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+                const PREFIX_BYTES: usize = Self::SIZE;
+
+                #[inline]
+                fn encode(&self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                }
+
+                #[inline]
+                fn decode(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().unwrap())
+                }
+
+                #[inline]
+                fn key_prefix(&self) -> u64 {
+                    // negative numbers flip all the bits, positive ones only the sign bit
+                    let bits = self.to_bits();
+                    let sign = 1 << (<$u>::BITS - 1);
+                    let key = if bits & sign != 0 { !bits } else { bits | sign };
+                    key.key_prefix()
+                }
+            }
+        )*
+    };
+}
+
+/// `usize` and `isize` take 8 bytes on every target, so the files do not depend on the
+/// pointer width of the machine that wrote them
+macro_rules! impl_record_pointer_sized {
+    ($($t:ty => $fixed:ty),*) => {
+        $(
+            impl Record for $t {
+                const SIZE: usize = <$fixed as Record>::SIZE;
+                const PREFIX_BYTES: usize = <$fixed as Record>::PREFIX_BYTES;
+
+                #[inline]
+                fn encode(&self, bytes: &mut [u8]) {
+                    (*self as $fixed).encode(bytes);
+                }
+
+                #[inline]
+                fn decode(bytes: &[u8]) -> Self {
+                    <$fixed>::decode(bytes)
+                        .try_into()
+                        .expect(concat!("record does not fit in ", stringify!($t)))
+                }
+
+                #[inline]
+                fn key_prefix(&self) -> u64 {
+                    (*self as $fixed).key_prefix()
+                }
+            }
+        )*
+    };
+}
+
+impl_record_unsigned!(u8, u16, u32, u64, u128);
+impl_record_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, i128 => u128);
+impl_record_float!(f32 => u32, f64 => u64);
+impl_record_pointer_sized!(usize => u64, isize => i64);
+
+/// `PREFIX_BYTES` of a record compared field by field, given `(SIZE, PREFIX_BYTES)`
+/// of the fields in order
+///
+/// A field can only extend the prefix if all the fields before it are exact.
+const fn lexicographic_prefix_bytes(fields: &[(usize, usize)]) -> usize {
+    let mut used = 0;
+    let mut i = 0;
+    while i < fields.len() && used < 8 {
+        let (size, prefix_bytes) = fields[i];
+        used += prefix_bytes;
+        if prefix_bytes < size {
+            break;
+        }
+        i += 1;
+    }
+    if used < 8 { used } else { 8 }
+}
+
+/// append the prefix of the next field to a key whose first `used` bytes are taken
+#[inline]
+fn chain_prefix(key: u64, used: usize, next: u64) -> u64 {
+    if used >= 8 {
+        key
+    } else {
+        key | (next >> (8 * used))
+    }
+}
+
+macro_rules! impl_record_tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: Record),+> Record for ($($name,)+) {
+            const SIZE: usize = 0 $(+ $name::SIZE)+;
+            const PREFIX_BYTES: usize =
+                lexicographic_prefix_bytes(&[$(($name::SIZE, $name::PREFIX_BYTES)),+]);
+
+            #[inline]
+            fn encode(&self, bytes: &mut [u8]) {
+                let mut offset = 0;
+                $(
+                    self.$index.encode(&mut bytes[offset..offset + $name::SIZE]);
+                    offset += $name::SIZE;
+                )+
+                let _ = offset;
+            }
+
+            #[inline]
+            fn decode(bytes: &[u8]) -> Self {
+                let mut offset = 0;
+                let record = ($({
+                    let field = $name::decode(&bytes[offset..offset + $name::SIZE]);
+                    offset += $name::SIZE;
+                    field
+                },)+);
+                let _ = offset;
+                record
+            }
+
+            #[inline]
+            fn key_prefix(&self) -> u64 {
+                let (mut key, mut used, mut exact) = (0, 0, true);
+                $(
+                    if exact && used < 8 {
+                        key = chain_prefix(key, used, self.$index.key_prefix());
+                        used += $name::PREFIX_BYTES;
+                        exact = $name::PREFIX_BYTES == $name::SIZE;
+                    }
+                )+
+                let _ = (used, exact);
+                key
+            }
+        }
+    };
+}
+
+impl_record_tuple!(A 0);
+impl_record_tuple!(A 0, B 1);
+impl_record_tuple!(A 0, B 1, C 2);
+impl_record_tuple!(A 0, B 1, C 2, D 3);
+
+impl<T: Record, const N: usize> Record for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+    const PREFIX_BYTES: usize = if N == 0 {
+        0
+    } else if T::PREFIX_BYTES < T::SIZE {
+        T::PREFIX_BYTES
+    } else if Self::SIZE < 8 {
+        Self::SIZE
+    } else {
+        8
+    };
+
+    #[inline]
+    fn encode(&self, bytes: &mut [u8]) {
+        for (x, chunk) in self.iter().zip(bytes.chunks_exact_mut(T::SIZE)) {
+            x.encode(chunk);
+        }
+    }
+
+    #[inline]
+    fn decode(bytes: &[u8]) -> Self {
+        std::array::from_fn(|i| T::decode(&bytes[i * T::SIZE..(i + 1) * T::SIZE]))
+    }
+
+    #[inline]
+    fn key_prefix(&self) -> u64 {
+        let (mut key, mut used) = (0, 0);
+        for x in self {
+            if used >= 8 {
+                break;
+            }
+            key = chain_prefix(key, used, x.key_prefix());
+            if T::PREFIX_BYTES < T::SIZE {
+                break;
+            }
+            used += T::SIZE;
+        }
+        key
+    }
+}
+
+/// default block size of [`RecordReader`] and [`RecordWriter`]
+pub const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+
+/// the largest multiple of `T::SIZE` not exceeding `block_size`, at least one record
+fn round_block_size<T: Record>(block_size: usize) -> usize {
+    const { assert!(T::SIZE > 0, "zero-sized records cannot be read or written") };
+    (block_size / T::SIZE).max(1) * T::SIZE
+}
+
+/// Reads [`Record`]s from an [`io::Read`] one block at a time.
+pub struct RecordReader<R, T> {
+    inner: R,
+    buffer: Vec<u8>,
+    /// `buffer[pos..len]` has not been decoded yet
+    pos: usize,
+    len: usize,
+    bytes_read: u64,
+    _record: std::marker::PhantomData<T>,
+}
+
+impl<R: Read, T: Record> RecordReader<R, T> {
+    pub fn new(inner: R) -> Self {
+        Self::with_block_size(inner, DEFAULT_BLOCK_SIZE)
+    }
+
+    /// `block_size` is rounded down to a whole number of records
+    pub fn with_block_size(inner: R, block_size: usize) -> Self {
+        RecordReader {
+            inner,
+            buffer: vec![0; round_block_size::<T>(block_size)],
+            pos: 0,
+            len: 0,
+            bytes_read: 0,
+            _record: std::marker::PhantomData,
+        }
+    }
+
+    /// the number of bytes read from the underlying reader so far
+    #[inline]
+    pub fn bytes_read(&self) -> u64 {
+        self.bytes_read
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// refill the buffer, returns false at the end of the input
+    ///
+    /// A trailing partial record is an `InvalidData` error.
+    fn fill(&mut self) -> io::Result<bool> {
+        let leftover = self.len - self.pos;
+        self.buffer.copy_within(self.pos..self.len, 0);
+        self.pos = 0;
+        self.len = leftover;
+        while self.len < self.buffer.len() {
+            match self.inner.read(&mut self.buffer[self.len..]) {
+                Ok(0) => break,
+                Ok(n) => {
+                    self.len += n;
+                    self.bytes_read += n as u64;
+                }
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if !self.len.is_multiple_of(T::SIZE) && self.len < self.buffer.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "input ends with a partial record",
+            ));
+        }
+        Ok(self.len >= T::SIZE)
+    }
+
+    pub fn read_record(&mut self) -> io::Result<Option<T>> {
+        if self.len - self.pos < T::SIZE && !self.fill()? {
+            return Ok(None);
+        }
+        let record = T::decode(&self.buffer[self.pos..self.pos + T::SIZE]);
+        self.pos += T::SIZE;
+        Ok(Some(record))
+    }
+
+    /// append at most `max` records to `records`, returns the number appended
+    pub fn read_records(&mut self, records: &mut Vec<T>, max: usize) -> io::Result<usize> {
+        let mut count = 0;
+        while count < max {
+            if self.len - self.pos < T::SIZE && !self.fill()? {
+                break;
+            }
+            let available = (self.len - self.pos) / T::SIZE;
+            let take = available.min(max - count);
+            let end = self.pos + take * T::SIZE;
+            records.extend(
+                self.buffer[self.pos..end]
+                    .chunks_exact(T::SIZE)
+                    .map(T::decode),
+            );
+            self.pos = end;
+            count += take;
+        }
+        Ok(count)
+    }
+}
+
+impl<R: Read, T: Record> Iterator for RecordReader<R, T> {
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<io::Result<T>> {
+        self.read_record().transpose()
+    }
+}
+
+/// Writes [`Record`]s to an [`io::Write`] one block at a time.
+///
+/// Like `BufWriter`, the buffer is flushed on drop and errors there are ignored,
+/// so call [`RecordWriter::flush`] or [`RecordWriter::into_inner`] to see them.
+pub struct RecordWriter<W: Write, T> {
+    inner: Option<W>,
+    buffer: Vec<u8>,
+    len: usize,
+    bytes_written: u64,
+    _record: std::marker::PhantomData<T>,
+}
+
+impl<W: Write, T: Record> RecordWriter<W, T> {
+    pub fn new(inner: W) -> Self {
+        Self::with_block_size(inner, DEFAULT_BLOCK_SIZE)
+    }
+
+    /// `block_size` is rounded down to a whole number of records
+    pub fn with_block_size(inner: W, block_size: usize) -> Self {
+        RecordWriter {
+            inner: Some(inner),
+            buffer: vec![0; round_block_size::<T>(block_size)],
+            len: 0,
+            bytes_written: 0,
+            _record: std::marker::PhantomData,
+        }
+    }
+
+    /// the number of bytes handed to the underlying writer so far
+    #[inline]
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    pub fn write_record(&mut self, record: &T) -> io::Result<()> {
+        if self.len == self.buffer.len() {
+            self.flush_buffer()?;
+        }
+        record.encode(&mut self.buffer[self.len..self.len + T::SIZE]);
+        self.len += T::SIZE;
+        Ok(())
+    }
+
+    pub fn write_records(&mut self, records: &[T]) -> io::Result<()> {
+        for record in records {
+            self.write_record(record)?;
+        }
+        Ok(())
+    }
+
+    fn flush_buffer(&mut self) -> io::Result<()> {
+        let inner = self.inner.as_mut().unwrap();
+        inner.write_all(&self.buffer[..self.len])?;
+        self.bytes_written += self.len as u64;
+        self.len = 0;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.flush_buffer()?;
+        self.inner.as_mut().unwrap().flush()
+    }
+
+    /// flush and return the underlying writer
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.inner.take().unwrap())
+    }
+}
+
+impl<W: Write, T> Drop for RecordWriter<W, T> {
+    fn drop(&mut self) {
+        if let Some(inner) = self.inner.as_mut() {
+            let _ = inner.write_all(&self.buffer[..self.len]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::fmt::Debug;
+
+    fn round_trip<T: Record + PartialEq + Debug>(value: T) {
+        let mut bytes = vec![0; T::SIZE];
+        value.encode(&mut bytes);
+        assert_eq!(T::decode(&bytes), value);
+        let unused_bits = 8 * (8 - T::PREFIX_BYTES);
+        assert_eq!(value.key_prefix() & ((1u128 << unused_bits) - 1) as u64, 0);
+    }
+
+    /// the prefix order must agree with `cmp`, and be exact when it covers the record
+    fn check_prefix_order<T: Record + Debug>(
+        values: &[T],
+        cmp: impl Fn(&T, &T) -> std::cmp::Ordering,
+    ) {
+        for a in values {
+            for b in values {
+                let (ka, kb) = (a.key_prefix(), b.key_prefix());
+                match cmp(a, b) {
+                    std::cmp::Ordering::Less => assert!(ka <= kb, "{a:?} {b:?}"),
+                    std::cmp::Ordering::Equal => assert_eq!(ka, kb, "{a:?} {b:?}"),
+                    std::cmp::Ordering::Greater => assert!(ka >= kb, "{a:?} {b:?}"),
+                }
+                if T::PREFIX_BYTES == T::SIZE {
+                    assert_eq!(ka.cmp(&kb), cmp(a, b), "{a:?} {b:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_record_encoding() {
+        round_trip(0xABu8);
+        round_trip(-12345i16);
+        round_trip(u32::MAX);
+        round_trip(i64::MIN);
+        round_trip(u128::MAX - 5);
+        round_trip(-1.5f32);
+        round_trip(f64::MAX);
+        round_trip(usize::MAX);
+        round_trip(-7isize);
+        round_trip((1u8, -2i32, 3.5f64));
+        round_trip((1u16, 2u16, 3u16, 4u16));
+        round_trip([7u32, 8, 9]);
+        round_trip([(1u8, 2u8); 5]);
+
+        let mut bytes = [0; 6];
+        (0x0102u16, 0x03040506u32).encode(&mut bytes);
+        assert_eq!(bytes, [0x02, 0x01, 0x06, 0x05, 0x04, 0x03]);
+
+        assert_eq!(<(u8, u16)>::PREFIX_BYTES, 3);
+        assert_eq!(<(u32, u64)>::PREFIX_BYTES, 8);
+        assert_eq!(<(u128, u8)>::PREFIX_BYTES, 8);
+        assert_eq!(<[u16; 3]>::PREFIX_BYTES, 6);
+        assert_eq!(<[u16; 5]>::PREFIX_BYTES, 8);
+        assert_eq!(<[u8; 0]>::PREFIX_BYTES, 0);
+        assert_eq!(<(u32, u16)>::SIZE, 6);
+        assert_eq!(<(usize, isize)>::SIZE, 16);
+    }
+
+    #[test]
+    fn test_key_prefix_order() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let small = |rng: &mut StdRng| rng.random_range(-3..3);
+
+        let values: Vec<i32> = (0..50)
+            .map(|_| rng.random())
+            .chain([i32::MIN, -1, 0, i32::MAX])
+            .collect();
+        check_prefix_order(&values, i32::cmp);
+        let values: Vec<u128> = (0..50)
+            .map(|_| rng.random::<u128>() >> rng.random_range(0..128))
+            .collect();
+        check_prefix_order(&values, u128::cmp);
+        let values: Vec<i8> = (i8::MIN..=i8::MAX).collect();
+        check_prefix_order(&values, i8::cmp);
+        let values: Vec<isize> = (0..50)
+            .map(|_| rng.random::<i64>() as isize)
+            .chain([isize::MIN, -1, 0, isize::MAX])
+            .collect();
+        check_prefix_order(&values, isize::cmp);
+        let values: Vec<f64> = (0..50)
+            .map(|_| rng.random_range(-1e9..1e9))
+            .chain([
+                0.0,
+                -0.0,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::MIN_POSITIVE,
+            ])
+            .collect();
+        check_prefix_order(&values, f64::total_cmp);
+        let values: Vec<f32> = (0..50).map(|_| rng.random_range(-10.0..10.0)).collect();
+        check_prefix_order(&values, f32::total_cmp);
+
+        let values: Vec<(i16, u8, i64)> = (0..100)
+            .map(|_| (small(&mut rng) as i16, rng.random(), small(&mut rng) as i64))
+            .collect();
+        check_prefix_order(&values, Ord::cmp);
+        let values: Vec<(u8, u128)> = (0..100)
+            .map(|_| (rng.random_range(0..3), rng.random_range(0..4) << 120))
+            .collect();
+        check_prefix_order(&values, Ord::cmp);
+        let values: Vec<[i8; 3]> = (0..100)
+            .map(|_| std::array::from_fn(|_| small(&mut rng) as i8))
+            .collect();
+        check_prefix_order(&values, Ord::cmp);
+        let values: Vec<[u32; 4]> = (0..100)
+            .map(|_| std::array::from_fn(|_| rng.random_range(0..3)))
+            .collect();
+        check_prefix_order(&values, Ord::cmp);
+    }
+
+    #[test]
+    fn test_record_reader_writer() {
+        let values: Vec<(u32, i16)> = (0..1000).map(|i| (i * 7, -(i as i16))).collect();
+        for block_size in [1, 6, 7, 100, 1 << 16] {
+            let mut writer = RecordWriter::with_block_size(Vec::new(), block_size);
+            writer.write_records(&values[..500]).unwrap();
+            for value in &values[500..] {
+                writer.write_record(value).unwrap();
+            }
+            let bytes = writer.into_inner().unwrap();
+            assert_eq!(bytes.len(), 6000);
+
+            let mut reader = RecordReader::<_, (u32, i16)>::with_block_size(&bytes[..], block_size);
+            let mut read = Vec::new();
+            assert_eq!(reader.read_records(&mut read, 333).unwrap(), 333);
+            assert_eq!(reader.read_record().unwrap(), Some(values[333]));
+            read.push(values[333]);
+            for value in reader.by_ref() {
+                read.push(value.unwrap());
+            }
+            assert_eq!(read, values);
+            assert_eq!(reader.bytes_read(), 6000);
+            assert_eq!(reader.read_records(&mut read, 10).unwrap(), 0);
+        }
+
+        // dropping the writer flushes it
+        let mut bytes = Vec::new();
+        {
+            let mut writer = RecordWriter::new(&mut bytes);
+            writer.write_record(&0x01020304u32).unwrap();
+        }
+        assert_eq!(bytes, [4, 3, 2, 1]);
+
+        // a partial record at the end of the input is an error
+        let mut reader = RecordReader::<_, u32>::with_block_size(&[1u8, 0, 0, 0, 2, 0][..], 4);
+        assert_eq!(reader.read_record().unwrap(), Some(1));
+        assert_eq!(
+            reader.read_record().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
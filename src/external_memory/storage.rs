@@ -0,0 +1,86 @@
+/// Linear element storage the matrix kernels run on.
+///
+/// Implemented for slices and vectors, and for storages that live outside of the
+/// process memory or count the accesses to it.
+pub trait Storage<T> {
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn element(&self, i: usize) -> &T;
+
+    fn element_mut(&mut self, i: usize) -> &mut T;
+
+    fn swap(&mut self, i: usize, j: usize);
+
+    /// the elements as one slice, if they are in process memory
+    fn contiguous(&self) -> Option<&[T]> {
+        None
+    }
+
+    fn contiguous_mut(&mut self) -> Option<&mut [T]> {
+        None
+    }
+}
+
+impl<T> Storage<T> for [T] {
+    #[inline]
+    fn len(&self) -> usize {
+        <[T]>::len(self)
+    }
+
+    #[inline]
+    fn element(&self, i: usize) -> &T {
+        &self[i]
+    }
+
+    #[inline]
+    fn element_mut(&mut self, i: usize) -> &mut T {
+        &mut self[i]
+    }
+
+    #[inline]
+    fn swap(&mut self, i: usize, j: usize) {
+        <[T]>::swap(self, i, j);
+    }
+
+    fn contiguous(&self) -> Option<&[T]> {
+        Some(self)
+    }
+
+    fn contiguous_mut(&mut self) -> Option<&mut [T]> {
+        Some(self)
+    }
+}
+
+impl<T> Storage<T> for Vec<T> {
+    #[inline]
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    #[inline]
+    fn element(&self, i: usize) -> &T {
+        &self[i]
+    }
+
+    #[inline]
+    fn element_mut(&mut self, i: usize) -> &mut T {
+        &mut self[i]
+    }
+
+    #[inline]
+    fn swap(&mut self, i: usize, j: usize) {
+        self.as_mut_slice().swap(i, j);
+    }
+
+    fn contiguous(&self) -> Option<&[T]> {
+        Some(self)
+    }
+
+    fn contiguous_mut(&mut self) -> Option<&mut [T]> {
+        Some(self)
+    }
+}
@@ -0,0 +1,356 @@
+//! Strassen and Strassen-Winograd matrix multiplication.
+//!
+//! Each level splits the three dimensions in halves and does 7 half-size products
+//! instead of 8, with 18 (Strassen) or 15 (Winograd) additions of blocks. Odd
+//! dimensions are peeled: the even part recurses, and the last row, column or rank-one
+//! update is added with the cache-oblivious kernel, which also takes over once a
+//! dimension is at most the cutoff. The error bound is weaker than for the cubic
+//! algorithm, [`ErrorReport`] measures it against `simple_mul`.
+
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
+
+use rand::distr::{Distribution, Uniform};
+use rand::{SeedableRng, rngs::StdRng};
+
+use super::cache_oblivious::{Matrix, mul_into_rec};
+use super::matrix_view::{MatrixView, MatrixViewMut};
+use super::storage::Storage;
+
+/// dimensions at most this go to the cache-oblivious kernel, for floats the packed GEMM
+/// is fast enough that a level of recursion only pays off well above it
+pub const DEFAULT_CUTOFF: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Variant {
+    Strassen,
+    Winograd,
+}
+
+/// a block addition or subtraction
+type Op<T> = fn(T, T) -> T;
+
+/// x op y entry by entry, for views of the same shape
+fn combine<T: Default + Clone>(x: MatrixView<'_, T>, y: MatrixView<'_, T>, op: Op<T>) -> Matrix<T> {
+    assert_eq!((x.rows(), x.cols()), (y.rows(), y.cols()));
+    let mut data = Vec::with_capacity(x.rows() * x.cols());
+    for i in 0..x.rows() {
+        match (x.row_slice(i), y.row_slice(i)) {
+            (Some(x), Some(y)) => {
+                data.extend(x.iter().zip(y).map(|(x, y)| op(x.clone(), y.clone())));
+            }
+            _ => data.extend((0..x.cols()).map(|j| op(x.get(i, j).clone(), y.get(i, j).clone()))),
+        }
+    }
+    Matrix::from_vec(data, x.rows(), x.cols())
+}
+
+/// c = c op x entry by entry
+fn update<T: Clone>(c: &mut MatrixViewMut<'_, T>, x: &Matrix<T>, op: Op<T>) {
+    let x = x.view();
+    for i in 0..c.rows() {
+        let x = x.row_slice(i).unwrap();
+        if let Some(row) = c.row_slice_mut(i) {
+            for (c, x) in row.iter_mut().zip(x) {
+                *c = op(c.clone(), x.clone());
+            }
+        } else {
+            for (j, x) in x.iter().enumerate() {
+                let entry = c.get_mut(i, j);
+                *entry = op(entry.clone(), x.clone());
+            }
+        }
+    }
+}
+
+/// the four half blocks (11, 12, 21, 22) of a view with even sides
+fn quarters<T>(x: MatrixView<'_, T>) -> [MatrixView<'_, T>; 4] {
+    let (top, bottom) = x.split_at_row(x.rows() / 2);
+    let (x11, x12) = top.split_at_col(x.cols() / 2);
+    let (x21, x22) = bottom.split_at_col(x.cols() / 2);
+    [x11, x12, x21, x22]
+}
+
+fn quarters_mut<T>(x: MatrixViewMut<'_, T>) -> [MatrixViewMut<'_, T>; 4] {
+    let (rows, cols) = (x.rows(), x.cols());
+    let (top, bottom) = x.split_at_row(rows / 2);
+    let (x11, x12) = top.split_at_col(cols / 2);
+    let (x21, x22) = bottom.split_at_col(cols / 2);
+    [x11, x12, x21, x22]
+}
+
+fn add<T: Add<Output = T>>(x: T, y: T) -> T {
+    x + y
+}
+
+fn sub<T: Sub<Output = T>>(x: T, y: T) -> T {
+    x - y
+}
+
+fn fast_mul_rec<T>(
+    a: MatrixView<'_, T>,
+    b: MatrixView<'_, T>,
+    mut c: MatrixViewMut<'_, T>,
+    cutoff: usize,
+    variant: Variant,
+) where
+    T: Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Default + Clone + 'static,
+{
+    let (m, k, n) = (a.rows(), a.cols(), b.cols());
+    if m.min(k).min(n) <= cutoff.max(1) {
+        mul_into_rec(&a, &b, &mut c);
+        return;
+    }
+
+    // peel the odd row, column and rank-one update
+    let (m2, k2, n2) = (m & !1, k & !1, n & !1);
+    let (a_top, a_bottom) = a.split_at_row(m2);
+    let (b_left, b_right) = b.split_at_col(n2);
+    let (c_top, mut c_bottom) = c.split_at_row(m2);
+    let (mut c_even, mut c_right) = c_top.split_at_col(n2);
+    mul_into_rec(&a_bottom, &b, &mut c_bottom);
+    mul_into_rec(&a_top, &b_right, &mut c_right);
+    if k2 < k {
+        let a_col = a_top.submatrix(0..m2, k2..k);
+        let b_row = b_left.submatrix(k2..k, 0..n2);
+        mul_into_rec(&a_col, &b_row, &mut c_even);
+    }
+    let a = a_top.submatrix(0..m2, 0..k2);
+    let b = b_left.submatrix(0..k2, 0..n2);
+
+    let [a11, a12, a21, a22] = quarters(a);
+    let [b11, b12, b21, b22] = quarters(b);
+    let [mut c11, mut c12, mut c21, mut c22] = quarters_mut(c_even);
+    let (add, sub): (Op<T>, Op<T>) = (add, sub);
+    let product = |x: MatrixView<'_, T>, y: MatrixView<'_, T>| {
+        let mut result = Matrix::new(x.rows(), y.cols());
+        fast_mul_rec(x, y, result.view_mut(), cutoff, variant);
+        result
+    };
+
+    match variant {
+        Variant::Strassen => {
+            let m1 = product(combine(a11, a22, add).view(), combine(b11, b22, add).view());
+            let m2 = product(combine(a21, a22, add).view(), b11);
+            let m3 = product(a11, combine(b12, b22, sub).view());
+            let m4 = product(a22, combine(b21, b11, sub).view());
+            let m5 = product(combine(a11, a12, add).view(), b22);
+            let m6 = product(combine(a21, a11, sub).view(), combine(b11, b12, add).view());
+            let m7 = product(combine(a12, a22, sub).view(), combine(b21, b22, add).view());
+            // c11 += m1 + m4 - m5 + m7, c12 += m3 + m5,
+            // c21 += m2 + m4, c22 += m1 - m2 + m3 + m6
+            for (x, op) in [(&m1, add), (&m4, add), (&m5, sub), (&m7, add)] {
+                update(&mut c11, x, op);
+            }
+            for (x, op) in [(&m3, add), (&m5, add)] {
+                update(&mut c12, x, op);
+            }
+            for (x, op) in [(&m2, add), (&m4, add)] {
+                update(&mut c21, x, op);
+            }
+            for (x, op) in [(&m1, add), (&m2, sub), (&m3, add), (&m6, add)] {
+                update(&mut c22, x, op);
+            }
+        }
+        Variant::Winograd => {
+            let s1 = combine(a21, a22, add);
+            let s2 = combine(s1.view(), a11, sub);
+            let s3 = combine(a11, a21, sub);
+            let s4 = combine(a12, s2.view(), sub);
+            let t1 = combine(b12, b11, sub);
+            let t2 = combine(b22, t1.view(), sub);
+            let t3 = combine(b22, b12, sub);
+            let t4 = combine(t2.view(), b21, sub);
+
+            let p1 = product(a11, b11);
+            let p2 = product(a12, b21);
+            let p3 = product(s4.view(), b22);
+            let p4 = product(a22, t4.view());
+            let p5 = product(s1.view(), t1.view());
+            let p6 = product(s2.view(), t2.view());
+            let p7 = product(s3.view(), t3.view());
+
+            let u2 = combine(p1.view(), p6.view(), add);
+            let u3 = combine(u2.view(), p7.view(), add);
+            let u4 = combine(u2.view(), p5.view(), add);
+            // c11 += p1 + p2, c12 += u4 + p3, c21 += u3 - p4, c22 += u3 + p5
+            update(&mut c11, &combine(p1.view(), p2.view(), add), add);
+            update(&mut c12, &combine(u4.view(), p3.view(), add), add);
+            update(&mut c21, &combine(u3.view(), p4.view(), sub), add);
+            update(&mut c22, &combine(u3.view(), p5.view(), add), add);
+        }
+    }
+}
+
+/// c += a * b with Strassen's algorithm, dimensions at most `cutoff` use [`Matrix::mul`]
+pub fn strassen_mul_into<T>(
+    a: MatrixView<'_, T>,
+    b: MatrixView<'_, T>,
+    c: MatrixViewMut<'_, T>,
+    cutoff: usize,
+) where
+    T: Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Default + Clone + 'static,
+{
+    assert_eq!(a.cols(), b.rows());
+    assert_eq!((c.rows(), c.cols()), (a.rows(), b.cols()));
+    fast_mul_rec(a, b, c, cutoff, Variant::Strassen);
+}
+
+/// c += a * b with the Winograd variant, which saves 3 of the 18 block additions
+pub fn winograd_mul_into<T>(
+    a: MatrixView<'_, T>,
+    b: MatrixView<'_, T>,
+    c: MatrixViewMut<'_, T>,
+    cutoff: usize,
+) where
+    T: Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Default + Clone + 'static,
+{
+    assert_eq!(a.cols(), b.rows());
+    assert_eq!((c.rows(), c.cols()), (a.rows(), b.cols()));
+    fast_mul_rec(a, b, c, cutoff, Variant::Winograd);
+}
+
+impl<T, S> Matrix<T, S>
+where
+    T: Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Default + Clone + 'static,
+    S: Storage<T> + AsRef<[T]>,
+{
+    /// self * other with [`strassen_mul_into`]
+    pub fn strassen_mul<S2: Storage<T> + AsRef<[T]>>(
+        &self,
+        other: &Matrix<T, S2>,
+        cutoff: usize,
+    ) -> Matrix<T> {
+        let mut result = Matrix::new(self.rows(), other.cols());
+        strassen_mul_into(self.view(), other.view(), result.view_mut(), cutoff);
+        result
+    }
+
+    /// self * other with [`winograd_mul_into`]
+    pub fn winograd_mul<S2: Storage<T> + AsRef<[T]>>(
+        &self,
+        other: &Matrix<T, S2>,
+        cutoff: usize,
+    ) -> Matrix<T> {
+        let mut result = Matrix::new(self.rows(), other.cols());
+        winograd_mul_into(self.view(), other.view(), result.view_mut(), cutoff);
+        result
+    }
+}
+
+/// The error of f32 products of random matrices in [-1, 1), against the product of the
+/// same matrices computed with `simple_mul` in f64.
+pub struct ErrorReport {
+    pub cutoff: usize,
+    /// (n, per method largest relative error of an entry, scaled by the row and column norms)
+    pub rows: Vec<(usize, Vec<(&'static str, f64)>)>,
+}
+
+impl ErrorReport {
+    pub fn measure(sizes: &[usize], cutoff: usize, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let uniform = Uniform::new(-1f32, 1.).unwrap();
+        let rows = sizes
+            .iter()
+            .map(|&n| {
+                let a = Matrix::from_vec(uniform.sample_iter(&mut rng).take(n * n).collect(), n, n);
+                let b = Matrix::from_vec(uniform.sample_iter(&mut rng).take(n * n).collect(), n, n);
+                let exact = a.map(|&x| x as f64).simple_mul(&b.map(|&x| x as f64));
+                // |a| |b|, the scale the rounding errors of any method are relative to
+                let scale = a
+                    .map(|x| x.abs() as f64)
+                    .simple_mul(&b.map(|x| x.abs() as f64));
+                let error = |product: Matrix<f32>| {
+                    let errors = product.zip_with(&exact, |&x, &y| (x as f64 - y).abs());
+                    errors
+                        .zip_with(&scale, |e, s| e / s)
+                        .iter()
+                        .fold(0., |max: f64, &e| max.max(e))
+                };
+                let errors = vec![
+                    ("simple", error(a.simple_mul(&b))),
+                    ("mul", error(a.mul(&b))),
+                    ("strassen", error(a.strassen_mul(&b, cutoff))),
+                    ("winograd", error(a.winograd_mul(&b, cutoff))),
+                ];
+                (n, errors)
+            })
+            .collect();
+        ErrorReport { cutoff, rows }
+    }
+}
+
+impl fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Some((_, first)) = self.rows.first() else {
+            return Ok(());
+        };
+        writeln!(f, "cutoff {}", self.cutoff)?;
+        write!(f, "{:>6}", "n")?;
+        for (name, _) in first {
+            write!(f, "{name:>10}")?;
+        }
+        writeln!(f)?;
+        for (n, errors) in &self.rows {
+            write!(f, "{n:>6}")?;
+            for (_, error) in errors {
+                write!(f, "{error:>10.2e}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(n: usize, m: usize, seed: u64) -> Matrix<i64> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let uniform = Uniform::new(-50, 50).unwrap();
+        Matrix::from_vec(uniform.sample_iter(&mut rng).take(n * m).collect(), n, m)
+    }
+
+    #[test]
+    fn test_fast_mul_exact() {
+        // odd and rectangular sizes, several levels below the cutoff of 4
+        for (m, k, n) in [
+            (1, 1, 1),
+            (8, 8, 8),
+            (37, 21, 16),
+            (64, 65, 63),
+            (30, 90, 45),
+        ] {
+            let a = sample(m, k, 0);
+            let b = sample(k, n, 1);
+            let expected = a.simple_mul(&b);
+            assert_eq!(a.strassen_mul(&b, 4), expected);
+            assert_eq!(a.winograd_mul(&b, 4), expected);
+            assert_eq!(a.winograd_mul(&b, DEFAULT_CUTOFF), expected);
+        }
+
+        // into a submatrix that already holds values
+        let (a, b) = (sample(20, 30, 2), sample(30, 10, 3));
+        let mut c = sample(25, 12, 4);
+        let mut expected = c.clone();
+        fn target(c: &mut Matrix<i64>) -> MatrixViewMut<'_, i64> {
+            c.view_mut().submatrix(2..22, 1..11)
+        }
+        strassen_mul_into(a.view(), b.view(), target(&mut c), 2);
+        a.mul_into(&b, &mut target(&mut expected));
+        assert_eq!(c, expected);
+    }
+
+    #[test]
+    fn test_error_report() {
+        let report = ErrorReport::measure(&[50, 200], 16, 0);
+        for (_, errors) in &report.rows {
+            for &(name, error) in errors {
+                // a few levels of recursion stay close to the cubic error
+                assert!(error < 1e-4, "{name}: {error}");
+            }
+        }
+        assert!(report.to_string().contains("winograd"));
+    }
+}
@@ -0,0 +1,204 @@
+//! A static search tree in van Emde Boas layout.
+//!
+//! The keys form a perfect binary search tree, padded with copies of the largest key.
+//! A tree of height h is stored as its top tree of height h / 2 followed by the bottom
+//! trees hanging from it, each laid out the same way, so a root-to-leaf path crosses
+//! O(log_B n) blocks for every block size B at once. The descent finds the position of
+//! a child with the per-depth tables of Brodal, Fagerberg and Jacob instead of storing
+//! pointers: the node at depth d is `bottom_size[d]` slots into the bottom tree chosen
+//! by the low bits of its breadth-first index, after the top tree rooted at
+//! `top_depth[d]`.
+
+/// A sorted set of keys stored in van Emde Boas order.
+#[derive(Debug, Clone)]
+pub struct VebTree<T> {
+    /// 2^height - 1 keys in van Emde Boas order
+    keys: Vec<T>,
+    /// the number of keys before padding
+    len: usize,
+    height: usize,
+    /// per depth: the depth of the root of the top tree the node's bottom tree hangs from
+    top_depth: Vec<usize>,
+    /// per depth: the size of that top tree
+    top_size: Vec<usize>,
+    /// per depth: the size of the bottom tree rooted at the node
+    bottom_size: Vec<usize>,
+}
+
+/// fill the depth tables for the subtree of `height` levels with its root at `depth`
+fn depth_tables(
+    depth: usize,
+    height: usize,
+    top_depth: &mut [usize],
+    top_size: &mut [usize],
+    bottom_size: &mut [usize],
+) {
+    if height <= 1 {
+        return;
+    }
+    let top = height / 2;
+    let bottom = height - top;
+    top_depth[depth + top] = depth;
+    top_size[depth + top] = (1 << top) - 1;
+    bottom_size[depth + top] = (1 << bottom) - 1;
+    depth_tables(depth, top, top_depth, top_size, bottom_size);
+    depth_tables(depth + top, bottom, top_depth, top_size, bottom_size);
+}
+
+/// call `f` with the breadth-first index (from 1) of every node of the subtree of
+/// `height` levels rooted at `root`, in van Emde Boas order
+fn for_each_in_layout(root: usize, height: usize, f: &mut impl FnMut(usize)) {
+    if height == 0 {
+        return;
+    }
+    if height == 1 {
+        f(root);
+        return;
+    }
+    let top = height / 2;
+    for_each_in_layout(root, top, f);
+    for k in 0..1 << top {
+        for_each_in_layout(root << top | k, height - top, f);
+    }
+}
+
+impl<T: Ord + Clone> VebTree<T> {
+    /// build the tree from keys in non-decreasing order
+    pub fn from_sorted(sorted: &[T]) -> Self {
+        assert!(sorted.is_sorted(), "keys must be sorted");
+        let len = sorted.len();
+        let height = (usize::BITS - len.leading_zeros()) as usize;
+        let (mut top_depth, mut top_size, mut bottom_size) =
+            (vec![0; height], vec![0; height], vec![0; height]);
+        depth_tables(0, height, &mut top_depth, &mut top_size, &mut bottom_size);
+
+        let mut keys = Vec::with_capacity((1 << height) - 1);
+        for_each_in_layout(1, height, &mut |node| {
+            let rank = Self::rank(node, height);
+            keys.push(sorted[rank.min(len - 1)].clone());
+        });
+        VebTree {
+            keys,
+            len,
+            height,
+            top_depth,
+            top_size,
+            bottom_size,
+        }
+    }
+
+    /// the in-order position of a node in a perfect tree of `height` levels
+    fn rank(node: usize, height: usize) -> usize {
+        let depth = (usize::BITS - 1 - node.leading_zeros()) as usize;
+        ((2 * (node - (1 << depth)) + 1) << (height - 1 - depth)) - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// (the number of keys less than x, the slot of the first key not less than x)
+    fn search(&self, x: &T) -> (usize, Option<usize>) {
+        let mut positions = [0; usize::BITS as usize];
+        let mut node = 1;
+        let mut found = None;
+        for depth in 0..self.height {
+            let position = if depth == 0 {
+                0
+            } else {
+                let top = self.top_size[depth];
+                positions[self.top_depth[depth]] + top + (node & top) * self.bottom_size[depth]
+            };
+            positions[depth] = position;
+            if self.keys[position] >= *x {
+                found = Some((node, position));
+                node *= 2;
+            } else {
+                node = 2 * node + 1;
+            }
+        }
+        match found {
+            Some((node, position)) => {
+                let rank = Self::rank(node, self.height);
+                // the padding only repeats the largest key
+                if rank < self.len {
+                    (rank, Some(position))
+                } else {
+                    (self.len, None)
+                }
+            }
+            None => (self.len, None),
+        }
+    }
+
+    /// the number of keys less than x, the same as `partition_point(|k| k < x)` on the
+    /// sorted keys
+    pub fn lower_bound(&self, x: &T) -> usize {
+        self.search(x).0
+    }
+
+    pub fn contains(&self, x: &T) -> bool {
+        self.search(x)
+            .1
+            .is_some_and(|position| self.keys[position] == *x)
+    }
+
+    /// the keys back in sorted order
+    pub fn to_sorted(&self) -> Vec<T> {
+        let mut sorted = vec![None; self.len];
+        let mut position = 0;
+        for_each_in_layout(1, self.height, &mut |node| {
+            let rank = Self::rank(node, self.height);
+            if rank < self.len {
+                sorted[rank] = Some(self.keys[position].clone());
+            }
+            position += 1;
+        });
+        sorted.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::distr::{Distribution, Uniform};
+    use rand::{SeedableRng, rngs::StdRng};
+
+    #[test]
+    fn test_veb_tree_small() {
+        // every height, with and without padding
+        for n in 0..=70u32 {
+            let sorted: Vec<u32> = (0..n).map(|x| 2 * x + 1).collect();
+            let tree = VebTree::from_sorted(&sorted);
+            assert_eq!(tree.len(), n as usize);
+            assert_eq!(tree.to_sorted(), sorted);
+            for x in 0..2 * n + 3 {
+                assert_eq!(tree.lower_bound(&x), sorted.partition_point(|&k| k < x));
+                assert_eq!(tree.contains(&x), x % 2 == 1 && x < 2 * n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_veb_tree_duplicates() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let uniform = Uniform::new(0, 1000).unwrap();
+        let mut keys: Vec<i32> = uniform.sample_iter(&mut rng).take(5000).collect();
+        keys.sort();
+        let tree = VebTree::from_sorted(&keys);
+        assert_eq!(tree.to_sorted(), keys);
+        for x in -1..1002 {
+            assert_eq!(tree.lower_bound(&x), keys.partition_point(|&k| k < x));
+            assert_eq!(tree.contains(&x), keys.binary_search(&x).is_ok());
+        }
+
+        let strings = ["ant", "bee", "cat", "cow", "dog"].map(String::from);
+        let tree = VebTree::from_sorted(&strings);
+        assert!(tree.contains(&"cow".to_string()));
+        assert_eq!(tree.lower_bound(&"cub".to_string()), 4);
+    }
+}
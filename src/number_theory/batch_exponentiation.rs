@@ -0,0 +1,125 @@
+use super::Montgomery;
+
+/// a_i^exp mod n for every a_i, one element at a time
+///
+/// Results are in [0, n).
+pub fn batch_pow_scalar(a: &[u32], exp: u64, montgomery: &Montgomery) -> Vec<u32> {
+    let n = montgomery.modulus();
+    a.iter()
+        .map(|&x| montgomery.to(montgomery.pow(montgomery.from(x), exp)) % n)
+        .collect()
+}
+
+/// a_i^exp mod n for every a_i
+///
+/// The exponent is shared, so all lanes follow the same square-and-multiply schedule
+/// and eight of them run in one AVX2 register. Falls back to [`batch_pow_scalar`]
+/// when AVX2 is not available. Results are in [0, n).
+pub fn batch_pow(a: &[u32], exp: u64, montgomery: &Montgomery) -> Vec<u32> {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("avx2") {
+        let mut result = vec![0; a.len()];
+        let simd_len = a.len() / 8 * 8;
+        // SAFETY: AVX2 is supported
+        unsafe {
+            avx2::batch_pow(&a[..simd_len], exp, montgomery, &mut result[..simd_len]);
+        }
+        result[simd_len..].copy_from_slice(&batch_pow_scalar(&a[simd_len..], exp, montgomery));
+        return result;
+    }
+    batch_pow_scalar(a, exp, montgomery)
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use super::Montgomery;
+    use std::arch::x86_64::*;
+
+    /// montgomery reduce of lhs * rhs in each 32-bit lane,
+    /// the same as `Montgomery::mul` with the same [0, 2 * n - 2] range
+    #[inline]
+    #[target_feature(enable = "avx2")]
+    fn mul(lhs: __m256i, rhs: __m256i, n: __m256i, inv_n: __m256i) -> __m256i {
+        // _mm256_mul_epu32 only multiplies the even lanes, so do the odd ones shifted down
+        let x_even = _mm256_mul_epu32(lhs, rhs);
+        let x_odd = _mm256_mul_epu32(_mm256_srli_epi64(lhs, 32), _mm256_srli_epi64(rhs, 32));
+        // q = x * n^(-1) mod R, only the low 32 bits of x are needed
+        let q = _mm256_mullo_epi32(_mm256_mullo_epi32(lhs, rhs), inv_n);
+        // m = q * n / R
+        let m_even = _mm256_mul_epu32(q, n);
+        let m_odd = _mm256_mul_epu32(_mm256_srli_epi64(q, 32), n);
+        // take the high halves of the 64-bit products back to 32-bit lanes
+        let x_high = _mm256_blend_epi32(_mm256_srli_epi64(x_even, 32), x_odd, 0b1010_1010);
+        let m = _mm256_blend_epi32(_mm256_srli_epi64(m_even, 32), m_odd, 0b1010_1010);
+        _mm256_add_epi32(x_high, _mm256_sub_epi32(n, m))
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub(super) fn batch_pow(a: &[u32], exp: u64, montgomery: &Montgomery, result: &mut [u32]) {
+        debug_assert!(a.len().is_multiple_of(8) && a.len() == result.len());
+        let n = _mm256_set1_epi32(montgomery.modulus() as i32);
+        let inv_n = _mm256_set1_epi32(montgomery.inv_n() as i32);
+        let sqr_r = _mm256_set1_epi32(montgomery.sqr_r() as i32);
+        let one = _mm256_set1_epi32(1);
+        // 1 in montgomery space, shared by all the lanes
+        let mont_one = mul(one, sqr_r, n, inv_n);
+
+        for (chunk, out) in a.chunks_exact(8).zip(result.chunks_exact_mut(8)) {
+            // SAFETY: chunk has exactly 8 u32s
+            let x = unsafe { _mm256_loadu_si256(chunk.as_ptr() as *const __m256i) };
+            let mut base = mul(x, sqr_r, n, inv_n);
+            let mut acc = mont_one;
+            let mut e = exp;
+            while e > 0 {
+                if e & 1 == 1 {
+                    acc = mul(acc, base, n, inv_n);
+                }
+                base = mul(base, base, n, inv_n);
+                e >>= 1;
+            }
+            // back to normal space, the result is in (0, n], so map n to 0
+            let y = mul(acc, one, n, inv_n);
+            let y = _mm256_min_epu32(y, _mm256_sub_epi32(y, n));
+            // SAFETY: out has exactly 8 u32s
+            unsafe { _mm256_storeu_si256(out.as_mut_ptr() as *mut __m256i, y) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::binpow_iter;
+    use rand::SeedableRng;
+    use rand::distr::{Distribution, Uniform};
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn test_batch_pow() {
+        const M: u64 = 1_000_000_007;
+        let montgomery = Montgomery::new(M as i32);
+        let mut rng = StdRng::seed_from_u64(0);
+        let uniform = Uniform::new(0, M as u32).unwrap();
+        let mut a: Vec<u32> = uniform.sample_iter(&mut rng).take(1003).collect();
+        a[0] = 0;
+        a[1] = 1;
+        a[2] = M as u32 - 1;
+        for exp in [0, 1, 2, 3, 12345, M - 2, u64::MAX] {
+            let expected: Vec<u32> = a
+                .iter()
+                .map(|&x| binpow_iter::<M>(x as u64, exp) as u32)
+                .collect();
+            assert_eq!(batch_pow_scalar(&a, exp, &montgomery), expected);
+            assert_eq!(batch_pow(&a, exp, &montgomery), expected);
+        }
+        assert!(batch_pow(&[], 5, &montgomery).is_empty());
+    }
+
+    #[test]
+    fn test_batch_pow_small_modulus() {
+        let montgomery = Montgomery::new(7);
+        let a: Vec<u32> = (0..16).map(|x| x % 7).collect();
+        let expected: Vec<u32> = a.iter().map(|&x| x.pow(5) % 7).collect();
+        assert_eq!(batch_pow(&a, 5, &montgomery), expected);
+    }
+}
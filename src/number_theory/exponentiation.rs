@@ -0,0 +1,361 @@
+//! Exponentiation variants generic over the modular multiplication backend.
+//!
+//! Exponents are passed as little-endian u64 limbs, so `&[e]` works for a u64
+//! and `e.limbs()` for a [`Uint`].
+
+use super::{Montgomery, MontgomerySpace, Uint, WideMontgomery, WideMontgomerySpace};
+use crate::arithmetic::{barrett_reduction_mod, cal_barrett_reduction_magic_number};
+
+/// A ring of residues in which the exponentiation runs.
+pub trait ModularBackend {
+    type Element: Copy;
+
+    /// the multiplicative identity
+    fn one(&self) -> Self::Element;
+
+    fn mul(&self, lhs: &Self::Element, rhs: &Self::Element) -> Self::Element;
+
+    #[inline]
+    fn square(&self, x: &Self::Element) -> Self::Element {
+        self.mul(x, x)
+    }
+}
+
+impl ModularBackend for Montgomery {
+    type Element = MontgomerySpace;
+
+    #[inline]
+    fn one(&self) -> MontgomerySpace {
+        self.from(1)
+    }
+
+    #[inline]
+    fn mul(&self, lhs: &MontgomerySpace, rhs: &MontgomerySpace) -> MontgomerySpace {
+        Montgomery::mul(self, *lhs, *rhs)
+    }
+}
+
+impl<const L: usize> ModularBackend for WideMontgomery<L> {
+    type Element = WideMontgomerySpace<L>;
+
+    #[inline]
+    fn one(&self) -> WideMontgomerySpace<L> {
+        self.from(&Uint::ONE)
+    }
+
+    #[inline]
+    fn mul(
+        &self,
+        lhs: &WideMontgomerySpace<L>,
+        rhs: &WideMontgomerySpace<L>,
+    ) -> WideMontgomerySpace<L> {
+        WideMontgomery::mul(self, lhs, rhs)
+    }
+}
+
+/// Plain residues in [0, n) reduced with Barrett reduction.
+#[derive(Debug, Clone, Copy)]
+pub struct Barrett {
+    n: u32,
+    magic_number: u64,
+}
+
+impl Barrett {
+    pub const fn new(n: u32) -> Self {
+        Barrett {
+            n,
+            magic_number: cal_barrett_reduction_magic_number(n),
+        }
+    }
+
+    #[inline]
+    pub const fn modulus(&self) -> u32 {
+        self.n
+    }
+}
+
+impl ModularBackend for Barrett {
+    type Element = u32;
+
+    #[inline]
+    fn one(&self) -> u32 {
+        1 % self.n
+    }
+
+    #[inline]
+    fn mul(&self, lhs: &u32, rhs: &u32) -> u32 {
+        barrett_reduction_mod(*lhs as u64 * *rhs as u64, self.n, self.magic_number)
+    }
+}
+
+/// number of significant bits of exp
+#[inline]
+fn exp_bits(exp: &[u64]) -> u32 {
+    for i in (0..exp.len()).rev() {
+        if exp[i] != 0 {
+            return 64 * i as u32 + 64 - exp[i].leading_zeros();
+        }
+    }
+    0
+}
+
+#[inline]
+fn exp_bit(exp: &[u64], i: u32) -> bool {
+    exp.get(i as usize / 64)
+        .is_some_and(|&limb| (limb >> (i % 64)) & 1 == 1)
+}
+
+/// bits [lo, lo + width) of exp as a number
+#[inline]
+fn exp_window(exp: &[u64], lo: u32, width: u32) -> usize {
+    let mut result = 0;
+    for k in (0..width).rev() {
+        result = (result << 1) | exp_bit(exp, lo + k) as usize;
+    }
+    result
+}
+
+/// left-to-right square-and-multiply, the reference for the other variants
+pub fn pow_binary<B: ModularBackend>(backend: &B, base: &B::Element, exp: &[u64]) -> B::Element {
+    let mut result = backend.one();
+    for i in (0..exp_bits(exp)).rev() {
+        result = backend.square(&result);
+        if exp_bit(exp, i) {
+            result = backend.mul(&result, base);
+        }
+    }
+    result
+}
+
+/// k-ary (fixed window) exponentiation with a table of base^0..base^(2^window - 1)
+pub fn pow_k_ary<B: ModularBackend>(
+    backend: &B,
+    base: &B::Element,
+    exp: &[u64],
+    window: u32,
+) -> B::Element {
+    assert!((1..=8).contains(&window));
+    let mut table = Vec::with_capacity(1 << window);
+    table.push(backend.one());
+    for i in 1..1 << window {
+        table.push(backend.mul(&table[i - 1], base));
+    }
+
+    let bits = exp_bits(exp);
+    let mut result = backend.one();
+    for w in (0..bits.div_ceil(window)).rev() {
+        for _ in 0..window {
+            result = backend.square(&result);
+        }
+        let digit = exp_window(exp, w * window, window);
+        if digit != 0 {
+            result = backend.mul(&result, &table[digit]);
+        }
+    }
+    result
+}
+
+/// sliding-window exponentiation with a table of the odd powers base^1, base^3, ..., base^(2^window - 1)
+pub fn pow_sliding_window<B: ModularBackend>(
+    backend: &B,
+    base: &B::Element,
+    exp: &[u64],
+    window: u32,
+) -> B::Element {
+    assert!((1..=8).contains(&window));
+    let base_sqr = backend.square(base);
+    let mut table = Vec::with_capacity(1 << (window - 1));
+    table.push(*base);
+    for i in 1..1 << (window - 1) {
+        table.push(backend.mul(&table[i - 1], &base_sqr));
+    }
+
+    let mut result = backend.one();
+    let mut i = exp_bits(exp) as i64 - 1;
+    while i >= 0 {
+        if !exp_bit(exp, i as u32) {
+            result = backend.square(&result);
+            i -= 1;
+            continue;
+        }
+        // the longest window [j, i] that ends with a set bit
+        let mut j = (i - window as i64 + 1).max(0);
+        while !exp_bit(exp, j as u32) {
+            j += 1;
+        }
+        let width = (i - j + 1) as u32;
+        for _ in 0..width {
+            result = backend.square(&result);
+        }
+        let digit = exp_window(exp, j as u32, width);
+        result = backend.mul(&result, &table[digit >> 1]);
+        i = j - 1;
+    }
+    result
+}
+
+/// Fixed-base comb (Lim-Lee) exponentiation for repeated g^x
+///
+/// The exponent bits are arranged in `teeth` rows of `span` columns, and
+/// table[v] = prod_{k in v} g^(2^(k * span)), so a power takes `span` squarings and
+/// at most `span` multiplications.
+pub struct FixedBaseComb<E> {
+    table: Vec<E>,
+    teeth: u32,
+    span: u32,
+}
+
+impl<E: Copy> FixedBaseComb<E> {
+    /// precompute the table for exponents of at most `max_bits` bits
+    pub fn new<B: ModularBackend<Element = E>>(
+        backend: &B,
+        base: &E,
+        max_bits: u32,
+        teeth: u32,
+    ) -> Self {
+        assert!((1..=12).contains(&teeth));
+        let span = max_bits.div_ceil(teeth).max(1);
+        // g^(2^(k * span)) for each tooth k
+        let mut tooth_powers = Vec::with_capacity(teeth as usize);
+        let mut g = *base;
+        for _ in 0..teeth {
+            tooth_powers.push(g);
+            for _ in 0..span {
+                g = backend.square(&g);
+            }
+        }
+        let mut table = Vec::with_capacity(1 << teeth);
+        table.push(backend.one());
+        for v in 1..1usize << teeth {
+            let k = v.trailing_zeros() as usize;
+            table.push(backend.mul(&table[v & (v - 1)], &tooth_powers[k]));
+        }
+        FixedBaseComb { table, teeth, span }
+    }
+
+    pub fn pow<B: ModularBackend<Element = E>>(&self, backend: &B, exp: &[u64]) -> E {
+        assert!(
+            exp_bits(exp) <= self.teeth * self.span,
+            "exponent is wider than the comb"
+        );
+        let mut result = backend.one();
+        for j in (0..self.span).rev() {
+            result = backend.square(&result);
+            let mut v = 0;
+            for k in (0..self.teeth).rev() {
+                v = (v << 1) | exp_bit(exp, k * self.span + j) as usize;
+            }
+            if v != 0 {
+                result = backend.mul(&result, &self.table[v]);
+            }
+        }
+        result
+    }
+}
+
+/// a^ea * b^eb with one shared squaring chain (Shamir's trick)
+pub fn multi_pow<B: ModularBackend>(
+    backend: &B,
+    a: &B::Element,
+    ea: &[u64],
+    b: &B::Element,
+    eb: &[u64],
+) -> B::Element {
+    let ab = backend.mul(a, b);
+    let mut result = backend.one();
+    for i in (0..exp_bits(ea).max(exp_bits(eb))).rev() {
+        result = backend.square(&result);
+        match (exp_bit(ea, i), exp_bit(eb, i)) {
+            (true, true) => result = backend.mul(&result, &ab),
+            (true, false) => result = backend.mul(&result, a),
+            (false, true) => result = backend.mul(&result, b),
+            (false, false) => {}
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::number_theory::{U256, binpow_iter};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const M: u64 = 1_000_000_007;
+
+    #[test]
+    fn test_pow_variants_u32() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let montgomery = Montgomery::new(M as i32);
+        let barrett = Barrett::new(M as u32);
+        for _ in 0..200 {
+            let base = rng.random_range(0..M);
+            let exp: u64 = rng.random::<u64>() >> rng.random_range(0..64);
+            let expected = binpow_iter::<M>(base, exp) as u32;
+
+            let mb = montgomery.from(base as u32);
+            let to = |x| montgomery.to(x) % M as u32;
+            assert_eq!(to(pow_binary(&montgomery, &mb, &[exp])), expected);
+            assert_eq!(pow_binary(&barrett, &(base as u32), &[exp]), expected);
+            for window in [1, 3, 4, 5, 8] {
+                assert_eq!(to(pow_k_ary(&montgomery, &mb, &[exp], window)), expected);
+                assert_eq!(
+                    to(pow_sliding_window(&montgomery, &mb, &[exp], window)),
+                    expected
+                );
+                assert_eq!(
+                    pow_sliding_window(&barrett, &(base as u32), &[exp], window),
+                    expected
+                );
+            }
+            for teeth in [1, 4, 8] {
+                let comb = FixedBaseComb::new(&montgomery, &mb, 64, teeth);
+                assert_eq!(to(comb.pow(&montgomery, &[exp])), expected);
+            }
+        }
+        assert_eq!(pow_k_ary(&barrett, &5, &[0], 4), 1);
+        assert_eq!(pow_sliding_window(&barrett, &5, &[], 4), 1);
+    }
+
+    #[test]
+    fn test_multi_pow() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let barrett = Barrett::new(M as u32);
+        for _ in 0..100 {
+            let (a, b) = (rng.random_range(0..M), rng.random_range(0..M));
+            let (ea, eb) = (rng.random::<u64>() >> 7, rng.random::<u64>());
+            let expected = binpow_iter::<M>(a, ea) * binpow_iter::<M>(b, eb) % M;
+            assert_eq!(
+                multi_pow(&barrett, &(a as u32), &[ea], &(b as u32), &[eb]) as u64,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_pow_variants_wide() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let p = U256::from_hex("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F");
+        let montgomery = WideMontgomery::new(p);
+        let g = montgomery.from(&U256::from_u64(7));
+        let comb = FixedBaseComb::new(&montgomery, &g, 256, 6);
+        for _ in 0..5 {
+            let exp = U256::from_limbs(std::array::from_fn(|_| rng.random()));
+            let expected = montgomery.pow(&g, &exp);
+            assert_eq!(pow_binary(&montgomery, &g, exp.limbs()), expected);
+            assert_eq!(pow_k_ary(&montgomery, &g, exp.limbs(), 4), expected);
+            assert_eq!(
+                pow_sliding_window(&montgomery, &g, exp.limbs(), 5),
+                expected
+            );
+            assert_eq!(comb.pow(&montgomery, exp.limbs()), expected);
+            let h = montgomery.from(&U256::from_u64(11));
+            let expected = montgomery.mul(&expected, &montgomery.pow(&h, &p));
+            assert_eq!(
+                multi_pow(&montgomery, &g, exp.limbs(), &h, p.limbs()),
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,627 @@
+//! Formal power series modulo an NTT prime.
+//!
+//! Every fast operation has an O(n^2) `*_naive` counterpart used for differential tests.
+//! Coefficients are stored from the lowest degree and must be in [0, p). They are moved
+//! into montgomery space on the way in and back on the way out, so all the arithmetic in
+//! between is [`Montgomery`] arithmetic.
+
+use super::{Montgomery, MontgomerySpace, Ntt};
+
+type Poly = Vec<MontgomerySpace>;
+
+/// the coefficients in montgomery space
+fn to_space(a: &[u32], mg: &Montgomery) -> Poly {
+    a.iter().map(|&x| mg.from(x)).collect()
+}
+
+/// x back in [0, p)
+#[inline]
+fn value(x: MontgomerySpace, mg: &Montgomery) -> u32 {
+    mg.to(x) % mg.modulus()
+}
+
+fn from_space(a: &[MontgomerySpace], mg: &Montgomery) -> Vec<u32> {
+    a.iter().map(|&x| value(x, mg)).collect()
+}
+
+/// x^(-1) by Fermat's little theorem
+fn inverse(x: MontgomerySpace, mg: &Montgomery) -> MontgomerySpace {
+    assert!(value(x, mg) != 0, "0 has no inverse");
+    mg.pow(x, (mg.modulus() - 2) as u64)
+}
+
+/// 1^(-1), 2^(-1), ..., n^(-1) in O(n), index 0 is unused
+fn inverses_up_to(n: usize, mg: &Montgomery) -> Poly {
+    let modulus = mg.modulus();
+    let mut inv = vec![mg.from(0), mg.from(1)];
+    for i in 2..=n as u32 {
+        let q = modulus / i;
+        let r = (modulus % i) as usize;
+        inv.push(mg.mul(mg.from(modulus - q), inv[r]));
+    }
+    inv.truncate(n + 1);
+    inv
+}
+
+/// modular square root by Tonelli-Shanks, `modulus` must be an odd prime below 2^31
+pub fn sqrt_mod(a: u32, modulus: u32) -> Option<u32> {
+    let a = a % modulus;
+    if a == 0 {
+        return Some(0);
+    }
+    let mg = Montgomery::new(modulus as i32);
+    let is_one = |x| value(x, &mg) == 1;
+    let half = ((modulus - 1) / 2) as u64;
+    let a = mg.from(a);
+    if !is_one(mg.pow(a, half)) {
+        return None;
+    }
+    let s = (modulus - 1).trailing_zeros();
+    let q = (modulus - 1) >> s;
+    let mut z = 2;
+    while value(mg.pow(mg.from(z), half), &mg) != modulus - 1 {
+        z += 1;
+    }
+    let mut m = s;
+    let mut c = mg.pow(mg.from(z), q as u64);
+    let mut t = mg.pow(a, q as u64);
+    let mut r = mg.pow(a, q.div_ceil(2) as u64);
+    while !is_one(t) {
+        let mut i = 0;
+        let mut t2 = t;
+        while !is_one(t2) {
+            t2 = mg.mul(t2, t2);
+            i += 1;
+        }
+        let b = mg.pow(c, 1 << (m - i - 1));
+        m = i;
+        c = mg.mul(b, b);
+        t = mg.mul(t, c);
+        r = mg.mul(r, b);
+    }
+    let r = value(r, &mg);
+    Some(r.min(modulus - r))
+}
+
+fn derivative(a: &[MontgomerySpace], mg: &Montgomery) -> Poly {
+    a.iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, &x)| mg.mul(x, mg.from(i as u32)))
+        .collect()
+}
+
+fn integral(a: &[MontgomerySpace], mg: &Montgomery) -> Poly {
+    let inv = inverses_up_to(a.len(), mg);
+    let mut result = Vec::with_capacity(a.len() + 1);
+    result.push(mg.from(0));
+    for (i, &x) in a.iter().enumerate() {
+        result.push(mg.mul(x, inv[i + 1]));
+    }
+    result
+}
+
+/// a * b mod x^n
+fn mul_truncated(a: &[MontgomerySpace], b: &[MontgomerySpace], n: usize, ntt: &Ntt) -> Poly {
+    let a = &a[..a.len().min(n)];
+    let b = &b[..b.len().min(n)];
+    let mut c = ntt.convolution_montgomery(a, b);
+    c.resize(n, ntt.montgomery().from(0));
+    c
+}
+
+fn inv(a: &[MontgomerySpace], n: usize, ntt: &Ntt) -> Poly {
+    let mg = ntt.montgomery();
+    let (zero, two) = (mg.from(0), mg.from(2));
+    let mut b = vec![inverse(a[0], mg)];
+    let mut len = 1;
+    while len < n {
+        len *= 2;
+        let ab = mul_truncated(a, &b, len, ntt);
+        let mut two_minus_ab: Poly = ab.iter().map(|&x| mg.sub(zero, x)).collect();
+        two_minus_ab[0] = mg.add(two_minus_ab[0], two);
+        b = mul_truncated(&b, &two_minus_ab, len, ntt);
+    }
+    b.truncate(n);
+    b
+}
+
+/// a^(-1) mod x^n by Newton iteration b' = b * (2 - a * b), a[0] must be nonzero
+pub fn poly_inv(a: &[u32], n: usize, ntt: &Ntt) -> Vec<u32> {
+    assert!(!a.is_empty() && a[0] != 0);
+    let mg = ntt.montgomery();
+    from_space(&inv(&to_space(a, mg), n, ntt), mg)
+}
+
+pub fn poly_inv_naive(a: &[u32], n: usize, modulus: u32) -> Vec<u32> {
+    assert!(!a.is_empty() && a[0] != 0);
+    let mg = Montgomery::new(modulus as i32);
+    let a = to_space(a, &mg);
+    let inv_a0 = inverse(a[0], &mg);
+    let mut b = Vec::with_capacity(n);
+    for k in 0..n {
+        if k == 0 {
+            b.push(inv_a0);
+            continue;
+        }
+        let mut sum = mg.from(0);
+        for j in 1..=k.min(a.len() - 1) {
+            sum = mg.add(sum, mg.mul(a[j], b[k - j]));
+        }
+        b.push(mg.mul(mg.sub(mg.from(0), sum), inv_a0));
+    }
+    from_space(&b, &mg)
+}
+
+fn div_rem(a: &[MontgomerySpace], b: &[MontgomerySpace], ntt: &Ntt) -> (Poly, Poly) {
+    if a.len() < b.len() {
+        return (vec![], a.to_vec());
+    }
+    let mg = ntt.montgomery();
+    let q_len = a.len() - b.len() + 1;
+    let rev_a: Poly = a.iter().rev().copied().collect();
+    let rev_b: Poly = b.iter().rev().copied().collect();
+    let rev_b_inv = inv(&rev_b, q_len, ntt);
+    let mut q = mul_truncated(&rev_a, &rev_b_inv, q_len, ntt);
+    q.reverse();
+    let r_len = b.len() - 1;
+    let bq = mul_truncated(b, &q, r_len, ntt);
+    let r = a[..r_len]
+        .iter()
+        .zip(&bq)
+        .map(|(&x, &y)| mg.sub(x, y))
+        .collect();
+    (q, r)
+}
+
+/// (q, r) with a = b * q + r, deg r < deg b
+///
+/// The last coefficient of b must be nonzero.
+/// q has max(0, len(a) - len(b) + 1) coefficients and r has min(len(a), len(b) - 1).
+pub fn poly_div_rem(a: &[u32], b: &[u32], ntt: &Ntt) -> (Vec<u32>, Vec<u32>) {
+    assert!(b.last().is_some_and(|&x| x != 0));
+    let mg = ntt.montgomery();
+    let (q, r) = div_rem(&to_space(a, mg), &to_space(b, mg), ntt);
+    (from_space(&q, mg), from_space(&r, mg))
+}
+
+pub fn poly_div_rem_naive(a: &[u32], b: &[u32], modulus: u32) -> (Vec<u32>, Vec<u32>) {
+    assert!(b.last().is_some_and(|&x| x != 0));
+    if a.len() < b.len() {
+        return (vec![], a.to_vec());
+    }
+    let mg = Montgomery::new(modulus as i32);
+    let b = to_space(b, &mg);
+    let inv_lead = inverse(*b.last().unwrap(), &mg);
+    let mut r = to_space(a, &mg);
+    let mut q = vec![mg.from(0); a.len() - b.len() + 1];
+    for i in (0..q.len()).rev() {
+        let coef = mg.mul(r[i + b.len() - 1], inv_lead);
+        q[i] = coef;
+        for (j, &y) in b.iter().enumerate() {
+            r[i + j] = mg.sub(r[i + j], mg.mul(coef, y));
+        }
+    }
+    r.truncate(b.len() - 1);
+    (from_space(&q, &mg), from_space(&r, &mg))
+}
+
+fn log(a: &[MontgomerySpace], n: usize, ntt: &Ntt) -> Poly {
+    if n == 0 {
+        return vec![];
+    }
+    let mg = ntt.montgomery();
+    let da = derivative(&a[..a.len().min(n)], mg);
+    let inv_a = inv(a, n, ntt);
+    let mut result = integral(&mul_truncated(&da, &inv_a, n - 1, ntt), mg);
+    result.truncate(n);
+    result
+}
+
+/// ln(a) mod x^n = ∫ a' / a, a[0] must be 1
+pub fn poly_log(a: &[u32], n: usize, ntt: &Ntt) -> Vec<u32> {
+    assert!(a.first() == Some(&1));
+    let mg = ntt.montgomery();
+    from_space(&log(&to_space(a, mg), n, ntt), mg)
+}
+
+/// uses k * b_k = k * a_k - sum_{j=1}^{k-1} j * b_j * a_{k-j}
+pub fn poly_log_naive(a: &[u32], n: usize, modulus: u32) -> Vec<u32> {
+    assert!(a.first() == Some(&1));
+    let mg = Montgomery::new(modulus as i32);
+    let a = to_space(a, &mg);
+    let inv = inverses_up_to(n, &mg);
+    let coef = |i: usize| a.get(i).copied().unwrap_or(mg.from(0));
+    let index = |i: usize| mg.from(i as u32);
+    let mut b = vec![mg.from(0); n];
+    for k in 1..n {
+        let mut sum = mg.mul(index(k), coef(k));
+        for (j, &b_j) in b.iter().enumerate().take(k).skip(1) {
+            sum = mg.sub(sum, mg.mul(mg.mul(index(j), b_j), coef(k - j)));
+        }
+        b[k] = mg.mul(sum, inv[k]);
+    }
+    from_space(&b, &mg)
+}
+
+/// exp(a) mod x^n by Newton iteration b' = b * (1 - ln b + a), a[0] must be 0
+pub fn poly_exp(a: &[u32], n: usize, ntt: &Ntt) -> Vec<u32> {
+    assert!(a.first().is_none_or(|&x| x == 0));
+    let mg = ntt.montgomery();
+    let a = to_space(a, mg);
+    let (zero, one) = (mg.from(0), mg.from(1));
+    let mut b = vec![one];
+    let mut len = 1;
+    while len < n {
+        len *= 2;
+        let ln_b = log(&b, len, ntt);
+        let mut t: Poly = (0..len)
+            .map(|i| mg.sub(a.get(i).copied().unwrap_or(zero), ln_b[i]))
+            .collect();
+        t[0] = mg.add(t[0], one);
+        b = mul_truncated(&b, &t, len, ntt);
+    }
+    b.truncate(n);
+    from_space(&b, mg)
+}
+
+/// uses k * b_k = sum_{j=1}^{k} j * a_j * b_{k-j}
+pub fn poly_exp_naive(a: &[u32], n: usize, modulus: u32) -> Vec<u32> {
+    assert!(a.first().is_none_or(|&x| x == 0));
+    let mg = Montgomery::new(modulus as i32);
+    let a = to_space(a, &mg);
+    let inv = inverses_up_to(n, &mg);
+    let coef = |i: usize| a.get(i).copied().unwrap_or(mg.from(0));
+    let mut b = vec![mg.from(0); n];
+    if n > 0 {
+        b[0] = mg.from(1);
+    }
+    for k in 1..n {
+        let mut sum = mg.from(0);
+        for j in 1..=k {
+            let t = mg.mul(mg.mul(mg.from(j as u32), coef(j)), b[k - j]);
+            sum = mg.add(sum, t);
+        }
+        b[k] = mg.mul(sum, inv[k]);
+    }
+    from_space(&b, &mg)
+}
+
+/// split a into x^(2k) * c with c[0] != 0, and take sqrt(c[0])
+fn sqrt_prepare(a: &[u32], n: usize, modulus: u32) -> Option<(usize, &[u32], u32)> {
+    let Some(zeros) = a.iter().position(|&x| x != 0) else {
+        return Some((n, &[], 0));
+    };
+    if zeros >= 2 * n {
+        return Some((n, &[], 0));
+    }
+    if zeros % 2 == 1 {
+        return None;
+    }
+    let rest = &a[zeros..];
+    let root = sqrt_mod(rest[0], modulus)?;
+    Some((zeros / 2, rest, root))
+}
+
+/// sqrt(a) mod x^n by Newton iteration b' = (b + a / b) / 2,
+/// returns None when a has no square root
+pub fn poly_sqrt(a: &[u32], n: usize, ntt: &Ntt) -> Option<Vec<u32>> {
+    let mg = ntt.montgomery();
+    let (shift, a, root) = sqrt_prepare(a, n, ntt.modulus())?;
+    let mut result = vec![0; shift];
+    if shift == n {
+        return Some(result);
+    }
+    let n = n - shift;
+    let a = to_space(a, mg);
+    let inv_two = mg.from(ntt.modulus().div_ceil(2));
+    let mut b = vec![mg.from(root)];
+    let mut len = 1;
+    while len < n {
+        len *= 2;
+        let a_over_b = mul_truncated(&a, &inv(&b, len, ntt), len, ntt);
+        b.resize(len, mg.from(0));
+        for (x, &y) in b.iter_mut().zip(&a_over_b) {
+            *x = mg.mul(mg.add(*x, y), inv_two);
+        }
+    }
+    b.truncate(n);
+    result.extend(from_space(&b, mg));
+    Some(result)
+}
+
+/// uses 2 * b_0 * b_k = a_k - sum_{j=1}^{k-1} b_j * b_{k-j}
+pub fn poly_sqrt_naive(a: &[u32], n: usize, modulus: u32) -> Option<Vec<u32>> {
+    let (shift, a, root) = sqrt_prepare(a, n, modulus)?;
+    let mut result = vec![0; shift];
+    if shift == n {
+        return Some(result);
+    }
+    let n = n - shift;
+    let mg = Montgomery::new(modulus as i32);
+    let a = to_space(a, &mg);
+    let root = mg.from(root);
+    let inv_two_b0 = inverse(mg.add(root, root), &mg);
+    let mut b = vec![mg.from(0); n];
+    b[0] = root;
+    for k in 1..n {
+        let mut sum = a.get(k).copied().unwrap_or(mg.from(0));
+        for j in 1..k {
+            sum = mg.sub(sum, mg.mul(b[j], b[k - j]));
+        }
+        b[k] = mg.mul(sum, inv_two_b0);
+    }
+    result.extend(from_space(&b, &mg));
+    Some(result)
+}
+
+/// subproduct tree of prod (x - x_i), stored like a segment tree
+struct SubproductTree {
+    polys: Vec<Poly>,
+}
+
+impl SubproductTree {
+    fn new(xs: &[MontgomerySpace], ntt: &Ntt) -> Self {
+        let mut tree = SubproductTree {
+            polys: vec![vec![]; 4 * xs.len().max(1)],
+        };
+        if !xs.is_empty() {
+            tree.build(1, xs, ntt);
+        }
+        tree
+    }
+
+    fn build(&mut self, node: usize, xs: &[MontgomerySpace], ntt: &Ntt) {
+        if xs.len() == 1 {
+            let mg = ntt.montgomery();
+            self.polys[node] = vec![mg.sub(mg.from(0), xs[0]), mg.from(1)];
+            return;
+        }
+        let mid = xs.len() / 2;
+        self.build(2 * node, &xs[..mid], ntt);
+        self.build(2 * node + 1, &xs[mid..], ntt);
+        self.polys[node] =
+            ntt.convolution_montgomery(&self.polys[2 * node], &self.polys[2 * node + 1]);
+    }
+
+    fn evaluate(
+        &self,
+        node: usize,
+        a: &[MontgomerySpace],
+        xs: &[MontgomerySpace],
+        result: &mut Poly,
+        ntt: &Ntt,
+    ) {
+        if xs.len() <= 32 {
+            for &x in xs {
+                result.push(horner(a, x, ntt.montgomery()));
+            }
+            return;
+        }
+        let mid = xs.len() / 2;
+        let (_, left) = div_rem(a, &self.polys[2 * node], ntt);
+        self.evaluate(2 * node, &left, &xs[..mid], result, ntt);
+        let (_, right) = div_rem(a, &self.polys[2 * node + 1], ntt);
+        self.evaluate(2 * node + 1, &right, &xs[mid..], result, ntt);
+    }
+
+    /// sum c_i * prod_{j != i} (x - x_j) over the leaves of node
+    fn combine(&self, node: usize, cs: &[MontgomerySpace], ntt: &Ntt) -> Poly {
+        if cs.len() == 1 {
+            return vec![cs[0]];
+        }
+        let mg = ntt.montgomery();
+        let mid = cs.len() / 2;
+        let left = self.combine(2 * node, &cs[..mid], ntt);
+        let right = self.combine(2 * node + 1, &cs[mid..], ntt);
+        let mut result = ntt.convolution_montgomery(&left, &self.polys[2 * node + 1]);
+        let other = ntt.convolution_montgomery(&right, &self.polys[2 * node]);
+        result.resize(result.len().max(other.len()), mg.from(0));
+        for (x, &y) in result.iter_mut().zip(&other) {
+            *x = mg.add(*x, y);
+        }
+        result.truncate(cs.len());
+        result
+    }
+}
+
+fn horner(a: &[MontgomerySpace], x: MontgomerySpace, mg: &Montgomery) -> MontgomerySpace {
+    a.iter()
+        .rev()
+        .fold(mg.from(0), |acc, &c| mg.add(mg.mul(acc, x), c))
+}
+
+/// a(x_0), a(x_1), ... in O(n log^2 n)
+pub fn poly_evaluate(a: &[u32], xs: &[u32], ntt: &Ntt) -> Vec<u32> {
+    if xs.is_empty() {
+        return vec![];
+    }
+    let mg = ntt.montgomery();
+    let xs = to_space(xs, mg);
+    let tree = SubproductTree::new(&xs, ntt);
+    let (_, a) = div_rem(&to_space(a, mg), &tree.polys[1], ntt);
+    let mut result = Vec::with_capacity(xs.len());
+    tree.evaluate(1, &a, &xs, &mut result, ntt);
+    from_space(&result, mg)
+}
+
+pub fn poly_evaluate_naive(a: &[u32], xs: &[u32], modulus: u32) -> Vec<u32> {
+    let mg = Montgomery::new(modulus as i32);
+    let a = to_space(a, &mg);
+    xs.iter()
+        .map(|&x| value(horner(&a, mg.from(x), &mg), &mg))
+        .collect()
+}
+
+/// the polynomial of degree < n through (x_i, y_i), x_i must be distinct
+pub fn poly_interpolate(xs: &[u32], ys: &[u32], ntt: &Ntt) -> Vec<u32> {
+    assert_eq!(xs.len(), ys.len());
+    if xs.is_empty() {
+        return vec![];
+    }
+    let mg = ntt.montgomery();
+    let xs = to_space(xs, mg);
+    let tree = SubproductTree::new(&xs, ntt);
+    // y_i / M'(x_i), where M = prod (x - x_i)
+    let dm = derivative(&tree.polys[1], mg);
+    let mut dm_at_x = Vec::with_capacity(xs.len());
+    let (_, dm) = div_rem(&dm, &tree.polys[1], ntt);
+    tree.evaluate(1, &dm, &xs, &mut dm_at_x, ntt);
+    let cs: Poly = ys
+        .iter()
+        .zip(&dm_at_x)
+        .map(|(&y, &d)| mg.mul(mg.from(y), inverse(d, mg)))
+        .collect();
+    from_space(&tree.combine(1, &cs, ntt), mg)
+}
+
+/// Lagrange interpolation in O(n^2)
+pub fn poly_interpolate_naive(xs: &[u32], ys: &[u32], modulus: u32) -> Vec<u32> {
+    assert_eq!(xs.len(), ys.len());
+    let n = xs.len();
+    let mg = Montgomery::new(modulus as i32);
+    let (xs, ys) = (to_space(xs, &mg), to_space(ys, &mg));
+    let zero = mg.from(0);
+    // m = prod (x - x_i)
+    let mut m = vec![mg.from(1)];
+    for &x in &xs {
+        let mut next = vec![zero; m.len() + 1];
+        for (i, &c) in m.iter().enumerate() {
+            next[i + 1] = mg.add(next[i + 1], c);
+            next[i] = mg.sub(next[i], mg.mul(c, x));
+        }
+        m = next;
+    }
+    let mut result = vec![zero; n];
+    for i in 0..n {
+        // m / (x - x_i) by synthetic division
+        let mut quotient = vec![zero; n];
+        let mut carry = zero;
+        for k in (0..n).rev() {
+            carry = mg.add(m[k + 1], mg.mul(carry, xs[i]));
+            quotient[k] = carry;
+        }
+        let mut denom = mg.from(1);
+        for j in 0..n {
+            if j != i {
+                denom = mg.mul(denom, mg.sub(xs[i], xs[j]));
+            }
+        }
+        let coef = mg.mul(ys[i], inverse(denom, &mg));
+        for (r, &q) in result.iter_mut().zip(&quotient) {
+            *r = mg.add(*r, mg.mul(coef, q));
+        }
+    }
+    from_space(&result, &mg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::distr::{Distribution, Uniform};
+    use rand::rngs::StdRng;
+
+    /// a * b mod x^n on plain coefficients
+    fn mul_truncated_plain(a: &[u32], b: &[u32], n: usize, ntt: &Ntt) -> Vec<u32> {
+        let mg = ntt.montgomery();
+        from_space(
+            &mul_truncated(&to_space(a, mg), &to_space(b, mg), n, ntt),
+            mg,
+        )
+    }
+
+    fn random_poly(rng: &mut StdRng, n: usize, modulus: u32) -> Vec<u32> {
+        let uniform = Uniform::new(0, modulus).unwrap();
+        uniform.sample_iter(rng).take(n).collect()
+    }
+
+    #[test]
+    fn test_poly_inv() {
+        let ntt = Ntt::default_prime();
+        let p = ntt.modulus();
+        let mut rng = StdRng::seed_from_u64(0);
+        for n in [1, 2, 7, 64, 300] {
+            let mut a = random_poly(&mut rng, n, p);
+            a[0] = a[0].max(1);
+            assert_eq!(poly_inv(&a, n, &ntt), poly_inv_naive(&a, n, p));
+            let inv = poly_inv(&a, n, &ntt);
+            let one = mul_truncated_plain(&a, &inv, n, &ntt);
+            assert_eq!(one[0], 1);
+            assert!(one[1..].iter().all(|&x| x == 0));
+        }
+    }
+
+    #[test]
+    fn test_poly_div_rem() {
+        let ntt = Ntt::default_prime();
+        let p = ntt.modulus();
+        let mut rng = StdRng::seed_from_u64(1);
+        for (n, m) in [(1, 1), (5, 10), (10, 5), (200, 100), (300, 1), (500, 499)] {
+            let a = random_poly(&mut rng, n, p);
+            let mut b = random_poly(&mut rng, m, p);
+            *b.last_mut().unwrap() = b.last().unwrap().max(&1).to_owned();
+            assert_eq!(poly_div_rem(&a, &b, &ntt), poly_div_rem_naive(&a, &b, p));
+        }
+    }
+
+    #[test]
+    fn test_poly_log_exp() {
+        let ntt = Ntt::default_prime();
+        let p = ntt.modulus();
+        let mut rng = StdRng::seed_from_u64(2);
+        for n in [1, 2, 3, 50, 257] {
+            let mut a = random_poly(&mut rng, n, p);
+            a[0] = 1;
+            let log_a = poly_log(&a, n, &ntt);
+            assert_eq!(log_a, poly_log_naive(&a, n, p));
+            let mut b = random_poly(&mut rng, n, p);
+            b[0] = 0;
+            let exp_b = poly_exp(&b, n, &ntt);
+            assert_eq!(exp_b, poly_exp_naive(&b, n, p));
+            assert_eq!(poly_exp(&log_a, n, &ntt), a);
+        }
+        // exp(x) = sum x^k / k!, with 1/2 = (p + 1) / 2 and 1/6 = (p + 1) / 6
+        let expected = vec![1, 1, p.div_ceil(2), (p + 1) / 6];
+        assert_eq!(poly_exp(&[0, 1], 4, &ntt), expected);
+    }
+
+    #[test]
+    fn test_poly_sqrt() {
+        let ntt = Ntt::default_prime();
+        let p = ntt.modulus();
+        let mut rng = StdRng::seed_from_u64(3);
+        assert_eq!(sqrt_mod(4, p), Some(2));
+        assert_eq!(sqrt_mod(0, p), Some(0));
+        for n in [1, 2, 3, 50, 257] {
+            let b = random_poly(&mut rng, n, p);
+            let mut a = mul_truncated_plain(&b, &b, n, &ntt);
+            let root = poly_sqrt(&a, n, &ntt).unwrap();
+            assert_eq!(Some(root.clone()), poly_sqrt_naive(&a, n, p));
+            assert_eq!(mul_truncated_plain(&root, &root, n, &ntt), a);
+            // shifted by x^2
+            a.splice(0..0, [0, 0]);
+            let shifted = poly_sqrt(&a, n + 1, &ntt).unwrap();
+            assert_eq!(Some(shifted.clone()), poly_sqrt_naive(&a, n + 1, p));
+            assert_eq!(shifted[0], 0);
+            assert_eq!(&shifted[1..], &root[..]);
+        }
+        assert_eq!(poly_sqrt(&[0, 1], 3, &ntt), None);
+        assert_eq!(poly_sqrt(&[0, 0, 0], 2, &ntt), Some(vec![0, 0]));
+    }
+
+    #[test]
+    fn test_poly_evaluate_interpolate() {
+        let ntt = Ntt::default_prime();
+        let p = ntt.modulus();
+        let mut rng = StdRng::seed_from_u64(4);
+        for n in [1, 2, 33, 100, 300] {
+            let a = random_poly(&mut rng, n, p);
+            let xs: Vec<u32> = (0..n as u32).map(|i| i * 7 + 3).collect();
+            let ys = poly_evaluate(&a, &xs, &ntt);
+            assert_eq!(ys, poly_evaluate_naive(&a, &xs, p));
+            assert_eq!(poly_interpolate(&xs, &ys, &ntt), a);
+            assert_eq!(poly_interpolate_naive(&xs, &ys, p), a);
+        }
+    }
+}
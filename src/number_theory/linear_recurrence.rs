@@ -0,0 +1,186 @@
+use super::{Montgomery, MontgomerySpace};
+use crate::external_memory::cache_oblivious::Matrix;
+
+/// F(n) mod modulus, using [[1, 1], [1, 0]]^n
+pub fn fibonacci_matrix(n: u64, modulus: u32) -> u32 {
+    let matrix = Matrix::from_vec(vec![1 % modulus, 1 % modulus, 1 % modulus, 0], 2, 2);
+    *matrix.pow_mod(n, modulus).get(0, 1)
+}
+
+/// F(n) mod n of montgomery, using fast doubling
+///
+/// F(2k) = F(k) * (2 * F(k + 1) - F(k))\
+/// F(2k + 1) = F(k)^2 + F(k + 1)^2
+pub fn fibonacci_fast_doubling(n: u64, montgomery: &Montgomery) -> u32 {
+    let mut a = montgomery.from(0); // F(k)
+    let mut b = montgomery.from(1); // F(k + 1)
+    for bit in (0..u64::BITS - n.leading_zeros()).rev() {
+        let c = montgomery.mul(a, montgomery.sub(montgomery.add(b, b), a));
+        let d = montgomery.add(montgomery.mul(a, a), montgomery.mul(b, b));
+        if (n >> bit) & 1 == 1 {
+            (a, b) = (d, montgomery.add(c, d));
+        } else {
+            (a, b) = (c, d);
+        }
+    }
+    montgomery.to(a) % montgomery.modulus()
+}
+
+/// a(n) mod modulus, where a(i) = coeffs[0] * a(i - 1) + ... + coeffs[k - 1] * a(i - k)
+/// and a(0..k) = init, using the k×k companion matrix
+///
+/// coeffs and init must be in [0, modulus)
+pub fn linear_recurrence_matrix(coeffs: &[u32], init: &[u32], n: u64, modulus: u32) -> u32 {
+    let k = coeffs.len();
+    assert!(k > 0);
+    assert_eq!(init.len(), k);
+    if n < k as u64 {
+        return init[n as usize] % modulus;
+    }
+    // state(i) = [a(i + k - 1), ..., a(i)]^T, state(i + 1) = companion * state(i)
+    let mut companion = Matrix::new(k, k);
+    for (j, &c) in coeffs.iter().enumerate() {
+        companion.set(0, j, c);
+    }
+    for i in 1..k {
+        companion.set(i, i - 1, 1 % modulus);
+    }
+    let power = companion.pow_mod(n - (k as u64 - 1), modulus);
+    let mut sum = 0u64;
+    for j in 0..k {
+        sum = (sum + *power.get(0, j) as u64 * init[k - 1 - j] as u64) % modulus as u64;
+    }
+    sum as u32
+}
+
+/// (lhs * rhs) mod (x^k - coeffs[0] * x^(k - 1) - ... - coeffs[k - 1])
+fn kitamasa_mul(
+    lhs: &[MontgomerySpace],
+    rhs: &[MontgomerySpace],
+    coeffs: &[MontgomerySpace],
+    montgomery: &Montgomery,
+) -> Vec<MontgomerySpace> {
+    let zero = montgomery.from(0);
+    let mut product = vec![zero; lhs.len() + rhs.len() - 1];
+    for (i, &a) in lhs.iter().enumerate() {
+        for (j, &b) in rhs.iter().enumerate() {
+            product[i + j] = montgomery.add(product[i + j], montgomery.mul(a, b));
+        }
+    }
+    kitamasa_reduce(product, coeffs, montgomery)
+}
+
+/// reduce a polynomial of any degree modulo the characteristic polynomial
+fn kitamasa_reduce(
+    mut poly: Vec<MontgomerySpace>,
+    coeffs: &[MontgomerySpace],
+    montgomery: &Montgomery,
+) -> Vec<MontgomerySpace> {
+    let k = coeffs.len();
+    // x^i = coeffs[0] * x^(i - 1) + ... + coeffs[k - 1] * x^(i - k)
+    for i in (k..poly.len()).rev() {
+        let top = poly[i];
+        for (j, &c) in coeffs.iter().enumerate() {
+            poly[i - 1 - j] = montgomery.add(poly[i - 1 - j], montgomery.mul(top, c));
+        }
+    }
+    poly.resize(k, montgomery.from(0));
+    poly
+}
+
+/// a(n) mod n of montgomery, where a(i) = coeffs[0] * a(i - 1) + ... + coeffs[k - 1] * a(i - k)
+/// and a(0..k) = init
+///
+/// Computes x^n mod the characteristic polynomial in O(k^2 log n),
+/// then a(n) = sum r_i * a(i).
+pub fn kitamasa(coeffs: &[u32], init: &[u32], n: u64, montgomery: &Montgomery) -> u32 {
+    let k = coeffs.len();
+    assert!(k > 0);
+    assert_eq!(init.len(), k);
+    if n < k as u64 {
+        return init[n as usize] % montgomery.modulus();
+    }
+    let coeffs: Vec<_> = coeffs.iter().map(|&c| montgomery.from(c)).collect();
+    let mut result = kitamasa_reduce(vec![montgomery.from(1)], &coeffs, montgomery);
+    let mut base = kitamasa_reduce(
+        vec![montgomery.from(0), montgomery.from(1)],
+        &coeffs,
+        montgomery,
+    );
+    let mut exp = n;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = kitamasa_mul(&result, &base, &coeffs, montgomery);
+        }
+        exp >>= 1;
+        if exp > 0 {
+            base = kitamasa_mul(&base, &base, &coeffs, montgomery);
+        }
+    }
+    let mut sum = montgomery.from(0);
+    for (&r, &a) in result.iter().zip(init) {
+        sum = montgomery.add(sum, montgomery.mul(r, montgomery.from(a)));
+    }
+    montgomery.to(sum) % montgomery.modulus()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const M: u32 = 1_000_000_007;
+
+    fn naive_linear_recurrence(coeffs: &[u32], init: &[u32], n: u64) -> u32 {
+        let mut a: Vec<u64> = init.iter().map(|&x| x as u64).collect();
+        while (a.len() as u64) <= n {
+            let len = a.len();
+            let mut next = 0;
+            for (j, &c) in coeffs.iter().enumerate() {
+                next = (next + c as u64 * a[len - 1 - j]) % M as u64;
+            }
+            a.push(next);
+        }
+        a[n as usize] as u32
+    }
+
+    #[test]
+    fn test_fibonacci() {
+        let montgomery = Montgomery::new(M as i32);
+        let expected = [0, 1, 1, 2, 3, 5, 8, 13, 21, 34, 55];
+        for (n, &f) in expected.iter().enumerate() {
+            assert_eq!(fibonacci_matrix(n as u64, M), f);
+            assert_eq!(fibonacci_fast_doubling(n as u64, &montgomery), f);
+        }
+        for n in [100, 1000, 12345] {
+            let f = naive_linear_recurrence(&[1, 1], &[0, 1], n);
+            assert_eq!(fibonacci_matrix(n, M), f);
+            assert_eq!(fibonacci_fast_doubling(n, &montgomery), f);
+        }
+        assert_eq!(
+            fibonacci_matrix(1 << 60, M),
+            fibonacci_fast_doubling(1 << 60, &montgomery)
+        );
+    }
+
+    #[test]
+    fn test_linear_recurrence() {
+        let montgomery = Montgomery::new(M as i32);
+        let cases: [(&[u32], &[u32]); 3] = [
+            (&[7], &[3]),
+            (&[1, 1, 1], &[0, 0, 1]),
+            (&[3, 0, M - 5, 2, 123456789], &[1, 2, 3, 4, 5]),
+        ];
+        for (coeffs, init) in cases {
+            for n in [0, 1, 2, 4, 5, 10, 100, 1000] {
+                let expected = naive_linear_recurrence(coeffs, init, n);
+                assert_eq!(linear_recurrence_matrix(coeffs, init, n, M), expected);
+                assert_eq!(kitamasa(coeffs, init, n, &montgomery), expected);
+            }
+            let n = 1_000_000_000_000;
+            assert_eq!(
+                linear_recurrence_matrix(coeffs, init, n, M),
+                kitamasa(coeffs, init, n, &montgomery)
+            );
+        }
+    }
+}
@@ -1,14 +1,26 @@
+mod batch_exponentiation;
 mod exgcd;
+mod exponentiation;
+mod formal_power_series;
+mod linear_recurrence;
 mod montgomery_multiplication;
+mod ntt;
+mod wide_integer;
 
 use crate::arithmetic::*;
+pub use batch_exponentiation::*;
 pub use exgcd::*;
+pub use exponentiation::*;
+pub use formal_power_series::*;
+pub use linear_recurrence::*;
 pub use montgomery_multiplication::*;
+pub use ntt::*;
+pub use wide_integer::*;
 
 pub const fn binpow_rec<const M: u64>(base: u64, exp: u64) -> u64 {
     match exp {
         0 => 1,
-        _ if exp % 2 == 0 => {
+        _ if exp.is_multiple_of(2) => {
             let half = binpow_rec::<M>(base, exp / 2);
             (half * half) % M
         }
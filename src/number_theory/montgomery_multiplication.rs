@@ -59,6 +59,24 @@ impl Montgomery {
         Montgomery { n, inv_n, sqr_r }
     }
 
+    /// the modulus n
+    #[inline]
+    pub const fn modulus(&self) -> u32 {
+        self.n
+    }
+
+    /// n^(-1) mod 2^32
+    #[inline]
+    pub(crate) const fn inv_n(&self) -> u32 {
+        self.inv_n
+    }
+
+    /// 2^64 mod n, in [1, n]
+    #[inline]
+    pub(crate) const fn sqr_r(&self) -> u32 {
+        self.sqr_r
+    }
+
     #[inline]
     /// times r^(-1) (mod n)
     /// returns a number in the [0, 2 * n - 2] range
@@ -97,6 +115,47 @@ impl Montgomery {
         let x = lhs.x as u64 * rhs.x as u64;
         self.reduce(x)
     }
+
+    /// map [0, 2 * n) to [0, n)
+    #[inline]
+    fn shrink(&self, x: u32) -> u32 {
+        if x >= self.n { x - self.n } else { x }
+    }
+
+    /// add two numbers in montgomery space
+    /// returns a number in the [0, n) range
+    #[inline]
+    pub fn add(&self, lhs: MontgomerySpace, rhs: MontgomerySpace) -> MontgomerySpace {
+        let x = self.shrink(lhs.x) + self.shrink(rhs.x);
+        MontgomerySpace { x: self.shrink(x) }
+    }
+
+    /// subtract two numbers in montgomery space
+    /// returns a number in the [0, n) range
+    #[inline]
+    pub fn sub(&self, lhs: MontgomerySpace, rhs: MontgomerySpace) -> MontgomerySpace {
+        let (lhs, rhs) = (self.shrink(lhs.x), self.shrink(rhs.x));
+        MontgomerySpace {
+            x: if lhs >= rhs {
+                lhs - rhs
+            } else {
+                lhs + self.n - rhs
+            },
+        }
+    }
+
+    /// base^exp in montgomery space
+    pub fn pow(&self, mut base: MontgomerySpace, mut exp: u64) -> MontgomerySpace {
+        let mut result = self.from(1);
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = self.mul(result, base);
+            }
+            base = self.mul(base, base);
+            exp >>= 1;
+        }
+        result
+    }
 }
 
 pub fn inverse_using_montgomery(base: i32, mod_val: i32) -> i32 {
@@ -140,6 +199,10 @@ mod tests {
         let a = montgomery.from(2);
         let b = montgomery.from(3);
         assert_eq!(montgomery.to(montgomery.mul(a, b)), 6);
+        assert_eq!(montgomery.to(montgomery.add(a, b)), 5);
+        assert_eq!(montgomery.to(montgomery.sub(b, a)), 1);
+        assert_eq!(montgomery.to(montgomery.sub(a, b)), 1_000_000_006);
+        assert_eq!(montgomery.to(montgomery.pow(a, 10)), 1024);
     }
 
     #[test]
@@ -0,0 +1,209 @@
+use super::{Montgomery, MontgomerySpace};
+
+/// Number theoretic transform modulo an NTT prime, i.e. a prime p = c * 2^k + 1
+///
+/// All arithmetic is done in montgomery space.
+#[derive(Debug, Clone, Copy)]
+pub struct Ntt {
+    montgomery: Montgomery,
+    modulus: u32,
+    primitive_root: u32,
+    /// the largest power of two dividing p - 1
+    max_len: usize,
+}
+
+impl Ntt {
+    /// `modulus` must be an odd prime less than 2^30 (so montgomery values stay in [0, 2 * n)),
+    /// `primitive_root` must be a primitive root of it
+    pub fn new(modulus: u32, primitive_root: u32) -> Self {
+        assert!(modulus < 1 << 30, "modulus must be less than 2^30");
+        let montgomery = Montgomery::new(modulus as i32);
+        let max_len = 1 << (modulus - 1).trailing_zeros();
+        Ntt {
+            montgomery,
+            modulus,
+            primitive_root,
+            max_len,
+        }
+    }
+
+    /// 998244353 = 119 * 2^23 + 1
+    pub fn default_prime() -> Self {
+        Ntt::new(998_244_353, 3)
+    }
+
+    #[inline]
+    pub const fn modulus(&self) -> u32 {
+        self.modulus
+    }
+
+    #[inline]
+    pub const fn montgomery(&self) -> &Montgomery {
+        &self.montgomery
+    }
+
+    /// the largest transform length supported by the modulus
+    #[inline]
+    pub const fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// in-place iterative NTT, `a.len()` must be a power of two not exceeding `max_len`
+    pub fn transform(&self, a: &mut [MontgomerySpace], invert: bool) {
+        let n = a.len();
+        assert!(n.is_power_of_two() && n <= self.max_len);
+        let mg = &self.montgomery;
+
+        // bit reversal permutation
+        let mut j = 0;
+        for i in 1..n {
+            let mut bit = n >> 1;
+            while j & bit != 0 {
+                j ^= bit;
+                bit >>= 1;
+            }
+            j ^= bit;
+            if i < j {
+                a.swap(i, j);
+            }
+        }
+
+        let root = mg.from(self.primitive_root);
+        let mut roots = Vec::with_capacity(n / 2);
+        let mut len = 2;
+        while len <= n {
+            let mut w_len = mg.pow(root, ((self.modulus - 1) as usize / len) as u64);
+            if invert {
+                w_len = mg.pow(w_len, (self.modulus - 2) as u64);
+            }
+            // precompute the twiddle factors so the butterflies do not depend on each other
+            let half = len / 2;
+            roots.clear();
+            roots.push(mg.from(1));
+            for k in 1..half {
+                roots.push(mg.mul(roots[k - 1], w_len));
+            }
+            for chunk in a.chunks_exact_mut(len) {
+                let (lo, hi) = chunk.split_at_mut(half);
+                for ((u, v), &w) in lo.iter_mut().zip(hi.iter_mut()).zip(&roots) {
+                    let t = mg.mul(*v, w);
+                    (*u, *v) = (mg.add(*u, t), mg.sub(*u, t));
+                }
+            }
+            len <<= 1;
+        }
+
+        if invert {
+            let inv_n = mg.pow(mg.from(n as u32), (self.modulus - 2) as u64);
+            for x in a.iter_mut() {
+                *x = mg.mul(*x, inv_n);
+            }
+        }
+    }
+
+    /// c(x) = a(x) * b(x) mod p
+    pub fn convolution(&self, a: &[u32], b: &[u32]) -> Vec<u32> {
+        if a.is_empty() || b.is_empty() {
+            return vec![];
+        }
+        if a.len().min(b.len()) <= 32 {
+            return convolution_naive(a, b, self.modulus);
+        }
+        let mg = &self.montgomery;
+        let fa: Vec<_> = a.iter().map(|&x| mg.from(x)).collect();
+        let fb: Vec<_> = b.iter().map(|&x| mg.from(x)).collect();
+        self.convolution_montgomery(&fa, &fb)
+            .into_iter()
+            .map(|x| mg.to(x) % self.modulus)
+            .collect()
+    }
+
+    /// [`Ntt::convolution`] of coefficients already in montgomery space
+    pub fn convolution_montgomery(
+        &self,
+        a: &[MontgomerySpace],
+        b: &[MontgomerySpace],
+    ) -> Vec<MontgomerySpace> {
+        if a.is_empty() || b.is_empty() {
+            return vec![];
+        }
+        let mg = &self.montgomery;
+        let zero = mg.from(0);
+        let result_len = a.len() + b.len() - 1;
+        if a.len().min(b.len()) <= 32 {
+            let mut result = vec![zero; result_len];
+            for (i, &x) in a.iter().enumerate() {
+                for (j, &y) in b.iter().enumerate() {
+                    result[i + j] = mg.add(result[i + j], mg.mul(x, y));
+                }
+            }
+            return result;
+        }
+        let n = result_len.next_power_of_two();
+        let mut fa = a.to_vec();
+        let mut fb = b.to_vec();
+        fa.resize(n, zero);
+        fb.resize(n, zero);
+        self.transform(&mut fa, false);
+        self.transform(&mut fb, false);
+        for (x, &y) in fa.iter_mut().zip(&fb) {
+            *x = mg.mul(*x, y);
+        }
+        self.transform(&mut fa, true);
+        fa.truncate(result_len);
+        fa
+    }
+}
+
+/// O(n * m) reference convolution
+pub fn convolution_naive(a: &[u32], b: &[u32], modulus: u32) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    let mut result = vec![0u64; a.len() + b.len() - 1];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            result[i + j] = (result[i + j] + x as u64 * y as u64) % modulus as u64;
+        }
+    }
+    result.into_iter().map(|x| x as u32).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::distr::{Distribution, Uniform};
+
+    #[test]
+    fn test_ntt_round_trip() {
+        let ntt = Ntt::default_prime();
+        let mg = ntt.montgomery();
+        let values: Vec<u32> = (0..64).map(|i| i * i + 7).collect();
+        let mut a: Vec<_> = values.iter().map(|&x| mg.from(x)).collect();
+        ntt.transform(&mut a, false);
+        ntt.transform(&mut a, true);
+        let back: Vec<u32> = a.iter().map(|&x| mg.to(x) % ntt.modulus()).collect();
+        assert_eq!(back, values);
+    }
+
+    #[test]
+    fn test_convolution() {
+        let mut rng = rand::rng();
+        for ntt in [
+            Ntt::default_prime(),
+            Ntt::new(469_762_049, 3),
+            Ntt::new(167_772_161, 3),
+        ] {
+            let uniform = Uniform::new(0, ntt.modulus()).unwrap();
+            for (n, m) in [(1, 1), (3, 100), (100, 3), (64, 64), (1000, 777)] {
+                let a: Vec<u32> = uniform.sample_iter(&mut rng).take(n).collect();
+                let b: Vec<u32> = uniform.sample_iter(&mut rng).take(m).collect();
+                assert_eq!(
+                    ntt.convolution(&a, &b),
+                    convolution_naive(&a, &b, ntt.modulus())
+                );
+            }
+        }
+        assert!(Ntt::default_prime().convolution(&[], &[1]).is_empty());
+    }
+}
@@ -0,0 +1,523 @@
+//! Stack allocated fixed-width unsigned integers and multi-limb montgomery multiplication.
+//!
+//! Limbs are little-endian u64. Arithmetic on [`Uint`] and [`WideMontgomery`] has no
+//! data-dependent branches or memory accesses, except where noted.
+
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Uint<const L: usize> {
+    limbs: [u64; L],
+}
+
+pub type U256 = Uint<4>;
+pub type U512 = Uint<8>;
+
+/// a + b + carry, returns (sum, carry)
+#[inline(always)]
+const fn adc(a: u64, b: u64, carry: u64) -> (u64, u64) {
+    let t = a as u128 + b as u128 + carry as u128;
+    (t as u64, (t >> 64) as u64)
+}
+
+/// a - b - borrow, returns (difference, borrow)
+#[inline(always)]
+const fn sbb(a: u64, b: u64, borrow: u64) -> (u64, u64) {
+    let t = (a as u128).wrapping_sub(b as u128 + borrow as u128);
+    (t as u64, (t >> 127) as u64)
+}
+
+/// t + a * b + carry, returns (low, high), never overflows
+#[inline(always)]
+const fn mac(t: u64, a: u64, b: u64, carry: u64) -> (u64, u64) {
+    let t = t as u128 + a as u128 * b as u128 + carry as u128;
+    (t as u64, (t >> 64) as u64)
+}
+
+impl<const L: usize> Uint<L> {
+    pub const ZERO: Self = Uint { limbs: [0; L] };
+    pub const ONE: Self = Uint::from_u64(1);
+    pub const MAX: Self = Uint {
+        limbs: [u64::MAX; L],
+    };
+    pub const BITS: u32 = 64 * L as u32;
+
+    #[inline]
+    pub const fn from_limbs(limbs: [u64; L]) -> Self {
+        Uint { limbs }
+    }
+
+    #[inline]
+    pub const fn from_u64(x: u64) -> Self {
+        let mut limbs = [0; L];
+        limbs[0] = x;
+        Uint { limbs }
+    }
+
+    #[inline]
+    pub const fn limbs(&self) -> &[u64; L] {
+        &self.limbs
+    }
+
+    /// parse a big-endian hex string, panics on invalid digits or overflow
+    pub fn from_hex(s: &str) -> Self {
+        let s = s.trim_start_matches("0x");
+        let mut result = Self::ZERO;
+        for (i, c) in s.bytes().rev().filter(|&c| c != b'_').enumerate() {
+            let digit = (c as char).to_digit(16).expect("invalid hex digit") as u64;
+            assert!(i < 16 * L || digit == 0, "hex string overflows");
+            if i < 16 * L {
+                result.limbs[i / 16] |= digit << (4 * (i % 16));
+            }
+        }
+        result
+    }
+
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        self.limbs.iter().fold(0, |acc, &x| acc | x) == 0
+    }
+
+    /// the i-th bit, counted from the least significant one
+    #[inline]
+    pub const fn bit(&self, i: u32) -> bool {
+        (self.limbs[i as usize / 64] >> (i % 64)) & 1 == 1
+    }
+
+    /// number of significant bits, not constant-time
+    pub fn bits(&self) -> u32 {
+        for i in (0..L).rev() {
+            if self.limbs[i] != 0 {
+                return 64 * i as u32 + 64 - self.limbs[i].leading_zeros();
+            }
+        }
+        0
+    }
+
+    pub fn overflowing_add(&self, rhs: &Self) -> (Self, bool) {
+        let mut result = Self::ZERO;
+        let mut carry = 0;
+        for i in 0..L {
+            (result.limbs[i], carry) = adc(self.limbs[i], rhs.limbs[i], carry);
+        }
+        (result, carry != 0)
+    }
+
+    pub fn overflowing_sub(&self, rhs: &Self) -> (Self, bool) {
+        let mut result = Self::ZERO;
+        let mut borrow = 0;
+        for i in 0..L {
+            (result.limbs[i], borrow) = sbb(self.limbs[i], rhs.limbs[i], borrow);
+        }
+        (result, borrow != 0)
+    }
+
+    #[inline]
+    pub fn wrapping_add(&self, rhs: &Self) -> Self {
+        self.overflowing_add(rhs).0
+    }
+
+    #[inline]
+    pub fn wrapping_sub(&self, rhs: &Self) -> Self {
+        self.overflowing_sub(rhs).0
+    }
+
+    /// full product, returns (low, high)
+    pub fn widening_mul(&self, rhs: &Self) -> (Self, Self) {
+        let mut lo = Self::ZERO;
+        let mut hi = Self::ZERO;
+        for i in 0..L {
+            let mut carry = 0;
+            for j in 0..L {
+                let k = i + j;
+                let t = if k < L { lo.limbs[k] } else { hi.limbs[k - L] };
+                let (t, c) = mac(t, self.limbs[i], rhs.limbs[j], carry);
+                if k < L {
+                    lo.limbs[k] = t;
+                } else {
+                    hi.limbs[k - L] = t;
+                }
+                carry = c;
+            }
+            // i + L is always in the high half
+            hi.limbs[i] = carry;
+        }
+        (lo, hi)
+    }
+
+    #[inline]
+    pub fn wrapping_mul(&self, rhs: &Self) -> Self {
+        self.widening_mul(rhs).0
+    }
+
+    /// returns `a` if `choice` is true, `b` otherwise, without branching
+    #[inline]
+    pub fn select(choice: bool, a: &Self, b: &Self) -> Self {
+        let mask = (choice as u64).wrapping_neg();
+        let mut result = Self::ZERO;
+        for i in 0..L {
+            result.limbs[i] = (a.limbs[i] & mask) | (b.limbs[i] & !mask);
+        }
+        result
+    }
+
+    pub fn shl1(&self) -> (Self, bool) {
+        let mut result = Self::ZERO;
+        let mut carry = 0;
+        for i in 0..L {
+            result.limbs[i] = (self.limbs[i] << 1) | carry;
+            carry = self.limbs[i] >> 63;
+        }
+        (result, carry != 0)
+    }
+}
+
+impl<const L: usize> Default for Uint<L> {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl<const L: usize> Ord for Uint<L> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs.iter().rev().cmp(other.limbs.iter().rev())
+    }
+}
+
+impl<const L: usize> PartialOrd for Uint<L> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl U256 {
+    /// full 512-bit product
+    pub fn mul_wide(&self, rhs: &U256) -> U512 {
+        let (lo, hi) = self.widening_mul(rhs);
+        U512::from_halves(&lo, &hi)
+    }
+}
+
+impl U512 {
+    pub fn from_halves(lo: &U256, hi: &U256) -> U512 {
+        let mut limbs = [0; 8];
+        limbs[..4].copy_from_slice(&lo.limbs);
+        limbs[4..].copy_from_slice(&hi.limbs);
+        U512 { limbs }
+    }
+
+    /// returns (low, high)
+    pub fn split(&self) -> (U256, U256) {
+        let mut lo = U256::ZERO;
+        let mut hi = U256::ZERO;
+        lo.limbs.copy_from_slice(&self.limbs[..4]);
+        hi.limbs.copy_from_slice(&self.limbs[4..]);
+        (lo, hi)
+    }
+}
+
+/// Montgomery multiplication over an L-limb odd modulus, R = 2^(64 * L)
+#[derive(Debug, Clone, Copy)]
+pub struct WideMontgomery<const L: usize> {
+    n: Uint<L>,
+    /// -n^(-1) mod 2^64
+    neg_inv_n: u64,
+    /// R^2 mod n
+    sqr_r: Uint<L>,
+}
+
+/// a number in montgomery space, always in [0, n)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WideMontgomerySpace<const L: usize> {
+    x: Uint<L>,
+}
+
+impl<const L: usize> WideMontgomery<L> {
+    /// `n` must be odd and greater than 1
+    pub fn new(n: Uint<L>) -> Self {
+        assert!(n.limbs[0] % 2 == 1, "n is not coprime to 2^64");
+        assert!(n > Uint::ONE);
+        let n0 = n.limbs[0];
+        let mut inv = 1u64; // 2^1
+        for _ in 0..6 {
+            inv = inv.wrapping_mul(2u64.wrapping_sub(n0.wrapping_mul(inv))); // 2^2, ..., 2^64
+        }
+
+        // 1 doubled 2 * 64 * L times is R^2
+        let mut sqr_r = Uint::ONE;
+        let mut montgomery = WideMontgomery {
+            n,
+            neg_inv_n: inv.wrapping_neg(),
+            sqr_r,
+        };
+        for _ in 0..2 * Uint::<L>::BITS {
+            sqr_r = montgomery.add_mod(&sqr_r, &sqr_r);
+        }
+        montgomery.sqr_r = sqr_r;
+        montgomery
+    }
+
+    #[inline]
+    pub const fn modulus(&self) -> &Uint<L> {
+        &self.n
+    }
+
+    /// (a + b) mod n for a, b in [0, n)
+    #[inline]
+    fn add_mod(&self, a: &Uint<L>, b: &Uint<L>) -> Uint<L> {
+        let (sum, carry) = a.overflowing_add(b);
+        let (reduced, borrow) = sum.overflowing_sub(&self.n);
+        // keep sum only when it did not overflow and is less than n
+        Uint::select(!carry & borrow, &sum, &reduced)
+    }
+
+    /// (a - b) mod n for a, b in [0, n)
+    #[inline]
+    fn sub_mod(&self, a: &Uint<L>, b: &Uint<L>) -> Uint<L> {
+        let (diff, borrow) = a.overflowing_sub(b);
+        let fixed = diff.wrapping_add(&self.n);
+        Uint::select(borrow, &fixed, &diff)
+    }
+
+    /// a * b * R^(-1) mod n by coarsely integrated operand scanning (CIOS),
+    /// a and b must be in [0, n)
+    fn mont_mul(&self, a: &Uint<L>, b: &Uint<L>) -> Uint<L> {
+        // t has L + 2 limbs, t[L] and t[L + 1] are kept separately
+        let mut t = [0u64; L];
+        let mut t_l = 0u64;
+        let mut t_l1;
+        for i in 0..L {
+            let mut carry = 0;
+            for (t_j, &a_j) in t.iter_mut().zip(&a.limbs) {
+                (*t_j, carry) = mac(*t_j, a_j, b.limbs[i], carry);
+            }
+            (t_l, t_l1) = adc(t_l, carry, 0);
+
+            let m = t[0].wrapping_mul(self.neg_inv_n);
+            let (_, mut carry) = mac(t[0], m, self.n.limbs[0], 0);
+            for j in 1..L {
+                (t[j - 1], carry) = mac(t[j], m, self.n.limbs[j], carry);
+            }
+            (t[L - 1], carry) = adc(t_l, carry, 0);
+            t_l = t_l1 + carry;
+        }
+        let t = Uint { limbs: t };
+        let (reduced, borrow) = t.overflowing_sub(&self.n);
+        // t < 2n, subtract n if t_l is set or t >= n
+        Uint::select((t_l == 0) & borrow, &t, &reduced)
+    }
+
+    /// convert normal space to montgomery space, x is reduced modulo n first
+    pub fn from(&self, x: &Uint<L>) -> WideMontgomerySpace<L> {
+        let x = if *x >= self.n {
+            self.reduce_slow(x)
+        } else {
+            *x
+        };
+        WideMontgomerySpace {
+            x: self.mont_mul(&x, &self.sqr_r),
+        }
+    }
+
+    /// x mod n by shift-and-subtract, not constant-time
+    fn reduce_slow(&self, x: &Uint<L>) -> Uint<L> {
+        let mut r = Uint::ZERO;
+        for i in (0..x.bits()).rev() {
+            let (doubled, carry) = r.shl1();
+            r = doubled;
+            r.limbs[0] |= x.bit(i) as u64;
+            if carry || r >= self.n {
+                r = r.wrapping_sub(&self.n);
+            }
+        }
+        r
+    }
+
+    /// convert montgomery space to normal space, returns a number in [0, n)
+    pub fn to(&self, m: &WideMontgomerySpace<L>) -> Uint<L> {
+        self.mont_mul(&m.x, &Uint::ONE)
+    }
+
+    #[inline]
+    pub fn mul(
+        &self,
+        lhs: &WideMontgomerySpace<L>,
+        rhs: &WideMontgomerySpace<L>,
+    ) -> WideMontgomerySpace<L> {
+        WideMontgomerySpace {
+            x: self.mont_mul(&lhs.x, &rhs.x),
+        }
+    }
+
+    #[inline]
+    pub fn add(
+        &self,
+        lhs: &WideMontgomerySpace<L>,
+        rhs: &WideMontgomerySpace<L>,
+    ) -> WideMontgomerySpace<L> {
+        WideMontgomerySpace {
+            x: self.add_mod(&lhs.x, &rhs.x),
+        }
+    }
+
+    #[inline]
+    pub fn sub(
+        &self,
+        lhs: &WideMontgomerySpace<L>,
+        rhs: &WideMontgomerySpace<L>,
+    ) -> WideMontgomerySpace<L> {
+        WideMontgomerySpace {
+            x: self.sub_mod(&lhs.x, &rhs.x),
+        }
+    }
+
+    /// base^exp in montgomery space
+    ///
+    /// Always does a square and a multiply for each of the 64 * L bits of exp,
+    /// so the running time does not depend on exp.
+    pub fn pow(&self, base: &WideMontgomerySpace<L>, exp: &Uint<L>) -> WideMontgomerySpace<L> {
+        let mut result = self.from(&Uint::ONE).x;
+        for i in (0..Uint::<L>::BITS).rev() {
+            result = self.mont_mul(&result, &result);
+            let multiplied = self.mont_mul(&result, &base.x);
+            result = Uint::select(exp.bit(i), &multiplied, &result);
+        }
+        WideMontgomerySpace { x: result }
+    }
+
+    /// base^exp mod n
+    pub fn pow_mod(&self, base: &Uint<L>, exp: &Uint<L>) -> Uint<L> {
+        self.to(&self.pow(&self.from(base), exp))
+    }
+
+    /// a^(-1) mod n by Fermat's little theorem, n must be prime
+    pub fn inv_mod(&self, a: &Uint<L>) -> Uint<L> {
+        let exp = self.n.wrapping_sub(&Uint::from_u64(2));
+        self.pow_mod(a, &exp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// secp256k1 field prime
+    const P256K1: &str = "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC2F";
+    /// NIST P-256 field prime
+    const P256: &str = "FFFFFFFF00000001000000000000000000000000FFFFFFFFFFFFFFFFFFFFFFFF";
+
+    fn random_uint<const L: usize>(rng: &mut StdRng) -> Uint<L> {
+        Uint::from_limbs(std::array::from_fn(|_| rng.random()))
+    }
+
+    /// (a * b) mod n through a full product and shift-and-subtract
+    fn mul_mod_reference(a: &U256, b: &U256, n: &U256) -> U256 {
+        let product = a.mul_wide(b);
+        let mut r = U256::ZERO;
+        for i in (0..512).rev() {
+            let (doubled, carry) = r.shl1();
+            r = doubled;
+            r.limbs[0] |= product.bit(i) as u64;
+            if carry || r >= *n {
+                r = r.wrapping_sub(n);
+            }
+        }
+        r
+    }
+
+    #[test]
+    fn test_uint_arithmetic() {
+        let a = U256::from_limbs([u64::MAX, u64::MAX, 0, 0]);
+        let (sum, carry) = a.overflowing_add(&U256::ONE);
+        assert_eq!(sum, U256::from_limbs([0, 0, 1, 0]));
+        assert!(!carry);
+        assert_eq!(U256::MAX.overflowing_add(&U256::ONE), (U256::ZERO, true));
+        assert_eq!(U256::ZERO.overflowing_sub(&U256::ONE), (U256::MAX, true));
+        assert_eq!(sum.wrapping_sub(&U256::ONE), a);
+
+        let (lo, hi) = U256::MAX.widening_mul(&U256::MAX);
+        // (2^256 - 1)^2 = 2^512 - 2^257 + 1
+        assert_eq!(lo, U256::ONE);
+        assert_eq!(hi, U256::MAX.wrapping_sub(&U256::ONE));
+
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let x: u128 = rng.random::<u64>() as u128;
+            let y: u128 = rng.random::<u64>() as u128;
+            let p = U256::from_u64(x as u64).mul_wide(&U256::from_u64(y as u64));
+            assert_eq!(p.limbs()[0] as u128 | (p.limbs()[1] as u128) << 64, x * y);
+            assert!(p.limbs()[2..].iter().all(|&l| l == 0));
+        }
+
+        assert_eq!(
+            U256::from_hex("0x1_0000_0000_0000_0001"),
+            U256::from_limbs([1, 1, 0, 0])
+        );
+        assert!(U256::from_hex(P256K1) > U256::from_hex(P256));
+        assert_eq!(U256::from_hex(P256K1).bits(), 256);
+        let (lo, hi) = U512::from_halves(&U256::ONE, &U256::MAX).split();
+        assert_eq!((lo, hi), (U256::ONE, U256::MAX));
+    }
+
+    #[test]
+    fn test_wide_montgomery_mul() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for n in [P256K1, P256, "3B9A_CA07"] {
+            let n = U256::from_hex(n);
+            let montgomery = WideMontgomery::new(n);
+            for _ in 0..100 {
+                let a = montgomery.reduce_slow(&random_uint(&mut rng));
+                let b = montgomery.reduce_slow(&random_uint(&mut rng));
+                let (ma, mb) = (montgomery.from(&a), montgomery.from(&b));
+                assert_eq!(montgomery.to(&ma), a);
+                assert_eq!(
+                    montgomery.to(&montgomery.mul(&ma, &mb)),
+                    mul_mod_reference(&a, &b, &n)
+                );
+                let sum = montgomery.to(&montgomery.add(&ma, &mb));
+                assert_eq!(
+                    montgomery.to(&montgomery.sub(&montgomery.from(&sum), &mb)),
+                    a
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_wide_montgomery_pow_inv() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let p = U256::from_hex(P256K1);
+        let montgomery = WideMontgomery::new(p);
+        let p_minus_one = p.wrapping_sub(&U256::ONE);
+        assert_eq!(
+            montgomery.pow_mod(&U256::from_u64(2), &U256::from_u64(10)),
+            U256::from_u64(1024)
+        );
+        assert_eq!(
+            montgomery.pow_mod(&U256::from_u64(3), &U256::ZERO),
+            U256::ONE
+        );
+        for _ in 0..10 {
+            let a = montgomery.reduce_slow(&random_uint(&mut rng));
+            assert_eq!(montgomery.pow_mod(&a, &p_minus_one), U256::ONE);
+            let inv = montgomery.inv_mod(&a);
+            assert_eq!(mul_mod_reference(&a, &inv, &p), U256::ONE);
+        }
+
+        // small modulus cross-checked against u128
+        let q = 1_000_000_007u64;
+        let montgomery = WideMontgomery::<8>::new(U512::from_u64(q));
+        let inv = montgomery.inv_mod(&U512::from_u64(2));
+        assert_eq!(inv, U512::from_u64(500000004));
+        let big = U512::from_limbs([5, 6, 7, 8, 9, 10, 11, 12]);
+        let expected = (0..8).rev().fold(0u128, |acc, i| {
+            ((acc << 64) + big.limbs()[i] as u128) % q as u128
+        });
+        assert_eq!(
+            montgomery.to(&montgomery.from(&big)),
+            U512::from_u64(expected as u64)
+        );
+    }
+}